@@ -0,0 +1,86 @@
+use connect_4_core::book::OpeningBook;
+use connect_4_core::notation::{parse_moves, play_moves};
+use std::io::Write;
+use std::time::Instant;
+
+const USAGE: &str = "Usage:
+  connect4-book build <file> [--depth <n>] [--threads <n>]
+      Solves every position up to the depth (default 8) and writes them to the file. Expect
+      this to take hours on a single core, the threads split the work.
+  connect4-book info <file>
+      Checks the file and prints what it holds.
+  connect4-book lookup <file> [moves]
+      Prints the score of the position after the moves, given as column digits like 4453, and
+      of every move from there.";
+
+fn build(path: &str, mut args: impl Iterator<Item = String>) -> anyhow::Result<()> {
+    let mut depth = 8;
+    let mut threads = std::thread::available_parallelism().map_or(1, usize::from);
+    while let Some(arg) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("{arg} is missing its value.\n\n{USAGE}"))?;
+        match arg.as_str() {
+            "--depth" => depth = value.parse()?,
+            "--threads" => threads = value.parse()?,
+            _ => anyhow::bail!("Unknown option {arg}.\n\n{USAGE}"),
+        }
+    }
+
+    let started = Instant::now();
+    let book = OpeningBook::build(depth, threads, |done, total| {
+        print!("\rSolved {done} of {total} positions at depth {depth}...");
+        let _ = std::io::stdout().flush();
+    });
+    println!();
+    book.save(path)?;
+    println!(
+        "Wrote {} positions to {path} in {:.1}s.",
+        book.len(),
+        started.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+fn lookup(path: &str, moves: Option<String>) -> anyhow::Result<()> {
+    let book = OpeningBook::load(path)?;
+    let board = play_moves(&parse_moves(moves.as_deref().unwrap_or(""))?)?;
+    println!("{board}\n");
+    match book.score(&board) {
+        Some(score) => println!("Score for the player to move: {score}"),
+        None => println!(
+            "The position isn't in the book, it holds the first {} moves.",
+            book.depth()
+        ),
+    }
+    if let Some(scores) = book.analyze(&board) {
+        for (column, score) in scores.iter().enumerate() {
+            if let Some(score) = score {
+                println!("  column {}: {score}", column + 1);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let (Some(command), Some(path)) = (args.next(), args.next()) else {
+        println!("{USAGE}");
+        return Ok(());
+    };
+    match command.as_str() {
+        "build" => build(&path, args),
+        "info" => {
+            let book = OpeningBook::load(&path)?;
+            println!(
+                "{path} is intact and holds {} positions up to {} moves in.",
+                book.len(),
+                book.depth()
+            );
+            Ok(())
+        }
+        "lookup" => lookup(&path, args.next()),
+        _ => anyhow::bail!("Unknown command {command}.\n\n{USAGE}"),
+    }
+}
@@ -0,0 +1,148 @@
+use connect_4_core::board::Board;
+use connect_4_core::eval::{self, Features, Weights, FEATURE_NAMES};
+use connect_4_core::solver::ParallelSolver;
+use rand::rngs::StdRng;
+use rand::seq::IteratorRandom;
+use rand::SeedableRng;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "Usage: connect4-tune [options]
+  Solves random positions and fits the evaluation weights to their scores by least squares.
+  A fifth of the positions are held back to compare the fitted weights with the defaults.
+
+Options:
+  --positions <n>  positions to solve (default 10000)
+  --min-moves <n>  fewest random moves before a position (default 10)
+  --max-moves <n>  most random moves before a position (default 24)
+  --time <ms>      time the solver gets per position, slower ones are skipped (default 500)
+  --threads <n>    solver threads (default: all cores)
+  --seed <n>       seed for the random positions (default 0)";
+
+struct Options {
+    positions: usize,
+    min_moves: usize,
+    max_moves: usize,
+    time: Duration,
+    threads: usize,
+    seed: u64,
+}
+
+fn parse_options() -> anyhow::Result<Option<Options>> {
+    let mut options = Options {
+        positions: 10_000,
+        min_moves: 10,
+        max_moves: 24,
+        time: Duration::from_millis(500),
+        threads: std::thread::available_parallelism().map_or(1, usize::from),
+        seed: 0,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--help" || arg == "-h" {
+            return Ok(None);
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("{arg} is missing its value.\n\n{USAGE}"))?;
+        match arg.as_str() {
+            "--positions" => options.positions = value.parse()?,
+            "--min-moves" => options.min_moves = value.parse()?,
+            "--max-moves" => options.max_moves = value.parse()?,
+            "--time" => options.time = Duration::from_millis(value.parse()?),
+            "--threads" => options.threads = value.parse()?,
+            "--seed" => options.seed = value.parse()?,
+            _ => anyhow::bail!("Unknown option {arg}.\n\n{USAGE}"),
+        }
+    }
+    if options.min_moves > options.max_moves {
+        anyhow::bail!("--min-moves can't be more than --max-moves.");
+    }
+    Ok(Some(options))
+}
+
+/// A random position that's still going and that the player to move can't win on the spot,
+/// since any search finds those wins without help from the evaluation.
+fn random_position(rng: &mut StdRng, moves: usize) -> Option<Board> {
+    let mut board = Board::new();
+    for _ in 0..moves {
+        let column = board
+            .legal_moves()
+            .filter(|column| !board.is_winning_move(*column))
+            .choose(rng)?;
+        let _ = board.drop_piece(column);
+    }
+    let can_win = board
+        .legal_moves()
+        .any(|column| board.is_winning_move(column));
+    (!can_win).then_some(board)
+}
+
+/// The mean distance of the evaluation from the solver score, and how often the two agree on
+/// who's winning among the decided positions.
+fn report(name: &str, weights: &Weights, samples: &[(Features, i32)]) {
+    let mut error = 0.0;
+    let mut decided = 0;
+    let mut agreed = 0;
+    for (features, score) in samples {
+        let guess = features.score(weights);
+        error += (guess as f64 / 100.0 - *score as f64).abs();
+        if *score != 0 {
+            decided += 1;
+            if guess.signum() == score.signum() {
+                agreed += 1;
+            }
+        }
+    }
+    println!(
+        "{name}: mean error {:.2}, right side winning in {:.1}% of decided positions",
+        error / samples.len().max(1) as f64,
+        100.0 * agreed as f64 / decided.max(1) as f64
+    );
+}
+
+fn main() -> anyhow::Result<()> {
+    let Some(options) = parse_options()? else {
+        println!("{USAGE}");
+        return Ok(());
+    };
+
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut solver = ParallelSolver::new(options.threads);
+    let mut samples = Vec::with_capacity(options.positions);
+    let mut skipped = 0;
+    let started = Instant::now();
+    while samples.len() < options.positions {
+        let moves = (options.min_moves..=options.max_moves)
+            .choose(&mut rng)
+            .expect("the range isn't empty");
+        let Some(board) = random_position(&mut rng, moves) else {
+            continue;
+        };
+        match solver.solve_until(&board, Instant::now() + options.time) {
+            Some(score) => samples.push((Features::of(&board), score)),
+            None => skipped += 1,
+        }
+        print!(
+            "\rSolved {} of {} positions, skipped {skipped}...",
+            samples.len(),
+            options.positions
+        );
+        let _ = std::io::stdout().flush();
+    }
+    println!(" took {:.1}s.", started.elapsed().as_secs_f64());
+
+    let held_back = samples.split_off(samples.len() * 4 / 5);
+    let fitted = eval::fit(&samples).ok_or_else(|| {
+        anyhow::anyhow!("The positions don't pin every weight down, try solving more of them.")
+    })?;
+
+    println!("\nFitted weights:");
+    for (name, weight) in FEATURE_NAMES.iter().zip(fitted.values()) {
+        println!("  {name}: {weight}");
+    }
+    println!("\nOn {} held back positions:", held_back.len());
+    report("  defaults", &Weights::default(), &held_back);
+    report("  fitted  ", &fitted, &held_back);
+    Ok(())
+}
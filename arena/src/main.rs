@@ -0,0 +1,149 @@
+use crate::rating::estimate;
+use crate::tournament::{create_player, round_robin, Record, TournamentOptions};
+use std::time::{Duration, Instant};
+
+pub mod rating;
+pub mod tournament;
+
+const DEFAULT_PLAYERS: [&str; 8] = [
+    "random",
+    "heuristic",
+    "minimax:2",
+    "minimax:4",
+    "minimax:6",
+    "mcts:1000",
+    "mcts:10000",
+    "solver",
+];
+
+const USAGE: &str = "Usage: connect4-arena [options] [players...]
+
+Players: random, heuristic, minimax:<depth>, mcts:<iterations>, mcts-heuristic:<iterations>,
+solver and solver:<threads>. Without any, random, heuristic, minimax at depths 2, 4 and 6, mcts
+with 1000 and 10000 iterations and the solver play each other.
+
+Options:
+  --games <n>          games per pairing, alternating colours (default 10)
+  --move-time <ms>     thinking time per move, 0 for unlimited (default 1000)
+  --opening-plies <n>  random moves before each pair of games (default 2)
+  --seed <n>           seed for the openings and random players (default 0)
+  --threads <n>        pairings played at the same time (default: one per core)";
+
+fn parse_args() -> anyhow::Result<Option<(Vec<String>, TournamentOptions)>> {
+    let mut options = TournamentOptions {
+        games_per_pairing: 10,
+        move_time: Some(Duration::from_millis(1000)),
+        opening_plies: 2,
+        seed: 0,
+        threads: std::thread::available_parallelism().map_or(1, usize::from),
+    };
+    let mut specs = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow::anyhow!("{arg} is missing its value.\n\n{USAGE}"))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--games" => options.games_per_pairing = value()?.parse()?,
+            "--move-time" => {
+                options.move_time = match value()?.parse()? {
+                    0 => None,
+                    millis => Some(Duration::from_millis(millis)),
+                }
+            }
+            "--opening-plies" => options.opening_plies = value()?.parse()?,
+            "--seed" => options.seed = value()?.parse()?,
+            "--threads" => options.threads = value()?.parse()?,
+            _ if arg.starts_with("--") => anyhow::bail!("Unknown option {arg}.\n\n{USAGE}"),
+            _ => specs.push(arg),
+        }
+    }
+    if specs.is_empty() {
+        specs = DEFAULT_PLAYERS.map(String::from).to_vec();
+    }
+    if specs.len() < 2 {
+        anyhow::bail!("A tournament needs at least two players.");
+    }
+    Ok(Some((specs, options)))
+}
+
+fn percentage(record: Record) -> String {
+    if record.games() == 0 {
+        "-".to_string()
+    } else {
+        format!("{:.1}%", 100.0 * record.points() / record.games() as f64)
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let Some((specs, options)) = parse_args()? else {
+        println!("{USAGE}");
+        return Ok(());
+    };
+    let names = specs
+        .iter()
+        .map(|spec| Ok(create_player(spec, options.seed)?.name()))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let width = names.iter().map(String::len).max().unwrap_or(0).max(8);
+
+    let started = Instant::now();
+    let records = round_robin(&specs, &options, |first, second, record| {
+        println!(
+            "{} vs {}: {} won, {} drawn, {} lost",
+            names[first], names[second], record.wins, record.draws, record.losses
+        );
+    })?;
+    println!("\nFinished in {:.1}s.", started.elapsed().as_secs_f64());
+
+    println!("\nScore of each row against each column, draws count half:\n");
+    print!("{:width$}", "");
+    for name in &names {
+        print!("  {name:>width$}");
+    }
+    println!();
+    for (player, name) in names.iter().enumerate() {
+        print!("{name:width$}");
+        for (opponent, record) in records[player].iter().enumerate() {
+            let cell = if opponent == player {
+                "-".to_string()
+            } else {
+                percentage(*record)
+            };
+            print!("  {cell:>width$}");
+        }
+        println!();
+    }
+
+    let estimates = estimate(&records);
+    let mut standings = (0..names.len()).collect::<Vec<_>>();
+    standings.sort_by(|a, b| estimates[*b].rating.total_cmp(&estimates[*a].rating));
+
+    println!("\nRatings with 95% confidence intervals, the average player is rated 0:\n");
+    println!(
+        "{:width$}  {:>5}  {:>4}  {:>4}  {:>4}  {:>6}  {:>13}",
+        "Player", "Games", "W", "D", "L", "Score", "Rating"
+    );
+    for player in standings {
+        let mut total = Record::default();
+        for (opponent, record) in records[player].iter().enumerate() {
+            if opponent != player {
+                total.add(*record);
+            }
+        }
+        let estimate = estimates[player];
+        println!(
+            "{:width$}  {:>5}  {:>4}  {:>4}  {:>4}  {:>6}  {:>13}",
+            names[player],
+            total.games(),
+            total.wins,
+            total.draws,
+            total.losses,
+            percentage(total),
+            format!("{:+.0} ± {:.0}", estimate.rating, estimate.margin),
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use crate::tournament::Record;
+use std::f64::consts::LN_10;
+
+const ITERATIONS: usize = 200;
+/// Every pairing is treated as if it had one extra drawn game, which keeps the ratings of
+/// players that won or lost everything finite.
+const PRIOR_DRAWS: f64 = 1.0;
+const Z_95: f64 = 1.96;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Estimate {
+    pub rating: f64,
+    /// Half the width of the 95% confidence interval around the rating.
+    pub margin: f64,
+}
+
+fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf((opponent - rating) / 400.0))
+}
+
+/// Fits Elo ratings to the results of every pairing by maximum likelihood, centred so the
+/// average player is rated zero.
+pub fn estimate(records: &[Vec<Record>]) -> Vec<Estimate> {
+    let players = records.len();
+    let mut ratings = vec![0.0; players];
+
+    for _ in 0..ITERATIONS {
+        for player in 0..players {
+            let mut actual = 0.0;
+            let mut expected = 0.0;
+            let mut slope = 0.0;
+            for opponent in 0..players {
+                let record = records[player][opponent];
+                if opponent == player || record.games() == 0 {
+                    continue;
+                }
+                let games = record.games() as f64 + PRIOR_DRAWS;
+                let score = expected_score(ratings[player], ratings[opponent]);
+                actual += record.points() + PRIOR_DRAWS / 2.0;
+                expected += games * score;
+                slope += games * score * (1.0 - score) * LN_10 / 400.0;
+            }
+            if slope > 0.0 {
+                ratings[player] += ((actual - expected) / slope).clamp(-400.0, 400.0);
+            }
+        }
+        let mean = ratings.iter().sum::<f64>() / players.max(1) as f64;
+        ratings.iter_mut().for_each(|rating| *rating -= mean);
+    }
+
+    (0..players)
+        .map(|player| {
+            let mut total = Record::default();
+            for (opponent, record) in records[player].iter().enumerate() {
+                if opponent != player {
+                    total.add(*record);
+                }
+            }
+            Estimate {
+                rating: ratings[player],
+                margin: margin(total),
+            }
+        })
+        .collect()
+}
+
+/// Turns the standard error of a player's overall score into Elo points around that score.
+fn margin(record: Record) -> f64 {
+    let games = record.games() as f64 + PRIOR_DRAWS;
+    let score = (record.points() + PRIOR_DRAWS / 2.0) / games;
+    let variance = (record.wins as f64 * (1.0 - score).powi(2)
+        + (record.draws as f64 + PRIOR_DRAWS) * (0.5 - score).powi(2)
+        + record.losses as f64 * score.powi(2))
+        / games;
+    let standard_error = (variance / games).sqrt();
+    // the slope of the Elo curve at this score
+    let elo_per_score = 400.0 / (LN_10 * score * (1.0 - score));
+    Z_95 * standard_error * elo_per_score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairing(first: Record) -> Vec<Vec<Record>> {
+        vec![
+            vec![Record::default(), first],
+            vec![first.reversed(), Record::default()],
+        ]
+    }
+
+    #[test]
+    fn even_records_rate_players_equally() {
+        let estimates = estimate(&pairing(Record {
+            wins: 4,
+            draws: 2,
+            losses: 4,
+        }));
+        assert!(estimates[0].rating.abs() < 1e-6);
+        assert!(estimates[1].rating.abs() < 1e-6);
+    }
+
+    #[test]
+    fn ratings_follow_the_score_with_the_prior_draw() {
+        // 3.5 out of 5 points with the prior draw, a 70% score
+        let estimates = estimate(&pairing(Record {
+            wins: 3,
+            draws: 0,
+            losses: 1,
+        }));
+        let difference = 400.0 * (0.7f64 / 0.3).log10();
+        assert!((estimates[0].rating - difference / 2.0).abs() < 1e-6);
+        assert!((estimates[1].rating + difference / 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn clean_sweeps_stay_finite() {
+        let estimates = estimate(&pairing(Record {
+            wins: 10,
+            draws: 0,
+            losses: 0,
+        }));
+        assert!(estimates[0].rating.is_finite() && estimates[0].rating > 0.0);
+        assert!(estimates[0].margin.is_finite());
+    }
+
+    #[test]
+    fn margins_grow_with_decisive_games_and_shrink_with_more_games() {
+        let draws = Record {
+            wins: 0,
+            draws: 10,
+            losses: 0,
+        };
+        assert_eq!(margin(draws), 0.0);
+
+        // an even score over 11 games, half of them decisive either way
+        let decisive = Record {
+            wins: 5,
+            draws: 0,
+            losses: 5,
+        };
+        let standard_error = (2.5f64 / 11.0).sqrt() / 11f64.sqrt();
+        let expected = Z_95 * standard_error * 400.0 / (LN_10 * 0.25);
+        assert!((margin(decisive) - expected).abs() < 1e-9);
+
+        let more_games = Record {
+            wins: 50,
+            draws: 0,
+            losses: 50,
+        };
+        assert!(margin(more_games) < margin(decisive) / 2.0);
+    }
+}
@@ -0,0 +1,248 @@
+use connect_4_core::board::{self, Board, Outcome};
+use connect_4_core::bot::{
+    Clock, HeuristicPlayer, MinimaxPlayer, Player, RandomPlayer, SolverPlayer,
+};
+use connect_4_core::mcts::{Budget, MctsOptions, MctsPlayer, Playout};
+use rand::rngs::StdRng;
+use rand::seq::IteratorRandom;
+use rand::SeedableRng;
+use std::sync::{mpsc, Mutex};
+use std::time::Duration;
+
+pub type BoxedPlayer = Box<dyn Player + Send>;
+
+pub struct TournamentOptions {
+    /// Games each pair of players plays, alternating colours, an odd count gives the first
+    /// player of the pairing the extra game with the first move.
+    pub games_per_pairing: u32,
+    pub move_time: Option<Duration>,
+    /// Random moves played before each pair of games so deterministic players don't keep
+    /// repeating the same game.
+    pub opening_plies: u32,
+    pub seed: u64,
+    pub threads: usize,
+}
+
+/// Games between two players from the point of view of the first one.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Record {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Record {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Wins count as one point and draws as half.
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+
+    pub fn add(&mut self, other: Record) {
+        self.wins += other.wins;
+        self.draws += other.draws;
+        self.losses += other.losses;
+    }
+
+    pub fn reversed(&self) -> Record {
+        Record {
+            wins: self.losses,
+            draws: self.draws,
+            losses: self.wins,
+        }
+    }
+}
+
+/// Builds a player from its name on the command line, like `minimax:4`.
+pub fn create_player(spec: &str, seed: u64) -> anyhow::Result<BoxedPlayer> {
+    let (kind, argument) = match spec.split_once(':') {
+        Some((kind, argument)) => (kind, Some(argument)),
+        None => (spec, None),
+    };
+    Ok(match (kind, argument) {
+        ("random", None) => Box::new(RandomPlayer::seeded(seed)),
+        ("heuristic", None) => Box::new(HeuristicPlayer),
+        ("minimax", Some(depth)) => Box::new(MinimaxPlayer::new(depth.parse()?)),
+        ("mcts", Some(iterations)) | ("mcts-heuristic", Some(iterations)) => {
+            let options = MctsOptions {
+                budget: Budget::Iterations(iterations.parse()?),
+                playout: if kind == "mcts" {
+                    Playout::Random
+                } else {
+                    Playout::Heuristic
+                },
+                ..MctsOptions::default()
+            };
+            Box::new(MctsPlayer::seeded(options, seed))
+        }
+        ("solver", None) => Box::new(SolverPlayer::new()),
+        ("solver", Some(threads)) => Box::new(SolverPlayer::with_threads(threads.parse()?)),
+        _ => anyhow::bail!(
+            "Unknown player {spec}, expected random, heuristic, minimax:<depth>, \
+            mcts:<iterations>, mcts-heuristic:<iterations>, solver or solver:<threads>."
+        ),
+    })
+}
+
+/// Plays every player against every other one, returning the record of each row player against
+/// each column player. `on_result` is called as pairings finish.
+pub fn round_robin(
+    specs: &[String],
+    options: &TournamentOptions,
+    mut on_result: impl FnMut(usize, usize, Record),
+) -> anyhow::Result<Vec<Vec<Record>>> {
+    let mut pairings = Vec::new();
+    for first in 0..specs.len() {
+        for second in first + 1..specs.len() {
+            pairings.push((first, second));
+        }
+    }
+    // taken from the back, so the first pairings get played first
+    pairings.reverse();
+    let pairings = Mutex::new(pairings);
+    let (result_sender, result_receiver) = mpsc::channel();
+
+    let mut records = vec![vec![Record::default(); specs.len()]; specs.len()];
+    std::thread::scope(|scope| -> anyhow::Result<()> {
+        for _ in 0..options.threads.max(1) {
+            let result_sender = result_sender.clone();
+            let pairings = &pairings;
+            scope.spawn(move || loop {
+                let Some((first, second)) = pairings.lock().unwrap().pop() else {
+                    return;
+                };
+                let seed = options.seed ^ ((first as u64) << 32 | second as u64);
+                let record = play_match(&specs[first], &specs[second], options, seed);
+                if result_sender.send((first, second, record)).is_err() {
+                    return;
+                }
+            });
+        }
+        drop(result_sender);
+
+        for (first, second, record) in result_receiver {
+            let record = record?;
+            records[first][second].add(record);
+            records[second][first].add(record.reversed());
+            on_result(first, second, record);
+        }
+        Ok(())
+    })?;
+    Ok(records)
+}
+
+fn play_match(
+    first_spec: &str,
+    second_spec: &str,
+    options: &TournamentOptions,
+    seed: u64,
+) -> anyhow::Result<Record> {
+    let mut first = create_player(first_spec, seed)?;
+    let mut second = create_player(second_spec, seed.wrapping_add(1))?;
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let mut record = Record::default();
+    let mut opening = Board::new();
+    for game in 0..options.games_per_pairing {
+        let first_goes_first = game % 2 == 0;
+        if first_goes_first {
+            opening = random_opening(&mut rng, options.opening_plies);
+        }
+        let outcome = if first_goes_first {
+            play_game(first.as_mut(), second.as_mut(), opening, options.move_time)
+        } else {
+            play_game(second.as_mut(), first.as_mut(), opening, options.move_time)
+        };
+        match outcome {
+            Outcome::Draw => record.draws += 1,
+            Outcome::Win(winner) if (winner == board::Player::First) == first_goes_first => {
+                record.wins += 1
+            }
+            Outcome::Win(_) => record.losses += 1,
+        }
+    }
+    Ok(record)
+}
+
+fn random_opening(rng: &mut StdRng, plies: u32) -> Board {
+    let mut board = Board::new();
+    for _ in 0..plies {
+        // the opening should never decide the game by itself
+        let column = board
+            .legal_moves()
+            .filter(|column| !board.is_winning_move(*column))
+            .choose(rng);
+        match column {
+            Some(column) => {
+                let _ = board.drop_piece(column);
+            }
+            None => break,
+        }
+    }
+    board
+}
+
+fn play_game(
+    first: &mut dyn Player,
+    second: &mut dyn Player,
+    mut board: Board,
+    move_time: Option<Duration>,
+) -> Outcome {
+    loop {
+        if let Some(outcome) = board.outcome() {
+            return outcome;
+        }
+        let player: &mut dyn Player = match board.next_player() {
+            board::Player::First => &mut *first,
+            board::Player::Second => &mut *second,
+        };
+        let clock = move_time.map_or_else(Clock::unlimited, Clock::new);
+        let column = player.choose_move(&board, &clock);
+        if board.drop_piece(column).is_err() {
+            // an illegal move forfeits the game
+            return Outcome::Win(board.next_player().other());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_count_points_from_the_first_players_side() {
+        let mut record = Record {
+            wins: 3,
+            draws: 2,
+            losses: 1,
+        };
+        assert_eq!(record.games(), 6);
+        assert_eq!(record.points(), 4.0);
+
+        let reversed = record.reversed();
+        assert_eq!((reversed.wins, reversed.draws, reversed.losses), (1, 2, 3));
+        assert_eq!(reversed.points(), 2.0);
+
+        record.add(reversed);
+        assert_eq!((record.wins, record.draws, record.losses), (4, 4, 4));
+        assert_eq!(record.points(), record.games() as f64 / 2.0);
+    }
+
+    #[test]
+    fn matches_play_exactly_the_requested_games() {
+        for games_per_pairing in [0, 1, 2, 5] {
+            let options = TournamentOptions {
+                games_per_pairing,
+                move_time: None,
+                opening_plies: 2,
+                seed: 7,
+                threads: 1,
+            };
+            let record = play_match("random", "random", &options, options.seed).unwrap();
+            assert_eq!(record.games(), games_per_pairing);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use client::game;
+use client::mediator::{PacketMessage, WindowMessage};
+use connect_4_core::board::{Board, Player};
+use connect_4_core::logger::{system_logger, LoggerOptions};
+use log::LevelFilter;
+use std::io::BufRead;
+use tokio::runtime::Builder;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::LocalSet;
+
+enum CliState {
+    Login,
+    Lobby,
+    Queued,
+    Game { board: Board, me: Option<Player> },
+}
+
+struct Cli {
+    state: CliState,
+    packet_sender: UnboundedSender<PacketMessage>,
+}
+
+impl Cli {
+    fn send(&self, message: PacketMessage) {
+        let _ = self.packet_sender.send(message);
+    }
+
+    fn log_in(&self, username: &str) {
+        self.send(PacketMessage::RequestUsername {
+            username: username.to_string(),
+        });
+    }
+
+    fn queue(&mut self) {
+        self.state = CliState::Queued;
+        println!("Looking for a game...");
+        self.send(PacketMessage::SearchForGame);
+    }
+
+    fn prompt_turn(&self) {
+        if let CliState::Game {
+            board,
+            me: Some(me),
+        } = &self.state
+        {
+            if board.next_player() == *me {
+                println!("Your move, enter a column from 1 to 7:");
+            } else {
+                println!("Waiting for your opponent...");
+            }
+        }
+    }
+
+    /// Handles a line typed by the player, returning false once they asked to quit.
+    fn handle_line(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        if line == "quit" {
+            return false;
+        }
+        if let Some(message) = line.strip_prefix("/say ") {
+            self.send(PacketMessage::Chat {
+                message: message.to_string(),
+            });
+            return true;
+        }
+
+        match &self.state {
+            CliState::Login if !line.is_empty() => self.log_in(line),
+            CliState::Lobby if line == "play" => self.queue(),
+            CliState::Lobby => println!("Type `play` to look for a game or `quit` to exit."),
+            CliState::Queued if line == "cancel" => self.send(PacketMessage::CancelSearch),
+            CliState::Game {
+                board,
+                me: Some(me),
+            } => {
+                if board.next_player() != *me {
+                    println!("It's not your turn.");
+                    return true;
+                }
+                match line.parse::<u8>() {
+                    Ok(column @ 1..=7) if board.can_drop(column - 1) => {
+                        self.send(PacketMessage::PlacePieceInGame { column: column - 1 });
+                    }
+                    Ok(column @ 1..=7) => println!("Column {column} is full."),
+                    _ => println!("Enter a column from 1 to 7."),
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn handle_window_message(&mut self, message: WindowMessage) {
+        match message {
+            WindowMessage::UsernameResult { success, username } => {
+                if success {
+                    println!("Logged in as {username}.");
+                    self.queue();
+                } else {
+                    println!("Couldn't log in as `{username}`, it's taken or isn't alphanumeric.");
+                    println!("Enter another username:");
+                }
+            }
+            WindowMessage::QueueUpdate {
+                position,
+                estimated_wait_seconds,
+            } => {
+                println!(
+                    "Position in queue: {position}, estimated wait ~{estimated_wait_seconds}s."
+                );
+            }
+            WindowMessage::SearchCancelled => {
+                self.state = CliState::Lobby;
+                println!("Search cancelled, type `play` to look again.");
+            }
+            WindowMessage::TransferToGame => {
+                self.state = CliState::Game {
+                    board: Board::new(),
+                    me: None,
+                };
+                println!("Game found, waiting for your opponent...");
+            }
+            WindowMessage::NotifyOpponentJoin {
+                username,
+                i_go_first,
+                opponent_is_bot,
+                ..
+            } => {
+                if let CliState::Game { board, me } = &mut self.state {
+                    *me = Some(if i_go_first {
+                        Player::First
+                    } else {
+                        Player::Second
+                    });
+                    println!(
+                        "Playing against {username}{}, you are {}.",
+                        if opponent_is_bot { " (bot)" } else { "" },
+                        if i_go_first { "x" } else { "o" }
+                    );
+                    println!("{board}");
+                }
+                self.prompt_turn();
+            }
+            WindowMessage::PlacePieceInGame { column, .. } => {
+                if let CliState::Game { board, .. } = &mut self.state {
+                    if board.drop_piece(column).is_ok() {
+                        println!("{board}");
+                    }
+                }
+                self.prompt_turn();
+            }
+            WindowMessage::Chat {
+                username, message, ..
+            } => println!("{username}: {message}"),
+            WindowMessage::ChatRejected { reason, .. } => println!("* {reason}"),
+            WindowMessage::WinGame => self.finish_game("You won!"),
+            WindowMessage::LoseGame => self.finish_game("You lost."),
+            WindowMessage::DrawGame => self.finish_game("The game ended in a draw."),
+            WindowMessage::ExitToLobby => self.finish_game("Your opponent left the game."),
+            _ => {}
+        }
+    }
+
+    fn finish_game(&mut self, result: &str) {
+        self.state = CliState::Lobby;
+        println!("{result}");
+        println!("Type `play` to look for another game or `quit` to exit.");
+    }
+}
+
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    system_logger(LoggerOptions {
+        log_level: LevelFilter::Warn,
+        log_file: None,
+    })?
+    .apply()?;
+
+    let (packet_sender, packet_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let (window_sender, mut window_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let rt = Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|err| {
+            connect_4_core::drax::err_explain!(format!("Error setting up thread builder: {err}"))
+        })?;
+
+    std::thread::spawn(move || {
+        let local = LocalSet::new();
+
+        local.spawn_local(game::spawn_game_client(window_sender, packet_receiver));
+
+        rt.block_on(local);
+    });
+
+    let (line_sender, mut line_receiver) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line_sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut cli = Cli {
+        state: CliState::Login,
+        packet_sender,
+    };
+    match std::env::args().nth(1) {
+        Some(username) => cli.log_in(&username),
+        None => println!("Enter a username:"),
+    }
+
+    loop {
+        tokio::select! {
+            message = window_receiver.recv() => match message {
+                Some(message) => cli.handle_window_message(message),
+                None => {
+                    println!("Disconnected from the server.");
+                    break;
+                }
+            },
+            line = line_receiver.recv() => match line {
+                Some(line) => {
+                    if !cli.handle_line(&line) {
+                        break;
+                    }
+                }
+                None => break,
+            },
+        }
+    }
+    Ok(())
+}
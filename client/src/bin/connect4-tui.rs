@@ -0,0 +1,481 @@
+use client::game;
+use client::mediator::{PacketMessage, WindowMessage};
+use connect_4_core::board::{Board, Player, HEIGHT, WIDTH};
+use connect_4_core::logger::{system_logger, LoggerOptions};
+use crossterm::cursor::Show;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use log::LevelFilter;
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Span, Spans};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Wrap};
+use ratatui::{Frame, Terminal};
+use tokio::runtime::Builder;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::LocalSet;
+
+const CHAT_HISTORY: usize = 50;
+
+enum Screen {
+    Login,
+    Lobby { queued: bool },
+    Game,
+}
+
+struct Tui {
+    screen: Screen,
+    packet_sender: UnboundedSender<PacketMessage>,
+    username_input: String,
+    chat_input: String,
+    chat_focused: bool,
+    status: String,
+    lobby_chat: Vec<String>,
+    game_chat: Vec<String>,
+    board: Board,
+    moves: Vec<u8>,
+    me: Option<Player>,
+    opponent: String,
+    selected_column: u8,
+}
+
+impl Tui {
+    fn new(packet_sender: UnboundedSender<PacketMessage>) -> Self {
+        Self {
+            screen: Screen::Login,
+            packet_sender,
+            username_input: String::new(),
+            chat_input: String::new(),
+            chat_focused: false,
+            status: "Enter a username and press enter.".to_string(),
+            lobby_chat: vec![],
+            game_chat: vec![],
+            board: Board::new(),
+            moves: vec![],
+            me: None,
+            opponent: String::new(),
+            selected_column: 3,
+        }
+    }
+
+    fn send(&self, message: PacketMessage) {
+        let _ = self.packet_sender.send(message);
+    }
+
+    fn my_turn(&self) -> bool {
+        matches!(self.screen, Screen::Game)
+            && self.board.outcome().is_none()
+            && self.me == Some(self.board.next_player())
+    }
+
+    /// Handles a key press, returning false once the player asked to quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.kind != KeyEventKind::Press {
+            return true;
+        }
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return false;
+        }
+
+        if self.chat_focused {
+            match key.code {
+                KeyCode::Esc | KeyCode::Tab => self.chat_focused = false,
+                KeyCode::Enter => {
+                    let message = self.chat_input.drain(..).collect::<String>();
+                    if !message.trim().is_empty() {
+                        self.send(PacketMessage::Chat { message });
+                    }
+                }
+                KeyCode::Backspace => {
+                    self.chat_input.pop();
+                }
+                KeyCode::Char(character) => self.chat_input.push(character),
+                _ => {}
+            }
+            return true;
+        }
+
+        match self.screen {
+            Screen::Login => match key.code {
+                KeyCode::Esc => return false,
+                KeyCode::Enter if !self.username_input.is_empty() => {
+                    self.send(PacketMessage::RequestUsername {
+                        username: self.username_input.clone(),
+                    });
+                }
+                KeyCode::Backspace => {
+                    self.username_input.pop();
+                }
+                KeyCode::Char(character) if character.is_alphanumeric() => {
+                    self.username_input.push(character)
+                }
+                _ => {}
+            },
+            Screen::Lobby { queued } => match key.code {
+                KeyCode::Esc | KeyCode::Char('q') => return false,
+                KeyCode::Tab => self.chat_focused = true,
+                KeyCode::Char('p') if !queued => {
+                    self.screen = Screen::Lobby { queued: true };
+                    self.status = "Looking for a game...".to_string();
+                    self.send(PacketMessage::SearchForGame);
+                }
+                KeyCode::Char('c') if queued => self.send(PacketMessage::CancelSearch),
+                _ => {}
+            },
+            Screen::Game => match key.code {
+                KeyCode::Esc => return false,
+                KeyCode::Tab => self.chat_focused = true,
+                KeyCode::Left => self.selected_column = self.selected_column.saturating_sub(1),
+                KeyCode::Right => {
+                    self.selected_column = (self.selected_column + 1).min(WIDTH as u8 - 1)
+                }
+                KeyCode::Char(digit @ '1'..='7') => self.selected_column = digit as u8 - b'1',
+                KeyCode::Enter | KeyCode::Down | KeyCode::Char(' ')
+                    if self.my_turn() && self.board.can_drop(self.selected_column) =>
+                {
+                    self.send(PacketMessage::PlacePieceInGame {
+                        column: self.selected_column,
+                    });
+                }
+                _ => {}
+            },
+        }
+        true
+    }
+
+    fn handle_window_message(&mut self, message: WindowMessage) {
+        match message {
+            WindowMessage::UsernameResult { success, username } => {
+                if success {
+                    self.screen = Screen::Lobby { queued: false };
+                    self.status = format!("Logged in as {username}.");
+                } else {
+                    self.status = format!("`{username}` is already taken, try another username.");
+                }
+            }
+            WindowMessage::QueueUpdate {
+                position,
+                estimated_wait_seconds,
+            } => {
+                self.status = format!(
+                    "Position in queue: {position}, estimated wait ~{estimated_wait_seconds}s."
+                );
+            }
+            WindowMessage::SearchCancelled => {
+                self.screen = Screen::Lobby { queued: false };
+                self.status = "Search cancelled.".to_string();
+            }
+            WindowMessage::TransferToGame => {
+                self.screen = Screen::Game;
+                self.board = Board::new();
+                self.moves.clear();
+                self.game_chat.clear();
+                self.me = None;
+                self.opponent.clear();
+                self.selected_column = 3;
+                self.status = "Waiting for your opponent...".to_string();
+            }
+            WindowMessage::NotifyOpponentJoin {
+                username,
+                i_go_first,
+                opponent_is_bot,
+                ..
+            } => {
+                self.me = Some(if i_go_first {
+                    Player::First
+                } else {
+                    Player::Second
+                });
+                self.opponent = if opponent_is_bot {
+                    format!("{username} (bot)")
+                } else {
+                    username
+                };
+                self.status = format!("Playing against {}.", self.opponent);
+            }
+            WindowMessage::PlacePieceInGame { column, .. } => {
+                if self.board.drop_piece(column).is_ok() {
+                    self.moves.push(column);
+                }
+            }
+            WindowMessage::Chat {
+                username,
+                message,
+                in_game,
+            } => self.push_chat_line(in_game, format!("{username}: {message}")),
+            WindowMessage::ChatRejected { reason, in_game } => {
+                self.push_chat_line(in_game, format!("* {reason}"))
+            }
+            WindowMessage::WinGame => self.finish_game("You won!"),
+            WindowMessage::LoseGame => self.finish_game("You lost."),
+            WindowMessage::DrawGame => self.finish_game("The game ended in a draw."),
+            WindowMessage::ExitToLobby => self.finish_game("Your opponent left the game."),
+            _ => {}
+        }
+    }
+
+    fn finish_game(&mut self, result: &str) {
+        self.screen = Screen::Lobby { queued: false };
+        self.status = format!("{result} Press p to play again.");
+    }
+
+    fn push_chat_line(&mut self, in_game: bool, line: String) {
+        let log = if in_game {
+            &mut self.game_chat
+        } else {
+            &mut self.lobby_chat
+        };
+        log.push(line);
+        if log.len() > CHAT_HISTORY {
+            log.remove(0);
+        }
+    }
+
+    fn draw<B: Backend>(&self, frame: &mut Frame<B>) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(10), Constraint::Length(3)].as_ref())
+            .split(frame.size());
+
+        match self.screen {
+            Screen::Login => {
+                let login = Paragraph::new(self.username_input.as_str())
+                    .block(Block::default().borders(Borders::ALL).title("Username"));
+                frame.render_widget(login, centered(rows[0], 40, 3));
+            }
+            Screen::Lobby { queued } => {
+                let columns = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Length(30), Constraint::Min(20)].as_ref())
+                    .split(rows[0]);
+                let menu = if queued {
+                    vec!["c  cancel search", "tab  chat", "q  quit"]
+                } else {
+                    vec!["p  look for a game", "tab  chat", "q  quit"]
+                };
+                let menu = Paragraph::new(menu.into_iter().map(Spans::from).collect::<Vec<_>>())
+                    .block(Block::default().borders(Borders::ALL).title("Lobby"));
+                frame.render_widget(menu, columns[0]);
+                frame.render_widget(chat_log(&self.lobby_chat, columns[1]), columns[1]);
+            }
+            Screen::Game => {
+                let columns = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints(
+                        [
+                            Constraint::Length(WIDTH as u16 * 3 + 2),
+                            Constraint::Length(16),
+                            Constraint::Min(20),
+                        ]
+                        .as_ref(),
+                    )
+                    .split(rows[0]);
+                frame.render_widget(self.board_widget(), columns[0]);
+                frame.render_widget(self.history_widget(), columns[1]);
+                frame.render_widget(chat_log(&self.game_chat, columns[2]), columns[2]);
+            }
+        }
+
+        let (title, input) = if self.chat_focused {
+            ("Chat (esc to leave)", self.chat_input.as_str())
+        } else {
+            ("Status", self.status.as_str())
+        };
+        let footer =
+            Paragraph::new(input).block(Block::default().borders(Borders::ALL).title(title));
+        frame.render_widget(footer, rows[1]);
+        if self.chat_focused {
+            frame.set_cursor(rows[1].x + 1 + self.chat_input.len() as u16, rows[1].y + 1);
+        }
+    }
+
+    fn board_widget(&self) -> Paragraph<'_> {
+        let my_colour = self.me.map(player_colour).unwrap_or(Color::Gray);
+        let landing_row = self.board.height(self.selected_column as usize);
+
+        let mut lines = vec![Spans::from(
+            (0..WIDTH as u8)
+                .map(|column| {
+                    if column == self.selected_column && self.my_turn() {
+                        Span::styled(" v ", Style::default().fg(my_colour))
+                    } else {
+                        Span::raw("   ")
+                    }
+                })
+                .collect::<Vec<_>>(),
+        )];
+        for row in (0..HEIGHT).rev() {
+            lines.push(Spans::from(
+                (0..WIDTH)
+                    .map(|column| match self.board.get(column, row) {
+                        Some(player) => Span::styled(
+                            " O ",
+                            Style::default()
+                                .fg(player_colour(player))
+                                .add_modifier(Modifier::BOLD),
+                        ),
+                        None if self.my_turn()
+                            && column == self.selected_column as usize
+                            && row == landing_row =>
+                        {
+                            Span::styled(" o ", Style::default().fg(my_colour))
+                        }
+                        None => Span::styled(" . ", Style::default().fg(Color::DarkGray)),
+                    })
+                    .collect::<Vec<_>>(),
+            ));
+        }
+        lines.push(Spans::from(
+            (1..=WIDTH)
+                .map(|column| format!(" {column} "))
+                .collect::<String>(),
+        ));
+
+        let title = if self.opponent.is_empty() {
+            "Board".to_string()
+        } else {
+            format!("vs {}", self.opponent)
+        };
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title))
+    }
+
+    fn history_widget(&self) -> List<'_> {
+        let items = self
+            .moves
+            .chunks(2)
+            .enumerate()
+            .map(|(turn, pair)| {
+                let columns = pair
+                    .iter()
+                    .map(|column| (column + 1).to_string())
+                    .collect::<Vec<_>>()
+                    .join("  ");
+                ListItem::new(format!("{:>2}. {columns}", turn + 1))
+            })
+            .collect::<Vec<_>>();
+        List::new(items).block(Block::default().borders(Borders::ALL).title("Moves"))
+    }
+}
+
+fn player_colour(player: Player) -> Color {
+    match player {
+        Player::First => Color::Red,
+        Player::Second => Color::Yellow,
+    }
+}
+
+fn chat_log(lines: &[String], area: Rect) -> Paragraph<'_> {
+    let visible = area.height.saturating_sub(2) as usize;
+    let lines = lines
+        .iter()
+        .skip(lines.len().saturating_sub(visible))
+        .map(|line| Spans::from(line.as_str()))
+        .collect::<Vec<_>>();
+    Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title("Chat"))
+}
+
+fn centered(area: Rect, width: u16, height: u16) -> Rect {
+    Rect {
+        x: area.x + area.width.saturating_sub(width) / 2,
+        y: area.y + area.height.saturating_sub(height) / 2,
+        width: width.min(area.width),
+        height: height.min(area.height),
+    }
+}
+
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    system_logger(LoggerOptions {
+        log_level: LevelFilter::Off,
+        log_file: None,
+    })?
+    .apply()?;
+
+    let (packet_sender, packet_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let (window_sender, mut window_receiver) = tokio::sync::mpsc::unbounded_channel();
+    let rt = Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|err| {
+            connect_4_core::drax::err_explain!(format!("Error setting up thread builder: {err}"))
+        })?;
+
+    std::thread::spawn(move || {
+        let local = LocalSet::new();
+
+        local.spawn_local(game::spawn_game_client(window_sender, packet_receiver));
+
+        rt.block_on(local);
+    });
+
+    let (event_sender, mut event_receiver) = tokio::sync::mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        while let Ok(event) = crossterm::event::read() {
+            if event_sender.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    let _guard = TerminalGuard::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+
+    let mut tui = Tui::new(packet_sender);
+    let result = loop {
+        if let Err(err) = terminal.draw(|frame| tui.draw(frame)) {
+            break Err(err.into());
+        }
+        tokio::select! {
+            message = window_receiver.recv() => match message {
+                Some(message) => tui.handle_window_message(message),
+                None => break Err(anyhow::anyhow!("Disconnected from the server.")),
+            },
+            event = event_receiver.recv() => match event {
+                Some(Event::Key(key)) => {
+                    if !tui.handle_key(key) {
+                        break Ok(());
+                    }
+                }
+                Some(_) => {}
+                None => break Ok(()),
+            },
+        }
+    };
+
+    result
+}
+
+/// Keeps the terminal in raw mode on the alternate screen until dropped, so an early return or a
+/// panic can't leave the shell unusable.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> anyhow::Result<Self> {
+        enable_raw_mode()?;
+        let guard = TerminalGuard;
+        crossterm::execute!(std::io::stdout(), EnterAlternateScreen)?;
+        // the panic message would be drawn on the alternate screen and vanish with it
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            default_hook(info);
+        }));
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = crossterm::execute!(std::io::stdout(), LeaveAlternateScreen, Show);
+}
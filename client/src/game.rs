@@ -1,304 +1,207 @@
-use crate::mediator::{ClientState, PacketMessage, WindowMessage};
-use connect_4_core::encode;
-use connect_4_core::packets::*;
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::net::TcpStream;
-use tokio::runtime::Builder;
-use tokio::sync::mpsc::error::TryRecvError;
+use crate::mediator::{PacketMessage, WindowMessage};
+use connect_4_client::{Connection, Event, GameOutcome};
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
-use tokio::sync::RwLock;
-use tokio::task::LocalSet;
 
 const SERVER_HOST: &str = "localhost:3000";
 
-#[derive(Debug)]
-enum InnerPacket {
-    Login(ClientboundLoginPacket),
-    Lobby(ClientboundLobbyPacket),
-    Game(ClientboundGamePacket),
-}
-
-macro_rules! handle_error_quit {
-    ($maybe_err:expr) => {
-        match $maybe_err {
-            Ok(val) => val,
-            Err(err) => {
-                log::error!("Error during packet reads: {}", err);
-                return;
-            }
-        }
-    };
-}
-
 pub async fn spawn_game_client(
     message_sender: UnboundedSender<WindowMessage>,
     mut message_receiver: UnboundedReceiver<PacketMessage>,
 ) -> anyhow::Result<()> {
-    let client = TcpStream::connect(SERVER_HOST).await?;
-    let (mut read, mut write) = client.into_split();
-    let client_state = Arc::new(RwLock::new(ClientState::Login));
-    let mut tick_interval = tokio::time::interval(Duration::from_millis(50));
-    encode!(
-        write,
-        ServerboundLoginPacket,
-        ServerboundLoginPacket::KeepAlive
-    );
-
-    let (packet_sender, mut packet_receiver) = tokio::sync::mpsc::unbounded_channel();
-
-    let rt = Builder::new_current_thread()
-        .enable_all()
-        .build()
-        .map_err(|err| {
-            connect_4_core::drax::err_explain!(format!("Error setting up thread builder: {err}"))
-        })?;
-
-    let client_state_clone = client_state.clone();
-    std::thread::spawn(move || {
-        let client_state = client_state_clone;
-        use connect_4_core::drax::prelude::DraxReadExt;
-
-        let local = LocalSet::new();
-
-        local.spawn_local(async move {
-            loop {
-                let read_client_state = client_state.read().await;
-                let current_state = *read_client_state;
-                drop(read_client_state);
-                handle_error_quit!(packet_sender.send(match current_state {
-                    ClientState::Login => {
-                        let next_packet = handle_error_quit!(
-                            read.decode_component::<(), ClientboundLoginPacket>(&mut ())
-                                .await
-                        );
-                        InnerPacket::Login(next_packet)
-                    }
-                    ClientState::Lobby => {
-                        let next_packet = handle_error_quit!(
-                            read.decode_component::<(), ClientboundLobbyPacket>(&mut ())
-                                .await
-                        );
-                        InnerPacket::Lobby(next_packet)
-                    }
-                    ClientState::Game => {
-                        let next_packet = handle_error_quit!(
-                            read.decode_component::<(), ClientboundGamePacket>(&mut ())
-                                .await
-                        );
-                        InnerPacket::Game(next_packet)
-                    }
-                }));
-            }
-        });
-
-        rt.block_on(local);
-    });
-
-    let mut pending_placement_transactions = HashMap::new();
-    let mut pending_username_transactions = HashMap::new();
-
-    macro_rules! read_receiver {
-        ($into:ident, $receiver:ident) => {
-            let mut $into = vec![];
-            loop {
-                let message = $receiver.try_recv();
-                match message {
-                    Ok(message) => $into.push(message),
-                    Err(err) => match err {
-                        TryRecvError::Empty => break,
-                        TryRecvError::Disconnected => return Ok(()),
-                    },
-                }
-            }
-        };
-    }
+    let mut connection = Connection::connect(SERVER_HOST).await?;
 
     loop {
-        read_receiver!(window_messages, message_receiver);
-        read_receiver!(packets, packet_receiver);
-
-        while let Some(next_message) = window_messages.pop() {
-            match next_message {
-                PacketMessage::RequestUsername { username } => {
-                    let next_transaction_id = pending_username_transactions
-                        .keys()
-                        .max()
-                        .cloned()
-                        .unwrap_or(0i32)
-                        + 1;
-                    pending_username_transactions.insert(next_transaction_id, username.clone());
-                    encode!(
-                        write,
-                        ServerboundLoginPacket,
-                        ServerboundLoginPacket::RequestUsername {
-                            transaction_id: next_transaction_id,
-                            username: username.clone()
-                        }
-                    );
-                }
-                PacketMessage::SearchForGame => {
-                    encode!(
-                        write,
-                        ServerboundLobbyPacket,
-                        ServerboundLobbyPacket::RequestGame
-                    );
+        tokio::select! {
+            message = message_receiver.recv() => {
+                let Some(message) = message else {
+                    return Ok(());
+                };
+                if let PacketMessage::RequestUsername { username } = message {
+                    let success = connection.login(&username).await?;
+                    message_sender.send(WindowMessage::UsernameResult { success, username })?;
+                } else {
+                    forward_packet_message(&connection, message)?;
                 }
-                PacketMessage::PlacePieceInGame { column } => {
-                    let next_transaction_id = pending_placement_transactions
-                        .keys()
-                        .max()
-                        .cloned()
-                        .unwrap_or(0i32)
-                        + 1;
-                    pending_placement_transactions.insert(next_transaction_id, column);
-                    encode!(
-                        write,
-                        ServerboundGamePacket,
-                        ServerboundGamePacket::PlacePiece {
-                            transaction_id: next_transaction_id,
-                            column
-                        }
-                    );
+            }
+            event = connection.next_event() => {
+                let Some(event) = event else {
+                    return Ok(());
+                };
+                if let Some(message) = window_message(event) {
+                    message_sender.send(message)?;
                 }
             }
         }
+    }
+}
 
-        let read_client_state = client_state.read().await;
-        let current_state = *read_client_state;
-        drop(read_client_state);
+fn forward_packet_message(connection: &Connection, message: PacketMessage) -> anyhow::Result<()> {
+    match message {
+        PacketMessage::RequestUsername { .. } => Ok(()),
+        PacketMessage::SearchForGame => connection.find_game(),
+        PacketMessage::CancelSearch => connection.cancel_search(),
+        PacketMessage::CreateRoom => connection.create_room(),
+        PacketMessage::JoinRoom { code } => connection.join_room(&code),
+        PacketMessage::CloseRoom => connection.close_room(),
+        PacketMessage::ListPlayers => connection.list_players(),
+        PacketMessage::Challenge { username, rules } => connection.challenge(&username, rules),
+        PacketMessage::AcceptChallenge { username } => connection.accept_challenge(&username),
+        PacketMessage::DeclineChallenge { username } => connection.decline_challenge(&username),
+        PacketMessage::Chat { message } => connection.chat(&message),
+        PacketMessage::ListGames => connection.list_games(),
+        PacketMessage::Spectate { game_id } => connection.spectate(game_id),
+        PacketMessage::LeaveSpectate => connection.leave_spectate(),
+        PacketMessage::RequestHistory { username, page } => {
+            connection.request_history(&username, page)
+        }
+        PacketMessage::RequestGameRecord { game_id } => connection.request_game_record(game_id),
+        PacketMessage::RequestLeaderboard { page } => connection.request_leaderboard(page),
+        PacketMessage::RequestProfile { username } => connection.request_profile(&username),
+        PacketMessage::RequestAnalysis { game_id } => connection.request_analysis(game_id),
+        PacketMessage::PlacePieceInGame { column } => connection.place(column),
+        PacketMessage::RequestHint => connection.request_hint(),
+    }
+}
 
-        while let Some(packet) = packets.pop() {
-            match current_state {
-                ClientState::Login => {
-                    if let InnerPacket::Login(login_packet) = packet {
-                        match login_packet {
-                            ClientboundLoginPacket::KeepAlive => {}
-                            ClientboundLoginPacket::UsernameResult {
-                                success,
-                                transaction_id,
-                            } => {
-                                let username = pending_username_transactions
-                                    .remove(&transaction_id)
-                                    .unwrap();
-                                if success {
-                                    pending_username_transactions.clear();
-                                    encode!(
-                                        write,
-                                        ServerboundLoginPacket,
-                                        ServerboundLoginPacket::AcquireUsername
-                                    );
-                                    let mut state_write = client_state.write().await;
-                                    *state_write = ClientState::Lobby;
-                                    drop(state_write);
-                                }
-                                message_sender
-                                    .send(WindowMessage::UsernameResult { success, username })?;
-                            }
-                        }
-                    }
-                }
-                ClientState::Lobby => {
-                    if let InnerPacket::Lobby(lobby_packet) = packet {
-                        match lobby_packet {
-                            ClientboundLobbyPacket::KeepAlive => {}
-                            ClientboundLobbyPacket::GameFound => {
-                                message_sender.send(WindowMessage::TransferToGame)?;
-                                encode!(
-                                    write,
-                                    ServerboundLobbyPacket,
-                                    ServerboundLobbyPacket::AcquireGame
-                                );
-                                let mut state_write = client_state.write().await;
-                                *state_write = ClientState::Game;
-                                drop(state_write);
-                            }
-                        }
-                    }
-                }
-                ClientState::Game => {
-                    if let InnerPacket::Game(game_packet) = packet {
-                        match game_packet {
-                            ClientboundGamePacket::KeepAlive => {}
-                            ClientboundGamePacket::OpponentJoin {
-                                username,
-                                i_go_first,
-                            } => {
-                                message_sender.send(WindowMessage::NotifyOpponentJoin {
-                                    username,
-                                    i_go_first,
-                                })?;
-                            }
-                            ClientboundGamePacket::PlacePieceAck { transaction_id } => {
-                                let column = pending_placement_transactions
-                                    .remove(&transaction_id)
-                                    .unwrap();
-                                pending_placement_transactions.clear();
-                                message_sender
-                                    .send(WindowMessage::PlacePieceInGame { me: true, column })?;
-                            }
-                            ClientboundGamePacket::OpponentPlacedPiece { column } => {
-                                message_sender
-                                    .send(WindowMessage::PlacePieceInGame { me: false, column })?;
-                            }
-                            ClientboundGamePacket::EarlyExit => {
-                                message_sender.send(WindowMessage::ExitToLobby)?;
-                                encode!(
-                                    write,
-                                    ServerboundGamePacket,
-                                    ServerboundGamePacket::AcquireLobby
-                                );
-                                let mut state_write = client_state.write().await;
-                                *state_write = ClientState::Lobby;
-                                drop(state_write);
-                            }
-                            ClientboundGamePacket::PlayerWin { me } => {
-                                if me {
-                                    message_sender.send(WindowMessage::WinGame)?;
-                                } else {
-                                    message_sender.send(WindowMessage::LoseGame)?;
-                                }
-                                encode!(
-                                    write,
-                                    ServerboundGamePacket,
-                                    ServerboundGamePacket::AcquireLobby
-                                );
-                                let mut state_write = client_state.write().await;
-                                *state_write = ClientState::Lobby;
-                                drop(state_write);
-                            }
-                        }
-                    }
-                }
-            }
+fn window_message(event: Event) -> Option<WindowMessage> {
+    Some(match event {
+        Event::QueueUpdate {
+            position,
+            estimated_wait_seconds,
+        } => WindowMessage::QueueUpdate {
+            position,
+            estimated_wait_seconds,
+        },
+        Event::SearchCancelled => WindowMessage::SearchCancelled,
+        Event::RoomCreated { code } => WindowMessage::RoomCreated { code },
+        Event::RoomJoinFailed { code } => WindowMessage::RoomJoinFailed { code },
+        Event::RoomClosed { expired } => WindowMessage::RoomClosed { expired },
+        Event::PlayerList { usernames } => WindowMessage::PlayerList { usernames },
+        Event::ChallengeReceived { username, rules } => {
+            WindowMessage::ChallengeReceived { username, rules }
         }
-        tick_interval.tick().await;
-        let read_client_state = client_state.read().await;
-        let current_state = *read_client_state;
-        drop(read_client_state);
-        match current_state {
-            ClientState::Login => {
-                encode!(
-                    write,
-                    ServerboundLoginPacket,
-                    ServerboundLoginPacket::KeepAlive
-                );
-            }
-            ClientState::Lobby => {
-                encode!(
-                    write,
-                    ServerboundLobbyPacket,
-                    ServerboundLobbyPacket::KeepAlive
-                );
-            }
-            ClientState::Game => {
-                encode!(
-                    write,
-                    ServerboundGamePacket,
-                    ServerboundGamePacket::KeepAlive
-                );
-            }
+        Event::ChallengeDeclined { username } => WindowMessage::ChallengeDeclined { username },
+        Event::ChallengeFailed { username } => WindowMessage::ChallengeFailed { username },
+        Event::Chat {
+            username,
+            message,
+            in_game,
+        } => WindowMessage::Chat {
+            username,
+            message,
+            in_game,
+        },
+        Event::ChatRejected { reason, in_game } => WindowMessage::ChatRejected { reason, in_game },
+        Event::GameList { games } => WindowMessage::GameList { games },
+        Event::HistoryPage {
+            username,
+            page,
+            has_more,
+            games,
+        } => WindowMessage::HistoryPage {
+            username,
+            page,
+            has_more,
+            games,
+        },
+        Event::GameRecord {
+            game_id,
+            first_player,
+            second_player,
+            moves,
+            ..
+        } => WindowMessage::GameRecord {
+            game_id,
+            first_player,
+            second_player,
+            moves,
+        },
+        Event::GameRecordNotFound { game_id } => WindowMessage::GameRecordNotFound { game_id },
+        Event::Leaderboard {
+            page,
+            has_more,
+            entries,
+        } => WindowMessage::Leaderboard {
+            page,
+            has_more,
+            entries,
+        },
+        Event::Profile {
+            username,
+            rank,
+            rating,
+            wins,
+            losses,
+            draws,
+            current_streak,
+            best_streak,
+            recent_form,
+        } => WindowMessage::Profile {
+            username,
+            rank,
+            rating,
+            wins,
+            losses,
+            draws,
+            current_streak,
+            best_streak,
+            recent_form,
+        },
+        Event::ProfileNotFound { username } => WindowMessage::ProfileNotFound { username },
+        Event::GameAnalysis { game_id, moves } => WindowMessage::GameAnalysis { game_id, moves },
+        Event::GameAnalysisNotFound { game_id } => WindowMessage::GameAnalysisNotFound { game_id },
+        Event::GameFound => WindowMessage::TransferToGame,
+        Event::OpponentJoined {
+            username,
+            i_go_first,
+            opponent_is_bot,
+            hints_allowed,
+        } => WindowMessage::NotifyOpponentJoin {
+            username,
+            i_go_first,
+            opponent_is_bot,
+            hints_allowed,
+        },
+        Event::PiecePlaced { column, mine } => WindowMessage::PlacePieceInGame { me: mine, column },
+        Event::SpectatorCount { count } => WindowMessage::SpectatorCount { count },
+        Event::Hint {
+            column,
+            score,
+            exact,
+        } => WindowMessage::Hint {
+            column,
+            score,
+            exact,
+        },
+        Event::HintUnavailable => WindowMessage::HintUnavailable,
+        Event::GameOver(GameOutcome::Won) => WindowMessage::WinGame,
+        Event::GameOver(GameOutcome::Lost) => WindowMessage::LoseGame,
+        Event::GameOver(GameOutcome::Draw) => WindowMessage::DrawGame,
+        Event::GameOver(GameOutcome::OpponentLeft) => WindowMessage::ExitToLobby,
+        Event::SpectateStarted { .. } => WindowMessage::TransferToSpectate,
+        Event::SpectateFailed { game_id } => WindowMessage::SpectateFailed { game_id },
+        Event::SpectateSnapshot {
+            first_player,
+            second_player,
+            board,
+            first_player_to_move,
+        } => WindowMessage::SpectateSnapshot {
+            first_player,
+            second_player,
+            board,
+            first_player_to_move,
+        },
+        Event::SpectatePiecePlaced {
+            column,
+            first_player,
+        } => WindowMessage::SpectatePiecePlaced {
+            column,
+            first_player,
+        },
+        Event::SpectateGameOver { winner, abandoned } => {
+            WindowMessage::SpectateGameOver { winner, abandoned }
         }
-    }
+        // the window already switched back to the lobby when it asked to leave
+        Event::SpectateLeft => return None,
+    })
 }
@@ -0,0 +1,5 @@
+#![feature(macro_metavar_expr)]
+
+pub mod game;
+pub mod local;
+pub mod mediator;
@@ -0,0 +1,251 @@
+//! Games played on this machine, without a server.
+
+use connect_4_core::board::{Board, DropError, Outcome, Player};
+use connect_4_core::bot::{self, Clock, HeuristicPlayer, MinimaxPlayer, SolverPlayer};
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How long the computer may think about a move.
+const THINKING_TIME: Duration = Duration::from_secs(2);
+
+/// A game between two players taking turns at the same machine.
+#[derive(Debug, Clone, Default)]
+pub struct LocalGame {
+    board: Board,
+    moves: Vec<u8>,
+}
+
+impl LocalGame {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn moves(&self) -> &[u8] {
+        &self.moves
+    }
+
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.board.outcome()
+    }
+
+    /// Drops a disc for the player to move and returns the outcome if that ended the game.
+    pub fn play(&mut self, column: u8) -> Result<Option<Outcome>, DropError> {
+        self.board.drop_piece(column)?;
+        self.moves.push(column);
+        Ok(self.board.outcome())
+    }
+
+    /// Takes back the last move, returning its column.
+    pub fn undo(&mut self) -> Option<u8> {
+        let column = self.moves.pop()?;
+        self.board.pop_piece(column);
+        Some(column)
+    }
+
+    /// Takes back the player's last move along with the computer's reply to it, if any, so it's
+    /// the player's move again. `computer` is the side the computer plays, `None` when two
+    /// people share the board and only the last move is taken back.
+    pub fn undo_turn(&mut self, computer: Option<Player>) {
+        self.undo();
+        if computer == Some(self.board.next_player()) {
+            self.undo();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+    Perfect,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Hard,
+        Difficulty::Perfect,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+            Difficulty::Perfect => "Perfect",
+        }
+    }
+
+    fn bot(self) -> SharedBot {
+        let bot: Box<dyn bot::Player + Send> = match self {
+            Difficulty::Easy => Box::new(HeuristicPlayer),
+            Difficulty::Medium => Box::new(MinimaxPlayer::new(4)),
+            Difficulty::Hard => Box::new(MinimaxPlayer::new(8)),
+            Difficulty::Perfect => Box::new(SolverPlayer::with_threads(
+                std::thread::available_parallelism().map_or(1, usize::from),
+            )),
+        };
+        Arc::new(Mutex::new(bot))
+    }
+}
+
+impl Display for Difficulty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+type SharedBot = Arc<Mutex<Box<dyn bot::Player + Send>>>;
+
+/// A computer opponent for a [`LocalGame`]. Its searches run on tokio's blocking threads, so
+/// whoever awaits a move isn't held up while it thinks.
+#[derive(Clone)]
+pub struct Computer {
+    difficulty: Difficulty,
+    side: Player,
+    bot: SharedBot,
+}
+
+impl Debug for Computer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Computer")
+            .field("difficulty", &self.difficulty)
+            .field("side", &self.side)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Computer {
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    /// The player the computer plays as.
+    pub fn side(&self) -> Player {
+        self.side
+    }
+
+    /// Picks a move for the position, which must still be going with the computer to move.
+    /// Setting `cancel` cuts the search short, the move it then returns is only worth throwing
+    /// away.
+    pub async fn choose_move(&self, board: Board, cancel: Arc<AtomicBool>) -> u8 {
+        let bot = self.bot.clone();
+        tokio::task::spawn_blocking(move || {
+            let clock = Clock::new(THINKING_TIME).cancelled_by(cancel);
+            bot.lock()
+                .expect("a search panicked")
+                .choose_move(&board, &clock)
+        })
+        .await
+        .expect("a search panicked")
+    }
+}
+
+/// Hands out one computer per difficulty, so a new game reuses the last one's search instead
+/// of setting up another. The perfect one alone has a large table and a thread per core.
+#[derive(Default)]
+pub struct Computers {
+    bots: HashMap<Difficulty, SharedBot>,
+}
+
+impl Computers {
+    pub fn get(&mut self, difficulty: Difficulty, side: Player) -> Computer {
+        Computer {
+            difficulty,
+            side,
+            bot: self
+                .bots
+                .entry(difficulty)
+                .or_insert_with(|| difficulty.bot())
+                .clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undoing_an_empty_game_does_nothing() {
+        let mut game = LocalGame::new();
+        assert_eq!(game.undo(), None);
+        game.undo_turn(Some(Player::Second));
+        assert!(game.moves().is_empty());
+        assert_eq!(game.board().move_count(), 0);
+    }
+
+    #[test]
+    fn undoing_takes_back_the_computers_reply_too() {
+        let mut game = LocalGame::new();
+        game.play(3).unwrap();
+        game.play(2).unwrap();
+        game.play(4).unwrap();
+        // the computer, playing second, hasn't answered the last move yet
+        game.undo_turn(Some(Player::Second));
+        assert_eq!(game.moves(), [3, 2]);
+
+        game.undo_turn(Some(Player::Second));
+        assert!(game.moves().is_empty());
+        assert_eq!(*game.board(), Board::new());
+    }
+
+    #[test]
+    fn undoing_without_a_computer_takes_back_one_move() {
+        let mut game = LocalGame::new();
+        game.play(3).unwrap();
+        game.play(2).unwrap();
+        game.undo_turn(None);
+        assert_eq!(game.moves(), [3]);
+        assert_eq!(game.board().next_player(), Player::Second);
+    }
+
+    #[test]
+    fn full_columns_refuse_discs() {
+        let mut game = LocalGame::new();
+        for _ in 0..6 {
+            game.play(0).unwrap();
+        }
+        assert_eq!(game.play(0), Err(DropError::ColumnFull(0)));
+        assert_eq!(game.moves().len(), 6);
+        assert_eq!(game.undo(), Some(0));
+        assert!(game.play(0).is_ok());
+    }
+
+    #[test]
+    fn computers_are_reused_per_difficulty() {
+        let mut computers = Computers::default();
+        let first = computers.get(Difficulty::Easy, Player::First);
+        let second = computers.get(Difficulty::Easy, Player::Second);
+        assert!(Arc::ptr_eq(&first.bot, &second.bot));
+        assert_eq!(
+            (first.side(), second.side()),
+            (Player::First, Player::Second)
+        );
+
+        let medium = computers.get(Difficulty::Medium, Player::First);
+        assert!(!Arc::ptr_eq(&first.bot, &medium.bot));
+        assert_eq!(medium.difficulty(), Difficulty::Medium);
+    }
+
+    #[test]
+    fn computers_answer_with_a_legal_move() {
+        let mut computers = Computers::default();
+        let computer = computers.get(Difficulty::Easy, Player::First);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let column =
+            runtime.block_on(computer.choose_move(Board::new(), Arc::new(AtomicBool::new(false))));
+        assert!(Board::new().can_drop(column));
+    }
+}
@@ -1,9 +1,8 @@
 #![feature(macro_metavar_expr)]
 
-mod game;
-pub(crate) mod mediator;
 mod render;
 
+use client::{game, mediator};
 use connect_4_core::logger::{system_logger, LoggerOptions};
 use log::LevelFilter;
 use tokio::runtime::Builder;
@@ -1,24 +1,152 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum ClientState {
-    Login,
-    Lobby,
-    Game,
-}
+use connect_4_core::packets::{
+    GameRules, GameSummary, HistoryEntry, LeaderboardEntry, MoveEvaluation,
+};
 
 #[derive(Debug)]
 pub enum WindowMessage {
     UsernameResult { success: bool, username: String },
     TransferToGame,
-    NotifyOpponentJoin { username: String, i_go_first: bool },
+    QueueUpdate {
+        position: i32,
+        estimated_wait_seconds: i32,
+    },
+    SearchCancelled,
+    RoomCreated {
+        code: String,
+    },
+    RoomJoinFailed {
+        code: String,
+    },
+    RoomClosed {
+        expired: bool,
+    },
+    PlayerList {
+        usernames: Vec<String>,
+    },
+    ChallengeReceived {
+        username: String,
+        rules: GameRules,
+    },
+    ChallengeDeclined {
+        username: String,
+    },
+    ChallengeFailed {
+        username: String,
+    },
+    Chat {
+        username: String,
+        message: String,
+        in_game: bool,
+    },
+    ChatRejected {
+        reason: String,
+        in_game: bool,
+    },
+    GameList {
+        games: Vec<GameSummary>,
+    },
+    SpectateFailed {
+        game_id: i32,
+    },
+    TransferToSpectate,
+    SpectateSnapshot {
+        first_player: String,
+        second_player: String,
+        board: Vec<u8>,
+        first_player_to_move: bool,
+    },
+    SpectatePiecePlaced {
+        column: u8,
+        first_player: bool,
+    },
+    SpectateGameOver {
+        winner: String,
+        abandoned: bool,
+    },
+    SpectatorCount {
+        count: i32,
+    },
+    HistoryPage {
+        username: String,
+        page: i32,
+        has_more: bool,
+        games: Vec<HistoryEntry>,
+    },
+    GameRecord {
+        game_id: i32,
+        first_player: String,
+        second_player: String,
+        moves: Vec<u8>,
+    },
+    GameRecordNotFound {
+        game_id: i32,
+    },
+    Leaderboard {
+        page: i32,
+        has_more: bool,
+        entries: Vec<LeaderboardEntry>,
+    },
+    Profile {
+        username: String,
+        rank: i32,
+        rating: i32,
+        wins: i32,
+        losses: i32,
+        draws: i32,
+        current_streak: i32,
+        best_streak: i32,
+        recent_form: String,
+    },
+    ProfileNotFound {
+        username: String,
+    },
+    GameAnalysis {
+        game_id: i32,
+        moves: Vec<MoveEvaluation>,
+    },
+    GameAnalysisNotFound {
+        game_id: i32,
+    },
+    NotifyOpponentJoin {
+        username: String,
+        i_go_first: bool,
+        opponent_is_bot: bool,
+        hints_allowed: bool,
+    },
     PlacePieceInGame { me: bool, column: u8 },
+    Hint {
+        column: u8,
+        score: i32,
+        exact: bool,
+    },
+    HintUnavailable,
     ExitToLobby,
     WinGame,
     LoseGame,
+    DrawGame,
 }
 
 #[derive(Debug)]
 pub enum PacketMessage {
     RequestUsername { username: String },
     SearchForGame,
+    CancelSearch,
+    CreateRoom,
+    JoinRoom { code: String },
+    CloseRoom,
+    ListPlayers,
+    Challenge { username: String, rules: GameRules },
+    AcceptChallenge { username: String },
+    DeclineChallenge { username: String },
+    Chat { message: String },
+    ListGames,
+    Spectate { game_id: i32 },
+    LeaveSpectate,
+    RequestHistory { username: String, page: i32 },
+    RequestGameRecord { game_id: i32 },
+    RequestLeaderboard { page: i32 },
+    RequestProfile { username: String },
+    RequestAnalysis { game_id: i32 },
     PlacePieceInGame { column: u8 },
+    RequestHint,
 }
@@ -1,4 +1,14 @@
+use crate::local::{Computer, Computers, Difficulty, LocalGame};
 use crate::mediator::{PacketMessage, WindowMessage};
+use chrono::{Local, TimeZone};
+use connect_4_core::analysis::Judgement;
+use connect_4_core::board::{Board, Outcome, Player};
+use connect_4_core::notation;
+use connect_4_core::packets::{
+    GameRules, GameSummary, HistoryEntry, LeaderboardEntry, MoveEvaluation,
+};
+use connect_4_core::record::GameRecord;
+use connect_4_core::solver;
 use gtk::gdk_pixbuf::{Pixbuf, PixbufLoader};
 use gtk::prelude::*;
 use relm4::component::{AsyncComponent, AsyncComponentParts};
@@ -7,12 +17,18 @@ use relm4::loading_widgets::LoadingWidgets;
 use relm4::*;
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
 const BOARD_ASSET: &[u8] = include_bytes!("assets/board-big.png");
 const RED_COIN_ASSET: &[u8] = include_bytes!("assets/red-coin-big.png");
 const YELLOW_COIN_ASSET: &[u8] = include_bytes!("assets/yellow-coin-big.png");
 
+const CHAT_HISTORY: usize = 50;
+const AUTOPLAY_STEP: Duration = Duration::from_millis(700);
+
 pub fn spawn_ui(
     message_sender: UnboundedSender<PacketMessage>,
     message_receiver: UnboundedReceiver<WindowMessage>,
@@ -26,7 +42,50 @@ pub fn spawn_ui(
 enum AppMessage {
     ForwardRequestUsername,
     LookForGame,
+    CancelSearch,
+    CreateRoom,
+    JoinRoom,
+    CloseRoom,
+    RefreshPlayers,
+    Challenge(String),
+    AnswerChallenge(bool),
+    SendChat(bool),
+    RefreshGames,
+    Spectate(i32),
+    LeaveSpectate,
+    OpenReplay,
+    ReplayStart,
+    ReplayBack,
+    ReplayForward,
+    ReplayEnd,
+    ToggleAutoplay,
+    AutoplayTick(u32),
+    LeaveReplay,
+    OpenHistory,
+    SearchHistory,
+    HistoryPrevious,
+    HistoryNext,
+    ReviewGame(i32),
+    AnalyseReplay,
+    LeaveHistory,
+    OpenLeaderboard,
+    LeaderboardPrevious,
+    LeaderboardNext,
+    LeaveLeaderboard,
+    ViewProfile(String),
+    ViewOwnProfile,
+    ProfileHistory,
+    LeaveProfile,
     PlaceColumn(u8),
+    RequestHint,
+    OpenLocalGame,
+    OpenComputerGame,
+    StartComputerGame,
+    LocalPlace(u8),
+    ComputerMove(u32, u8),
+    LocalUndo,
+    LocalRestart,
+    LeaveLocalGame,
     Window(WindowMessage),
 }
 
@@ -35,7 +94,14 @@ enum ViewMode {
     RequestUsername,
     Lobby,
     LookingForGame,
+    HostingRoom,
     Game,
+    Spectate,
+    Replay,
+    History,
+    Leaderboard,
+    Profile,
+    Local,
 }
 
 #[derive(Debug)]
@@ -48,7 +114,154 @@ struct App {
     known_board: Rc<RefCell<[[Option<bool>; 6]; 7]>>,
     my_turn: bool,
     opponent: Option<String>,
+    hints_allowed: bool,
+    hint: Rc<RefCell<Option<u8>>>,
+    hint_notice: Option<String>,
     game_draw_handler: DrawHandler,
+    queue_status: Option<(i32, i32)>,
+    room_code_buffer: gtk::EntryBuffer,
+    room_code: Option<String>,
+    lobby_notice: Option<String>,
+    players_list: gtk::ListBox,
+    challenger_goes_first: gtk::CheckButton,
+    allow_hints: gtk::CheckButton,
+    incoming_challenges: Vec<(String, GameRules)>,
+    lobby_chat: Vec<String>,
+    lobby_chat_buffer: gtk::EntryBuffer,
+    game_chat: Vec<String>,
+    game_chat_buffer: gtk::EntryBuffer,
+    spectator_count: i32,
+    games_list: gtk::ListBox,
+    spectate_board: Rc<RefCell<[[Option<bool>; 6]; 7]>>,
+    spectate_draw_handler: DrawHandler,
+    spectate_players: (String, String),
+    spectate_first_player_to_move: bool,
+    spectate_result: Option<String>,
+    replay_buffer: gtk::EntryBuffer,
+    replay_moves: Vec<u8>,
+    replay_board: Rc<RefCell<Board>>,
+    replay_draw_handler: DrawHandler,
+    replay_players: (String, String),
+    replay_autoplay: bool,
+    replay_autoplay_generation: u32,
+    replay_from_history: bool,
+    replay_game_id: Option<i32>,
+    replay_analysis: Option<String>,
+    history_buffer: gtk::EntryBuffer,
+    history_list: gtk::ListBox,
+    history_username: String,
+    history_page: i32,
+    history_has_more: bool,
+    history_notice: Option<String>,
+    leaderboard_list: gtk::ListBox,
+    leaderboard_page: i32,
+    leaderboard_has_more: bool,
+    profile_username: String,
+    profile_summary: String,
+    local_game: Rc<RefCell<LocalGame>>,
+    local_draw_handler: DrawHandler,
+    local_computer: Option<Computer>,
+    computers: Computers,
+    /// Picking the computer's difficulty and side, before its game starts.
+    choosing_computer: bool,
+    local_thinking: bool,
+    local_generation: u32,
+    /// Set when the computer's move in progress is no longer wanted.
+    local_cancel: Arc<AtomicBool>,
+    computer_difficulty: gtk::DropDown,
+    play_first: gtk::CheckButton,
+}
+
+fn draw_board(ctx: &gtk::cairo::Context, board: &[[Option<bool>; 6]; 7]) {
+    let board_pix_buf = pixbuf_from(276, 238, BOARD_ASSET);
+    let red_coin_pix_buf = pixbuf_from(28, 28, RED_COIN_ASSET);
+    let yellow_coin_pix_buf = pixbuf_from(28, 28, YELLOW_COIN_ASSET);
+
+    for x in 0..7 {
+        for y in 0..6 {
+            if let Some(is_red) = board[x][y] {
+                let coin_pix_buf = if is_red {
+                    &red_coin_pix_buf
+                } else {
+                    &yellow_coin_pix_buf
+                };
+                ctx.set_source_pixbuf(
+                    coin_pix_buf,
+                    (10 + (x * 38)) as f64,
+                    (10 + ((5 - y) * 38)) as f64,
+                );
+                ctx.paint().expect("Painting coins.");
+            }
+        }
+    }
+
+    ctx.set_source_pixbuf(&board_pix_buf, 0f64, 0f64);
+    ctx.paint().expect("Failed to paint");
+}
+
+fn board_cells(board: &Board) -> [[Option<bool>; 6]; 7] {
+    let mut cells = [[None; 6]; 7];
+    for (x, column) in cells.iter_mut().enumerate() {
+        for (y, cell) in column.iter_mut().enumerate() {
+            *cell = board.get(x, y).map(|player| player == Player::First);
+        }
+    }
+    cells
+}
+
+fn draw_winning_line(ctx: &gtk::cairo::Context, line: &[(usize, usize)]) {
+    ctx.set_source_rgb(0.2, 0.9, 0.3);
+    ctx.set_line_width(4.0);
+    for (x, y) in line {
+        ctx.new_sub_path();
+        ctx.arc(
+            (24 + (x * 38)) as f64,
+            (24 + ((5 - y) * 38)) as f64,
+            16.0,
+            0.0,
+            std::f64::consts::TAU,
+        );
+    }
+    ctx.stroke().expect("Painting winning line.");
+}
+
+fn draw_hint(ctx: &gtk::cairo::Context, column: u8) {
+    ctx.set_source_rgba(0.2, 0.9, 0.3, 0.35);
+    ctx.rectangle((5 + column as usize * 38) as f64, 5.0, 38.0, 228.0);
+    ctx.fill().expect("Painting hint.");
+}
+
+/// One line per move, with the solver's judgement and the move it would have played instead.
+fn annotate_moves(moves: &[MoveEvaluation], players: &(String, String)) -> String {
+    if moves.is_empty() {
+        return "There are no moves to analyse.".to_string();
+    }
+    moves
+        .iter()
+        .enumerate()
+        .map(|(index, evaluation)| {
+            let player = if index % 2 == 0 {
+                &players.0
+            } else {
+                &players.1
+            };
+            let played = format!("{}. {player} plays {}", index + 1, evaluation.column + 1);
+            if !evaluation.solved {
+                return format!("{played}, not solved in time");
+            }
+            let outcome = solver::describe_score(index, evaluation.score);
+            match Judgement::of(evaluation.best_score, evaluation.score) {
+                Judgement::Best => format!("{played}, best ({outcome})"),
+                judgement => format!(
+                    "{played}{}, {judgement} ({outcome}), best was {} ({})",
+                    judgement.symbol(),
+                    evaluation.best_move + 1,
+                    solver::describe_score(index, evaluation.best_score)
+                ),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 fn pixbuf_from(width: i32, height: i32, bytes: &[u8]) -> Pixbuf {
@@ -101,6 +314,19 @@ impl AsyncComponent for App {
                         #[watch]
                         set_label: &format!("Failed to acquire username `{}`; it's probably already taken.\nMake sure it's alphanumeric.", model.last_username_failure.as_ref().unwrap_or(&String::new())),
                         set_margin_all: 5,
+                    },
+
+                    gtk::Button {
+                        set_label: "Local Game",
+                        set_tooltip_text: Some("Two players taking turns at this machine"),
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::OpenLocalGame,
+                    },
+
+                    gtk::Button {
+                        set_label: "Play vs Computer",
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::OpenComputerGame,
                     }
                 },
 
@@ -112,11 +338,245 @@ impl AsyncComponent for App {
                     set_spacing: 5,
                     set_margin_all: 5,
 
+                    gtk::Label {
+                        #[watch]
+                        set_visible: model.lobby_notice.is_some(),
+                        #[watch]
+                        set_label: model.lobby_notice.as_deref().unwrap_or_default(),
+                        set_margin_all: 5,
+                    },
+
                     gtk::Button {
                         set_label: "Look for Game",
                         set_margin_all: 5,
                         connect_clicked => AppMessage::LookForGame,
-                    }
+                    },
+
+                    gtk::Button {
+                        set_label: "Create Private Room",
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::CreateRoom,
+                    },
+
+                    gtk::Button {
+                        set_label: "Local Game",
+                        set_tooltip_text: Some("Two players taking turns at this machine"),
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::OpenLocalGame,
+                    },
+
+                    gtk::Button {
+                        set_label: "Play vs Computer",
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::OpenComputerGame,
+                    },
+
+                    gtk::Button {
+                        set_label: "Game History",
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::OpenHistory,
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 5,
+                        set_homogeneous: true,
+
+                        gtk::Button {
+                            set_label: "Leaderboard",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::OpenLeaderboard,
+                        },
+
+                        gtk::Button {
+                            set_label: "My Profile",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::ViewOwnProfile,
+                        },
+                    },
+
+                    gtk::Box {
+                        #[watch]
+                        set_visible: !model.incoming_challenges.is_empty(),
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 5,
+
+                        gtk::Label {
+                            #[watch]
+                            set_label: &model.incoming_challenges.first().map(|(username, rules)| {
+                                format!(
+                                    "{username} challenged you! {} first.{}",
+                                    if rules.challenger_goes_first { "They go" } else { "You go" },
+                                    if rules.hints_allowed { " Hints are allowed, so it won't be rated." } else { "" }
+                                )
+                            }).unwrap_or_default(),
+                            set_margin_all: 5,
+                        },
+
+                        gtk::Button {
+                            set_label: "Accept",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::AnswerChallenge(true),
+                        },
+
+                        gtk::Button {
+                            set_label: "Decline",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::AnswerChallenge(false),
+                        },
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 5,
+
+                        gtk::Entry {
+                            set_buffer: &model.room_code_buffer,
+                            set_tooltip_text: Some("Invite code"),
+                            set_margin_all: 5,
+                            connect_activate => AppMessage::JoinRoom,
+                        },
+
+                        gtk::Button {
+                            set_label: "Join Room",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::JoinRoom,
+                        },
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 5,
+
+                        gtk::Label {
+                            set_label: "Online players",
+                            set_margin_all: 5,
+                        },
+
+                        gtk::Button {
+                            set_label: "Refresh",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::RefreshPlayers,
+                        },
+                    },
+
+                    #[local_ref]
+                    challenger_goes_first -> gtk::CheckButton {
+                        set_label: Some("I go first when challenging"),
+                        set_active: true,
+                        set_margin_all: 5,
+                    },
+
+                    #[local_ref]
+                    allow_hints -> gtk::CheckButton {
+                        set_label: Some("Allow hints (the game won't be rated)"),
+                        set_margin_all: 5,
+                    },
+
+                    #[local_ref]
+                    players_list -> gtk::ListBox {
+                        set_selection_mode: gtk::SelectionMode::None,
+                        set_margin_all: 5,
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 5,
+
+                        gtk::Label {
+                            set_label: "Live games",
+                            set_margin_all: 5,
+                        },
+
+                        gtk::Button {
+                            set_label: "Refresh",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::RefreshGames,
+                        },
+                    },
+
+                    #[local_ref]
+                    games_list -> gtk::ListBox {
+                        set_selection_mode: gtk::SelectionMode::None,
+                        set_margin_all: 5,
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 5,
+
+                        gtk::Entry {
+                            set_buffer: &model.replay_buffer,
+                            set_tooltip_text: Some("Moves to replay, e.g. 4453, or a saved game record"),
+                            set_hexpand: true,
+                            set_margin_all: 5,
+                            connect_activate => AppMessage::OpenReplay,
+                        },
+
+                        gtk::Button {
+                            set_label: "Open Replay",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::OpenReplay,
+                        },
+                    },
+
+                    gtk::ScrolledWindow {
+                        set_min_content_height: 120,
+                        set_margin_all: 5,
+
+                        gtk::Label {
+                            #[watch]
+                            set_label: &model.lobby_chat.join("\n"),
+                            set_wrap: true,
+                            set_xalign: 0.0,
+                            set_yalign: 1.0,
+                        },
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 5,
+
+                        gtk::Entry {
+                            set_buffer: &model.lobby_chat_buffer,
+                            set_tooltip_text: Some("Say something"),
+                            set_hexpand: true,
+                            set_margin_all: 5,
+                            connect_activate => AppMessage::SendChat(false),
+                        },
+
+                        gtk::Button {
+                            set_label: "Send",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::SendChat(false),
+                        },
+                    },
+                },
+
+                gtk::Box {
+                    #[watch]
+                    set_visible: matches!(model.mode, ViewMode::HostingRoom),
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 5,
+                    set_margin_all: 5,
+
+                    gtk::Label {
+                        #[watch]
+                        set_label: &format!("Invite code: {}", model.room_code.as_ref().unwrap_or(&String::new())),
+                        set_selectable: true,
+                        set_margin_all: 5,
+                    },
+
+                    gtk::Label {
+                        set_label: "Waiting for your opponent to join...",
+                        set_margin_all: 5,
+                    },
+
+                    gtk::Button {
+                        set_label: "Close Room",
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::CloseRoom,
+                    },
                 },
 
                 gtk::Box {
@@ -130,6 +590,443 @@ impl AsyncComponent for App {
                         set_label: "Waiting for opponent...",
                         set_margin_all: 5,
                     },
+
+                    gtk::Label {
+                        #[watch]
+                        set_visible: model.queue_status.is_some(),
+                        #[watch]
+                        set_label: &model.queue_status.map(|(position, estimated_wait_seconds)| {
+                            format!("Position in queue: {position}\nEstimated wait: ~{estimated_wait_seconds}s")
+                        }).unwrap_or_default(),
+                        set_margin_all: 5,
+                    },
+
+                    gtk::Button {
+                        set_label: "Cancel",
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::CancelSearch,
+                    },
+                },
+
+                gtk::Box {
+                    #[watch]
+                    set_visible: matches!(model.mode, ViewMode::Spectate),
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 5,
+                    set_margin_all: 5,
+
+                    gtk::Label {
+                        #[watch]
+                        set_label: &format!("{} (red) vs {} (yellow)", model.spectate_players.0, model.spectate_players.1),
+                        set_margin_all: 5,
+                    },
+
+                    gtk::Label {
+                        #[watch]
+                        set_label: &match model.spectate_result.as_ref() {
+                            Some(result) => result.clone(),
+                            None => format!(
+                                "{} to move",
+                                if model.spectate_first_player_to_move {
+                                    &model.spectate_players.0
+                                } else {
+                                    &model.spectate_players.1
+                                }
+                            ),
+                        },
+                        set_margin_all: 5,
+                    },
+
+                    #[local_ref]
+                    spectate_area -> gtk::DrawingArea {
+                        set_size_request: (276, 238),
+                        set_draw_func: move |_, ctx, _, _| {
+                            draw_board(ctx, &spectate_board.borrow());
+                        }
+                    },
+
+                    gtk::Button {
+                        set_label: "Back to Lobby",
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::LeaveSpectate,
+                    },
+                },
+
+                gtk::Box {
+                    #[watch]
+                    set_visible: matches!(model.mode, ViewMode::Leaderboard),
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 5,
+                    set_margin_all: 5,
+
+                    gtk::Label {
+                        #[watch]
+                        set_label: &format!("Leaderboard, page {}", model.leaderboard_page + 1),
+                        set_margin_all: 5,
+                    },
+
+                    #[local_ref]
+                    leaderboard_list -> gtk::ListBox {
+                        set_selection_mode: gtk::SelectionMode::None,
+                        set_margin_all: 5,
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_halign: gtk::Align::Center,
+
+                        gtk::Button {
+                            set_label: "Previous",
+                            #[watch]
+                            set_sensitive: model.leaderboard_page > 0,
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::LeaderboardPrevious,
+                        },
+                        gtk::Button {
+                            set_label: "Next",
+                            #[watch]
+                            set_sensitive: model.leaderboard_has_more,
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::LeaderboardNext,
+                        },
+                    },
+
+                    gtk::Button {
+                        set_label: "Back to Lobby",
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::LeaveLeaderboard,
+                    },
+                },
+
+                gtk::Box {
+                    #[watch]
+                    set_visible: matches!(model.mode, ViewMode::Profile),
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 5,
+                    set_margin_all: 5,
+
+                    gtk::Label {
+                        #[watch]
+                        set_label: &model.profile_summary,
+                        set_xalign: 0.0,
+                        set_margin_all: 5,
+                    },
+
+                    gtk::Button {
+                        set_label: "Game History",
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::ProfileHistory,
+                    },
+
+                    gtk::Button {
+                        set_label: "Back to Lobby",
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::LeaveProfile,
+                    },
+                },
+
+                gtk::Box {
+                    #[watch]
+                    set_visible: matches!(model.mode, ViewMode::History),
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 5,
+                    set_margin_all: 5,
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 5,
+
+                        gtk::Entry {
+                            set_buffer: &model.history_buffer,
+                            set_tooltip_text: Some("Player"),
+                            set_hexpand: true,
+                            set_margin_all: 5,
+                            connect_activate => AppMessage::SearchHistory,
+                        },
+
+                        gtk::Button {
+                            set_label: "Search",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::SearchHistory,
+                        },
+                    },
+
+                    gtk::Label {
+                        #[watch]
+                        set_label: &match model.history_notice.as_ref() {
+                            Some(notice) => notice.clone(),
+                            None => format!("Games of {}, page {}", model.history_username, model.history_page + 1),
+                        },
+                        set_margin_all: 5,
+                    },
+
+                    #[local_ref]
+                    history_list -> gtk::ListBox {
+                        set_selection_mode: gtk::SelectionMode::None,
+                        set_margin_all: 5,
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_halign: gtk::Align::Center,
+
+                        gtk::Button {
+                            set_label: "Newer",
+                            #[watch]
+                            set_sensitive: model.history_page > 0,
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::HistoryPrevious,
+                        },
+                        gtk::Button {
+                            set_label: "Older",
+                            #[watch]
+                            set_sensitive: model.history_has_more,
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::HistoryNext,
+                        },
+                    },
+
+                    gtk::Button {
+                        set_label: "Back to Lobby",
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::LeaveHistory,
+                    },
+                },
+
+                gtk::Box {
+                    #[watch]
+                    set_visible: matches!(model.mode, ViewMode::Replay),
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 5,
+                    set_margin_all: 5,
+
+                    gtk::Label {
+                        #[watch]
+                        set_label: &format!("{} (red) vs {} (yellow)", model.replay_players.0, model.replay_players.1),
+                        set_margin_all: 5,
+                    },
+
+                    gtk::Label {
+                        #[watch]
+                        set_label: &model.replay_status(),
+                        set_margin_all: 5,
+                    },
+
+                    #[local_ref]
+                    replay_area -> gtk::DrawingArea {
+                        set_size_request: (276, 238),
+                        set_draw_func: move |_, ctx, _, _| {
+                            let board = replay_board.borrow();
+                            draw_board(ctx, &board_cells(&board));
+                            if let Some(line) = board.winning_line() {
+                                draw_winning_line(ctx, &line);
+                            }
+                        }
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_halign: gtk::Align::Center,
+
+                        gtk::Button {
+                            set_label: "|<",
+                            set_tooltip_text: Some("Start"),
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::ReplayStart,
+                        },
+                        gtk::Button {
+                            set_label: "<",
+                            set_tooltip_text: Some("Step back"),
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::ReplayBack,
+                        },
+                        gtk::Button {
+                            #[watch]
+                            set_label: if model.replay_autoplay { "Pause" } else { "Play" },
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::ToggleAutoplay,
+                        },
+                        gtk::Button {
+                            set_label: ">",
+                            set_tooltip_text: Some("Step forward"),
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::ReplayForward,
+                        },
+                        gtk::Button {
+                            set_label: ">|",
+                            set_tooltip_text: Some("End"),
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::ReplayEnd,
+                        },
+                    },
+
+                    gtk::Button {
+                        set_label: "Analyse",
+                        #[watch]
+                        set_visible: model.replay_game_id.is_some(),
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::AnalyseReplay,
+                    },
+
+                    gtk::ScrolledWindow {
+                        #[watch]
+                        set_visible: model.replay_analysis.is_some(),
+                        set_min_content_height: 160,
+                        set_margin_all: 5,
+
+                        gtk::Label {
+                            #[watch]
+                            set_label: model.replay_analysis.as_deref().unwrap_or_default(),
+                            set_xalign: 0.0,
+                            set_yalign: 0.0,
+                        },
+                    },
+
+                    gtk::Button {
+                        set_label: "Back to Lobby",
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::LeaveReplay,
+                    },
+                },
+
+                gtk::Box {
+                    #[watch]
+                    set_visible: matches!(model.mode, ViewMode::Local),
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: 5,
+                    set_margin_all: 5,
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_halign: gtk::Align::Center,
+
+                        gtk::Label {
+                            #[watch]
+                            set_label: &model.local_status(),
+                            set_margin_all: 5,
+                        },
+
+                        gtk::Spinner {
+                            #[watch]
+                            set_visible: model.local_thinking,
+                            #[watch]
+                            set_spinning: model.local_thinking,
+                        },
+                    },
+
+                    #[local_ref]
+                    local_area -> gtk::DrawingArea {
+                        set_size_request: (276, 238),
+                        set_draw_func: move |_, ctx, _, _| {
+                            let game = local_game.borrow();
+                            draw_board(ctx, &board_cells(game.board()));
+                            if let Some(line) = game.board().winning_line() {
+                                draw_winning_line(ctx, &line);
+                            }
+                        }
+                    },
+
+                    gtk::Box {
+                        #[watch]
+                        set_visible: model.local_players_turn(),
+                        set_orientation: gtk::Orientation::Horizontal,
+
+                        gtk::Button {
+                            set_label: "1",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::LocalPlace(0),
+                        },
+                        gtk::Button {
+                            set_label: "2",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::LocalPlace(1),
+                        },
+                        gtk::Button {
+                            set_label: "3",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::LocalPlace(2),
+                        },
+                        gtk::Button {
+                            set_label: "4",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::LocalPlace(3),
+                        },
+                        gtk::Button {
+                            set_label: "5",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::LocalPlace(4),
+                        },
+                        gtk::Button {
+                            set_label: "6",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::LocalPlace(5),
+                        },
+                        gtk::Button {
+                            set_label: "7",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::LocalPlace(6),
+                        },
+                    },
+
+                    gtk::Box {
+                        #[watch]
+                        set_visible: !model.choosing_computer,
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_halign: gtk::Align::Center,
+
+                        gtk::Button {
+                            set_label: "Undo",
+                            #[watch]
+                            set_sensitive: !model.local_game.borrow().moves().is_empty(),
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::LocalUndo,
+                        },
+                        gtk::Button {
+                            set_label: "New Game",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::LocalRestart,
+                        },
+                    },
+
+                    gtk::Box {
+                        #[watch]
+                        set_visible: model.choosing_computer || model.local_computer.is_some(),
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_halign: gtk::Align::Center,
+
+                        gtk::Label {
+                            set_label: "Difficulty:",
+                            set_margin_all: 5,
+                        },
+
+                        #[local_ref]
+                        computer_difficulty -> gtk::DropDown {
+                            set_selected: 1,
+                            set_tooltip_text: Some("Takes effect in the next game"),
+                            set_margin_all: 5,
+                        },
+
+                        #[local_ref]
+                        play_first -> gtk::CheckButton {
+                            set_label: Some("I go first"),
+                            set_active: true,
+                            set_margin_all: 5,
+                        },
+                    },
+
+                    gtk::Button {
+                        #[watch]
+                        set_visible: model.choosing_computer,
+                        set_label: "Start Game",
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::StartComputerGame,
+                    },
+
+                    gtk::Button {
+                        set_label: "Leave",
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::LeaveLocalGame,
+                    },
                 },
 
                 gtk::Box {
@@ -148,41 +1045,39 @@ impl AsyncComponent for App {
 
                     gtk::Label {
                         #[watch]
-                        set_label: &format!("Opponent: {}", model.opponent.as_ref().unwrap_or(&String::new())),
+                        set_label: &format!("Opponent: {}", model.opponent.as_ref().unwrap_or(&String::new())),
+                        #[watch]
+                        set_visible: !matches!(model.opponent, None),
+                    },
+
+                    gtk::Label {
+                        set_label: &format!("Your turn!"),
+                        #[watch]
+                        set_visible: model.my_turn,
+                    },
+
+                    gtk::Label {
+                        #[watch]
+                        set_label: model.hint_notice.as_deref().unwrap_or_default(),
                         #[watch]
-                        set_visible: !matches!(model.opponent, None),
+                        set_visible: model.hint_notice.is_some(),
                     },
 
                     gtk::Label {
-                        set_label: &format!("Your turn!"),
                         #[watch]
-                        set_visible: model.my_turn,
+                        set_label: &format!("Spectators: {}", model.spectator_count),
+                        #[watch]
+                        set_visible: model.spectator_count > 0,
                     },
 
                     #[local_ref]
                     area -> gtk::DrawingArea {
                         set_size_request: (276, 238),
                         set_draw_func: move |_, ctx, _, _| {
-                            let board_pix_buf = pixbuf_from(276, 238, BOARD_ASSET);
-                            let red_coin_pix_buf = pixbuf_from(28, 28, RED_COIN_ASSET);
-                            let yellow_coin_pix_buf = pixbuf_from(28, 28, YELLOW_COIN_ASSET);
-
-                            for x in 0..7 {
-                                for y in 0..6 {
-                                    if let Some(is_red) = board.borrow()[x][y] {
-                                        let coin_pix_buf = if is_red {
-                                            &red_coin_pix_buf
-                                        } else {
-                                            &yellow_coin_pix_buf
-                                        };
-                                        ctx.set_source_pixbuf(coin_pix_buf, (10 + (x * 38)) as f64, (10 + ((5 - y) * 38)) as f64);
-                                        ctx.paint().expect("Painting coins.");
-                                    }
-                                }
+                            draw_board(ctx, &board.borrow());
+                            if let Some(column) = *hint.borrow() {
+                                draw_hint(ctx, column);
                             }
-
-                            ctx.set_source_pixbuf(&board_pix_buf, 0f64, 0f64);
-                            ctx.paint().expect("Failed to paint");
                         }
                     },
 
@@ -226,7 +1121,47 @@ impl AsyncComponent for App {
                             set_margin_all: 5,
                             connect_clicked => AppMessage::PlaceColumn(6),
                         },
-                    }
+                    },
+
+                    gtk::Button {
+                        set_label: "Hint",
+                        #[watch]
+                        set_visible: model.my_turn && model.hints_allowed,
+                        set_margin_all: 5,
+                        connect_clicked => AppMessage::RequestHint,
+                    },
+
+                    gtk::ScrolledWindow {
+                        set_min_content_height: 120,
+                        set_margin_all: 5,
+
+                        gtk::Label {
+                            #[watch]
+                            set_label: &model.game_chat.join("\n"),
+                            set_wrap: true,
+                            set_xalign: 0.0,
+                            set_yalign: 1.0,
+                        },
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 5,
+
+                        gtk::Entry {
+                            set_buffer: &model.game_chat_buffer,
+                            set_tooltip_text: Some("Say something"),
+                            set_hexpand: true,
+                            set_margin_all: 5,
+                            connect_activate => AppMessage::SendChat(true),
+                        },
+
+                        gtk::Button {
+                            set_label: "Send",
+                            set_margin_all: 5,
+                            connect_clicked => AppMessage::SendChat(true),
+                        },
+                    },
                 }
             }
         }
@@ -263,7 +1198,62 @@ impl AsyncComponent for App {
             known_board: Rc::new(RefCell::new([[None; 6]; 7])),
             my_turn: false,
             opponent: None,
+            hints_allowed: false,
+            hint: Rc::new(RefCell::new(None)),
+            hint_notice: None,
             game_draw_handler: DrawHandler::new(),
+            queue_status: None,
+            room_code_buffer: gtk::EntryBuffer::new(None),
+            room_code: None,
+            lobby_notice: None,
+            players_list: gtk::ListBox::new(),
+            challenger_goes_first: gtk::CheckButton::new(),
+            allow_hints: gtk::CheckButton::new(),
+            incoming_challenges: vec![],
+            lobby_chat: vec![],
+            lobby_chat_buffer: gtk::EntryBuffer::new(None),
+            game_chat: vec![],
+            game_chat_buffer: gtk::EntryBuffer::new(None),
+            spectator_count: 0,
+            games_list: gtk::ListBox::new(),
+            spectate_board: Rc::new(RefCell::new([[None; 6]; 7])),
+            spectate_draw_handler: DrawHandler::new(),
+            spectate_players: (String::new(), String::new()),
+            spectate_first_player_to_move: true,
+            spectate_result: None,
+            replay_buffer: gtk::EntryBuffer::new(None),
+            replay_moves: vec![],
+            replay_board: Rc::new(RefCell::new(Board::new())),
+            replay_draw_handler: DrawHandler::new(),
+            replay_players: (String::new(), String::new()),
+            replay_autoplay: false,
+            replay_autoplay_generation: 0,
+            replay_from_history: false,
+            replay_game_id: None,
+            replay_analysis: None,
+            history_buffer: gtk::EntryBuffer::new(None),
+            history_list: gtk::ListBox::new(),
+            history_username: String::new(),
+            history_page: 0,
+            history_has_more: false,
+            history_notice: None,
+            leaderboard_list: gtk::ListBox::new(),
+            leaderboard_page: 0,
+            leaderboard_has_more: false,
+            profile_username: String::new(),
+            profile_summary: String::new(),
+            local_game: Rc::new(RefCell::new(LocalGame::new())),
+            local_draw_handler: DrawHandler::new(),
+            local_computer: None,
+            computers: Computers::default(),
+            choosing_computer: false,
+            local_thinking: false,
+            local_generation: 0,
+            local_cancel: Arc::new(AtomicBool::new(false)),
+            computer_difficulty: gtk::DropDown::from_strings(
+                &Difficulty::ALL.map(Difficulty::name),
+            ),
+            play_first: gtk::CheckButton::new(),
         };
 
         let mut flip = false;
@@ -277,7 +1267,22 @@ impl AsyncComponent for App {
         drop(board);
 
         let area = model.game_draw_handler.drawing_area();
+        let players_list = &model.players_list;
+        let challenger_goes_first = &model.challenger_goes_first;
+        let allow_hints = &model.allow_hints;
+        let games_list = &model.games_list;
+        let spectate_area = model.spectate_draw_handler.drawing_area();
+        let spectate_board = model.spectate_board.clone();
+        let replay_area = model.replay_draw_handler.drawing_area();
+        let replay_board = model.replay_board.clone();
+        let local_area = model.local_draw_handler.drawing_area();
+        let local_game = model.local_game.clone();
+        let computer_difficulty = &model.computer_difficulty;
+        let play_first = &model.play_first;
+        let history_list = &model.history_list;
+        let leaderboard_list = &model.leaderboard_list;
         let board = model.known_board.clone();
+        let hint = model.hint.clone();
 
         let sender_clone = sender.clone();
         tokio::spawn(async move {
@@ -294,7 +1299,7 @@ impl AsyncComponent for App {
     async fn update(
         &mut self,
         message: AppMessage,
-        _sender: AsyncComponentSender<Self>,
+        sender: AsyncComponentSender<Self>,
         _root: &Self::Root,
     ) {
         match message {
@@ -307,26 +1312,527 @@ impl AsyncComponent for App {
             }
             AppMessage::LookForGame => {
                 self.mode = ViewMode::LookingForGame;
+                self.queue_status = None;
+                self.lobby_notice = None;
                 self.packet_message_sender
                     .send(PacketMessage::SearchForGame)
                     .unwrap();
             }
+            AppMessage::CancelSearch => {
+                self.packet_message_sender
+                    .send(PacketMessage::CancelSearch)
+                    .unwrap();
+            }
+            AppMessage::CreateRoom => {
+                self.lobby_notice = None;
+                self.packet_message_sender
+                    .send(PacketMessage::CreateRoom)
+                    .unwrap();
+            }
+            AppMessage::JoinRoom => {
+                self.lobby_notice = None;
+                self.packet_message_sender
+                    .send(PacketMessage::JoinRoom {
+                        code: self.room_code_buffer.text().trim().to_string(),
+                    })
+                    .unwrap();
+            }
+            AppMessage::CloseRoom => {
+                self.packet_message_sender
+                    .send(PacketMessage::CloseRoom)
+                    .unwrap();
+            }
+            AppMessage::RefreshPlayers => {
+                self.packet_message_sender
+                    .send(PacketMessage::ListPlayers)
+                    .unwrap();
+            }
+            AppMessage::Challenge(username) => {
+                self.lobby_notice = Some(format!("Challenge sent to {username}."));
+                self.packet_message_sender
+                    .send(PacketMessage::Challenge {
+                        username,
+                        rules: GameRules {
+                            challenger_goes_first: self.challenger_goes_first.is_active(),
+                            hints_allowed: self.allow_hints.is_active(),
+                        },
+                    })
+                    .unwrap();
+            }
+            AppMessage::AnswerChallenge(accept) => {
+                if self.incoming_challenges.is_empty() {
+                    return;
+                }
+                let (username, _) = self.incoming_challenges.remove(0);
+                let message = if accept {
+                    self.incoming_challenges.clear();
+                    PacketMessage::AcceptChallenge { username }
+                } else {
+                    PacketMessage::DeclineChallenge { username }
+                };
+                self.packet_message_sender.send(message).unwrap();
+            }
+            AppMessage::SendChat(in_game) => {
+                let buffer = if in_game {
+                    &self.game_chat_buffer
+                } else {
+                    &self.lobby_chat_buffer
+                };
+                let message = buffer.text().trim().to_string();
+                buffer.set_text("");
+                if !message.is_empty() {
+                    self.packet_message_sender
+                        .send(PacketMessage::Chat { message })
+                        .unwrap();
+                }
+            }
+            AppMessage::RefreshGames => {
+                self.packet_message_sender
+                    .send(PacketMessage::ListGames)
+                    .unwrap();
+            }
+            AppMessage::Spectate(game_id) => {
+                self.lobby_notice = None;
+                self.packet_message_sender
+                    .send(PacketMessage::Spectate { game_id })
+                    .unwrap();
+            }
+            AppMessage::LeaveSpectate => {
+                self.mode = ViewMode::Lobby;
+                self.packet_message_sender
+                    .send(PacketMessage::LeaveSpectate)
+                    .unwrap();
+            }
+            AppMessage::OpenReplay => {
+                let text = self.replay_buffer.text().to_string();
+                let replay = if text.contains("moves=") {
+                    text.trim()
+                        .parse::<GameRecord>()
+                        .map(|record| {
+                            let moves = record.moves.iter().map(|played| played.column).collect();
+                            (moves, (record.first_player, record.second_player))
+                        })
+                        .map_err(|err| format!("that isn't a saved game record, {err}"))
+                } else {
+                    notation::parse_moves(&text)
+                        .map(|moves| (moves, ("Red".to_string(), "Yellow".to_string())))
+                        .map_err(|err| err.to_string())
+                };
+                match replay.and_then(|(moves, players)| {
+                    notation::play_moves(&moves)
+                        .map(|_| (moves, players))
+                        .map_err(|err| err.to_string())
+                }) {
+                    Ok((moves, players)) => {
+                        self.lobby_notice = None;
+                        self.open_replay(moves, players);
+                        self.replay_from_history = false;
+                    }
+                    Err(err) => {
+                        self.lobby_notice = Some(format!("Couldn't open replay: {err}."));
+                    }
+                }
+            }
+            AppMessage::ReplayStart => {
+                self.replay_autoplay = false;
+                while self.step_replay(false) {}
+            }
+            AppMessage::ReplayBack => {
+                self.replay_autoplay = false;
+                self.step_replay(false);
+            }
+            AppMessage::ReplayForward => {
+                self.replay_autoplay = false;
+                self.step_replay(true);
+            }
+            AppMessage::ReplayEnd => {
+                self.replay_autoplay = false;
+                while self.step_replay(true) {}
+            }
+            AppMessage::ToggleAutoplay => {
+                self.replay_autoplay = !self.replay_autoplay;
+                self.replay_autoplay_generation = self.replay_autoplay_generation.wrapping_add(1);
+                if self.replay_autoplay {
+                    if self.replay_board.borrow().move_count() == self.replay_moves.len() {
+                        while self.step_replay(false) {}
+                    }
+                    self.schedule_autoplay(&sender);
+                }
+            }
+            AppMessage::AutoplayTick(generation) => {
+                if !self.replay_autoplay
+                    || generation != self.replay_autoplay_generation
+                    || !matches!(self.mode, ViewMode::Replay)
+                {
+                    return;
+                }
+                if self.step_replay(true)
+                    && self.replay_board.borrow().move_count() < self.replay_moves.len()
+                {
+                    self.schedule_autoplay(&sender);
+                } else {
+                    self.replay_autoplay = false;
+                }
+            }
+            AppMessage::OpenLocalGame => {
+                self.mode = ViewMode::Local;
+                self.new_local_game(false, &sender);
+            }
+            AppMessage::OpenComputerGame => {
+                self.mode = ViewMode::Local;
+                self.drop_computer_move();
+                self.local_computer = None;
+                self.choosing_computer = true;
+                *self.local_game.borrow_mut() = LocalGame::new();
+                self.local_draw_handler.drawing_area().queue_draw();
+            }
+            AppMessage::StartComputerGame => {
+                self.new_local_game(true, &sender);
+            }
+            AppMessage::LocalPlace(column) => {
+                if !self.local_players_turn() {
+                    return;
+                }
+                if self.local_game.borrow_mut().play(column).is_ok() {
+                    self.local_draw_handler.drawing_area().queue_draw();
+                    self.start_computer_move(&sender);
+                }
+            }
+            AppMessage::ComputerMove(generation, column) => {
+                if generation != self.local_generation {
+                    return;
+                }
+                self.local_thinking = false;
+                let _ = self.local_game.borrow_mut().play(column);
+                self.local_draw_handler.drawing_area().queue_draw();
+            }
+            AppMessage::LocalUndo => {
+                self.drop_computer_move();
+                self.local_game
+                    .borrow_mut()
+                    .undo_turn(self.local_computer.as_ref().map(Computer::side));
+                self.local_draw_handler.drawing_area().queue_draw();
+                self.start_computer_move(&sender);
+            }
+            AppMessage::LocalRestart => {
+                self.new_local_game(self.local_computer.is_some(), &sender);
+            }
+            AppMessage::LeaveLocalGame => {
+                self.drop_computer_move();
+                self.mode = if self.username.is_some() {
+                    ViewMode::Lobby
+                } else {
+                    ViewMode::RequestUsername
+                };
+            }
+            AppMessage::LeaveReplay => {
+                self.replay_autoplay = false;
+                self.mode = if self.replay_from_history {
+                    ViewMode::History
+                } else {
+                    ViewMode::Lobby
+                };
+            }
+            AppMessage::OpenHistory => {
+                if self.history_buffer.text().is_empty() {
+                    self.history_buffer
+                        .set_text(self.username.as_deref().unwrap_or_default());
+                }
+                self.mode = ViewMode::History;
+                self.request_history(self.history_buffer.text().trim().to_string(), 0);
+            }
+            AppMessage::SearchHistory => {
+                self.request_history(self.history_buffer.text().trim().to_string(), 0);
+            }
+            AppMessage::HistoryPrevious => {
+                if self.history_page > 0 {
+                    self.request_history(self.history_username.clone(), self.history_page - 1);
+                }
+            }
+            AppMessage::HistoryNext => {
+                if self.history_has_more {
+                    self.request_history(self.history_username.clone(), self.history_page + 1);
+                }
+            }
+            AppMessage::ReviewGame(game_id) => {
+                self.packet_message_sender
+                    .send(PacketMessage::RequestGameRecord { game_id })
+                    .unwrap();
+            }
+            AppMessage::AnalyseReplay => {
+                if let Some(game_id) = self.replay_game_id {
+                    self.replay_analysis = Some("Analysing the game...".to_string());
+                    self.packet_message_sender
+                        .send(PacketMessage::RequestAnalysis { game_id })
+                        .unwrap();
+                }
+            }
+            AppMessage::LeaveHistory => {
+                self.mode = ViewMode::Lobby;
+            }
+            AppMessage::OpenLeaderboard => {
+                self.mode = ViewMode::Leaderboard;
+                self.packet_message_sender
+                    .send(PacketMessage::RequestLeaderboard { page: 0 })
+                    .unwrap();
+            }
+            AppMessage::LeaderboardPrevious => {
+                if self.leaderboard_page > 0 {
+                    self.packet_message_sender
+                        .send(PacketMessage::RequestLeaderboard {
+                            page: self.leaderboard_page - 1,
+                        })
+                        .unwrap();
+                }
+            }
+            AppMessage::LeaderboardNext => {
+                if self.leaderboard_has_more {
+                    self.packet_message_sender
+                        .send(PacketMessage::RequestLeaderboard {
+                            page: self.leaderboard_page + 1,
+                        })
+                        .unwrap();
+                }
+            }
+            AppMessage::LeaveLeaderboard => {
+                self.mode = ViewMode::Lobby;
+            }
+            AppMessage::ViewProfile(username) => {
+                self.profile_username = username.clone();
+                self.profile_summary = format!("Loading {username}...");
+                self.mode = ViewMode::Profile;
+                self.packet_message_sender
+                    .send(PacketMessage::RequestProfile { username })
+                    .unwrap();
+            }
+            AppMessage::ViewOwnProfile => {
+                if let Some(username) = self.username.clone() {
+                    sender.input(AppMessage::ViewProfile(username));
+                }
+            }
+            AppMessage::ProfileHistory => {
+                self.history_buffer.set_text(&self.profile_username);
+                self.mode = ViewMode::History;
+                self.request_history(self.profile_username.clone(), 0);
+            }
+            AppMessage::LeaveProfile => {
+                self.mode = ViewMode::Lobby;
+            }
             AppMessage::PlaceColumn(column) => {
                 self.packet_message_sender
                     .send(PacketMessage::PlacePieceInGame { column })
                     .unwrap();
             }
+            AppMessage::RequestHint => {
+                self.hint_notice = Some("Thinking...".to_string());
+                self.packet_message_sender
+                    .send(PacketMessage::RequestHint)
+                    .unwrap();
+            }
             AppMessage::Window(window_message) => match window_message {
                 WindowMessage::UsernameResult { username, success } => {
                     if success {
                         self.last_username_failure = None;
                         self.username = Some(username);
                         self.mode = ViewMode::Lobby;
+                        self.packet_message_sender
+                            .send(PacketMessage::ListPlayers)
+                            .unwrap();
+                        self.packet_message_sender
+                            .send(PacketMessage::ListGames)
+                            .unwrap();
                     } else {
                         self.last_username_failure = Some(username);
                     }
                 }
+                WindowMessage::QueueUpdate {
+                    position,
+                    estimated_wait_seconds,
+                } => {
+                    self.queue_status = Some((position, estimated_wait_seconds));
+                }
+                WindowMessage::SearchCancelled => {
+                    self.queue_status = None;
+                    self.mode = ViewMode::Lobby;
+                }
+                WindowMessage::RoomCreated { code } => {
+                    self.room_code = Some(code);
+                    self.mode = ViewMode::HostingRoom;
+                }
+                WindowMessage::RoomJoinFailed { code } => {
+                    self.lobby_notice =
+                        Some(format!("Couldn't join room `{code}`; it may have expired."));
+                }
+                WindowMessage::RoomClosed { expired } => {
+                    self.room_code = None;
+                    if expired {
+                        self.lobby_notice = Some("Your private room expired.".to_string());
+                    }
+                    self.mode = ViewMode::Lobby;
+                }
+                WindowMessage::PlayerList { usernames } => {
+                    self.show_players(usernames, &sender);
+                }
+                WindowMessage::ChallengeReceived { username, rules } => {
+                    self.incoming_challenges
+                        .retain(|(challenger, _)| !challenger.eq(&username));
+                    self.incoming_challenges.push((username, rules));
+                }
+                WindowMessage::ChallengeDeclined { username } => {
+                    self.lobby_notice = Some(format!("{username} declined your challenge."));
+                }
+                WindowMessage::ChallengeFailed { username } => {
+                    self.lobby_notice = Some(format!("Couldn't start a game with {username}."));
+                }
+                WindowMessage::Chat {
+                    username,
+                    message,
+                    in_game,
+                } => {
+                    self.push_chat_line(in_game, format!("{username}: {message}"));
+                }
+                WindowMessage::ChatRejected { reason, in_game } => {
+                    self.push_chat_line(in_game, format!("* {reason}"));
+                }
+                WindowMessage::GameList { games } => {
+                    self.show_games(games, &sender);
+                }
+                WindowMessage::SpectateFailed { .. } => {
+                    self.lobby_notice = Some("That game has already finished.".to_string());
+                }
+                WindowMessage::TransferToSpectate => {
+                    *self.spectate_board.borrow_mut() = [[None; 6]; 7];
+                    self.spectate_players = (String::new(), String::new());
+                    self.spectate_result = None;
+                    self.mode = ViewMode::Spectate;
+                    self.spectate_draw_handler.drawing_area().queue_draw();
+                }
+                WindowMessage::SpectateSnapshot {
+                    first_player,
+                    second_player,
+                    board,
+                    first_player_to_move,
+                } => {
+                    let mut spectate_board = self.spectate_board.borrow_mut();
+                    for (idx, cell) in board.iter().enumerate().take(42) {
+                        spectate_board[idx / 6][idx % 6] = match cell {
+                            1 => Some(true),
+                            2 => Some(false),
+                            _ => None,
+                        };
+                    }
+                    drop(spectate_board);
+                    self.spectate_players = (first_player, second_player);
+                    self.spectate_first_player_to_move = first_player_to_move;
+                    self.spectate_draw_handler.drawing_area().queue_draw();
+                }
+                WindowMessage::SpectatePiecePlaced {
+                    column,
+                    first_player,
+                } => {
+                    let mut spectate_board = self.spectate_board.borrow_mut();
+                    if let Some(cell) = spectate_board
+                        .get_mut(column as usize)
+                        .and_then(|cells| cells.iter_mut().find(|cell| cell.is_none()))
+                    {
+                        *cell = Some(first_player);
+                    }
+                    drop(spectate_board);
+                    self.spectate_first_player_to_move = !first_player;
+                    self.spectate_draw_handler.drawing_area().queue_draw();
+                }
+                WindowMessage::SpectateGameOver { winner, abandoned } => {
+                    self.spectate_result = Some(if abandoned {
+                        "The game was abandoned.".to_string()
+                    } else if winner.is_empty() {
+                        "The game ended in a draw.".to_string()
+                    } else {
+                        format!("{winner} wins!")
+                    });
+                }
+                WindowMessage::SpectatorCount { count } => {
+                    self.spectator_count = count;
+                }
+                WindowMessage::HistoryPage {
+                    username,
+                    page,
+                    has_more,
+                    games,
+                } => {
+                    self.history_notice = if games.is_empty() && page == 0 {
+                        Some(format!("{username} hasn't finished any games yet."))
+                    } else {
+                        None
+                    };
+                    self.history_page = page;
+                    self.history_has_more = has_more;
+                    self.show_history(&username, games, &sender);
+                    self.history_username = username;
+                }
+                WindowMessage::GameRecord {
+                    game_id,
+                    first_player,
+                    second_player,
+                    moves,
+                } => {
+                    if matches!(self.mode, ViewMode::History) {
+                        self.open_replay(moves, (first_player, second_player));
+                        self.replay_from_history = true;
+                        self.replay_game_id = Some(game_id);
+                    }
+                }
+                WindowMessage::GameAnalysis { game_id, moves } => {
+                    if self.replay_game_id == Some(game_id) {
+                        self.replay_analysis = Some(annotate_moves(&moves, &self.replay_players));
+                    }
+                }
+                WindowMessage::GameAnalysisNotFound { game_id } => {
+                    if self.replay_game_id == Some(game_id) {
+                        self.replay_analysis =
+                            Some("That game is no longer stored on the server.".to_string());
+                    }
+                }
+                WindowMessage::GameRecordNotFound { .. } => {
+                    self.history_notice =
+                        Some("That game is no longer stored on the server.".to_string());
+                }
+                WindowMessage::Leaderboard {
+                    page,
+                    has_more,
+                    entries,
+                } => {
+                    self.leaderboard_page = page;
+                    self.leaderboard_has_more = has_more;
+                    self.show_leaderboard(entries, &sender);
+                }
+                WindowMessage::Profile {
+                    username,
+                    rank,
+                    rating,
+                    wins,
+                    losses,
+                    draws,
+                    current_streak,
+                    best_streak,
+                    recent_form,
+                } => {
+                    let streak = match current_streak {
+                        streak if streak > 0 => format!("{streak} game winning streak"),
+                        streak if streak < 0 => format!("{} game losing streak", -streak),
+                        _ => "No current streak".to_string(),
+                    };
+                    self.profile_summary = format!(
+                        "#{rank} {username}\nRating: {rating}\nWins: {wins}, losses: {losses}, draws: {draws}\n{streak}, best winning streak: {best_streak}\nRecent form: {}",
+                        if recent_form.is_empty() { "-" } else { &recent_form }
+                    );
+                }
+                WindowMessage::ProfileNotFound { username } => {
+                    self.profile_summary = format!("{username} hasn't finished any games yet.");
+                }
                 WindowMessage::TransferToGame => {
+                    self.incoming_challenges.clear();
+                    self.game_chat.clear();
+                    self.spectator_count = 0;
                     let mut mut_board = self.known_board.borrow_mut();
                     for x in 0..7 {
                         for y in 0..6 {
@@ -335,6 +1841,8 @@ impl AsyncComponent for App {
                     }
                     self.my_turn = false;
                     self.opponent = None;
+                    self.hints_allowed = false;
+                    self.clear_hint();
                     self.mode = ViewMode::Game;
                 }
                 WindowMessage::ExitToLobby => {
@@ -347,22 +1855,331 @@ impl AsyncComponent for App {
                         .unwrap();
                     self.known_board.borrow_mut()[column as usize][mut_pos] = Some(me);
                     self.my_turn = !self.my_turn;
+                    self.clear_hint();
+                    self.game_draw_handler.drawing_area().queue_draw();
+                }
+                WindowMessage::Hint {
+                    column,
+                    score,
+                    exact,
+                } => {
+                    self.hint_notice = Some(if exact {
+                        let moves = self.known_board.borrow().iter().flatten().flatten().count();
+                        format!(
+                            "Hint: column {}, {}.",
+                            column + 1,
+                            solver::describe_score(moves, score)
+                        )
+                    } else {
+                        format!("Hint: column {}, a quick estimate.", column + 1)
+                    });
+                    *self.hint.borrow_mut() = Some(column);
                     self.game_draw_handler.drawing_area().queue_draw();
                 }
+                WindowMessage::HintUnavailable => {
+                    self.hint_notice = Some("No hint is available right now.".to_string());
+                }
                 WindowMessage::WinGame => {
                     self.mode = ViewMode::Lobby;
                 }
                 WindowMessage::LoseGame => {
                     self.mode = ViewMode::Lobby;
                 }
+                WindowMessage::DrawGame => {
+                    self.lobby_notice = Some("The game ended in a draw.".to_string());
+                    self.mode = ViewMode::Lobby;
+                }
                 WindowMessage::NotifyOpponentJoin {
                     i_go_first,
                     username,
+                    opponent_is_bot,
+                    hints_allowed,
                 } => {
                     self.my_turn = i_go_first;
-                    self.opponent = Some(username);
+                    self.hints_allowed = hints_allowed;
+                    self.opponent = Some(if opponent_is_bot {
+                        format!("{username} (bot)")
+                    } else {
+                        username
+                    });
+                }
+            },
+        }
+    }
+}
+
+impl App {
+    fn clear_hint(&mut self) {
+        *self.hint.borrow_mut() = None;
+        self.hint_notice = None;
+    }
+
+    fn open_replay(&mut self, moves: Vec<u8>, players: (String, String)) {
+        self.replay_moves = moves;
+        self.replay_players = players;
+        self.replay_autoplay = false;
+        self.replay_game_id = None;
+        self.replay_analysis = None;
+        *self.replay_board.borrow_mut() = Board::new();
+        self.mode = ViewMode::Replay;
+        self.replay_draw_handler.drawing_area().queue_draw();
+    }
+
+    /// Plays or takes back a single move of the replay, returning whether there was one.
+    fn step_replay(&mut self, forward: bool) -> bool {
+        let mut board = self.replay_board.borrow_mut();
+        let position = board.move_count();
+        let stepped = if forward {
+            self.replay_moves
+                .get(position)
+                .map_or(false, |column| board.drop_piece(*column).is_ok())
+        } else {
+            position > 0 && board.pop_piece(self.replay_moves[position - 1]).is_some()
+        };
+        drop(board);
+        self.replay_draw_handler.drawing_area().queue_draw();
+        stepped
+    }
+
+    fn schedule_autoplay(&self, sender: &AsyncComponentSender<Self>) {
+        let sender = sender.clone();
+        let generation = self.replay_autoplay_generation;
+        tokio::spawn(async move {
+            tokio::time::sleep(AUTOPLAY_STEP).await;
+            sender.input(AppMessage::AutoplayTick(generation));
+        });
+    }
+
+    fn new_local_game(&mut self, vs_computer: bool, sender: &AsyncComponentSender<Self>) {
+        self.drop_computer_move();
+        self.choosing_computer = false;
+        self.local_computer = vs_computer.then(|| {
+            let difficulty = Difficulty::ALL
+                .get(self.computer_difficulty.selected() as usize)
+                .copied()
+                .unwrap_or(Difficulty::Medium);
+            let side = if self.play_first.is_active() {
+                Player::Second
+            } else {
+                Player::First
+            };
+            self.computers.get(difficulty, side)
+        });
+        *self.local_game.borrow_mut() = LocalGame::new();
+        self.local_draw_handler.drawing_area().queue_draw();
+        self.start_computer_move(sender);
+    }
+
+    /// Ignores the computer's move in progress and cancels its search, which frees the computer
+    /// up for the next one.
+    fn drop_computer_move(&mut self) {
+        self.local_generation = self.local_generation.wrapping_add(1);
+        self.local_thinking = false;
+        self.local_cancel.store(true, Ordering::Relaxed);
+        self.local_cancel = Arc::new(AtomicBool::new(false));
+    }
+
+    /// Whether the columns can be played, which they can't while the computer has the move.
+    fn local_players_turn(&self) -> bool {
+        let game = self.local_game.borrow();
+        !self.choosing_computer
+            && game.outcome().is_none()
+            && self.local_computer.as_ref().map_or(true, |computer| {
+                computer.side() != game.board().next_player()
+            })
+    }
+
+    /// Has the computer search for its move off the main thread, if it's the one to move.
+    fn start_computer_move(&mut self, sender: &AsyncComponentSender<Self>) {
+        let Some(computer) = self.local_computer.clone() else {
+            return;
+        };
+        let board = *self.local_game.borrow().board();
+        if board.outcome().is_some() || board.next_player() != computer.side() {
+            return;
+        }
+        self.local_thinking = true;
+        let generation = self.local_generation;
+        let cancel = self.local_cancel.clone();
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            let column = computer.choose_move(board, cancel).await;
+            sender.input(AppMessage::ComputerMove(generation, column));
+        });
+    }
+
+    fn local_status(&self) -> String {
+        if self.choosing_computer {
+            return "Pick your opponent".to_string();
+        }
+        let game = self.local_game.borrow();
+        if let Some(computer) = &self.local_computer {
+            return match game.outcome() {
+                Some(Outcome::Win(player)) if player == computer.side() => {
+                    format!("The computer ({}) wins!", computer.difficulty())
                 }
+                Some(Outcome::Win(_)) => "You win!".to_string(),
+                Some(Outcome::Draw) => "Draw.".to_string(),
+                None if self.local_thinking => "Thinking...".to_string(),
+                None => "Your move".to_string(),
+            };
+        }
+        match game.outcome() {
+            Some(Outcome::Win(Player::First)) => "Red wins!".to_string(),
+            Some(Outcome::Win(Player::Second)) => "Yellow wins!".to_string(),
+            Some(Outcome::Draw) => "Draw.".to_string(),
+            None => match game.board().next_player() {
+                Player::First => "Red to move".to_string(),
+                Player::Second => "Yellow to move".to_string(),
             },
         }
     }
+
+    fn replay_status(&self) -> String {
+        let board = self.replay_board.borrow();
+        let status = format!("Move {} of {}", board.move_count(), self.replay_moves.len());
+        match board.winner() {
+            Some(Player::First) => format!("{status}, {} wins!", self.replay_players.0),
+            Some(Player::Second) => format!("{status}, {} wins!", self.replay_players.1),
+            None if board.is_full() => format!("{status}, draw."),
+            None => status,
+        }
+    }
+
+    fn show_games(&self, games: Vec<GameSummary>, sender: &AsyncComponentSender<Self>) {
+        while let Some(row) = self.games_list.first_child() {
+            self.games_list.remove(&row);
+        }
+
+        for game in games {
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+            row.append(&gtk::Label::new(Some(&format!(
+                "{} vs {} ({} moves, {} watching)",
+                game.first_player, game.second_player, game.moves, game.spectators
+            ))));
+
+            let watch = gtk::Button::with_label("Watch");
+            let sender = sender.clone();
+            let game_id = game.game_id;
+            watch.connect_clicked(move |_| {
+                sender.input(AppMessage::Spectate(game_id));
+            });
+            row.append(&watch);
+
+            self.games_list.append(&row);
+        }
+    }
+
+    fn show_leaderboard(
+        &self,
+        entries: Vec<LeaderboardEntry>,
+        sender: &AsyncComponentSender<Self>,
+    ) {
+        while let Some(row) = self.leaderboard_list.first_child() {
+            self.leaderboard_list.remove(&row);
+        }
+
+        for entry in entries {
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+            row.append(&gtk::Label::new(Some(&format!(
+                "#{} {}: {} ({}-{}-{})",
+                entry.rank, entry.username, entry.rating, entry.wins, entry.losses, entry.draws
+            ))));
+
+            let profile = gtk::Button::with_label("Profile");
+            let sender = sender.clone();
+            let username = entry.username;
+            profile.connect_clicked(move |_| {
+                sender.input(AppMessage::ViewProfile(username.clone()));
+            });
+            row.append(&profile);
+
+            self.leaderboard_list.append(&row);
+        }
+    }
+
+    fn request_history(&mut self, username: String, page: i32) {
+        if username.is_empty() {
+            return;
+        }
+        self.packet_message_sender
+            .send(PacketMessage::RequestHistory { username, page })
+            .unwrap();
+    }
+
+    fn show_history(
+        &self,
+        username: &str,
+        games: Vec<HistoryEntry>,
+        sender: &AsyncComponentSender<Self>,
+    ) {
+        while let Some(row) = self.history_list.first_child() {
+            self.history_list.remove(&row);
+        }
+
+        for game in games {
+            let result = if game.abandoned {
+                "Abandoned"
+            } else if game.winner.is_empty() {
+                "Draw"
+            } else if game.winner.eq_ignore_ascii_case(username) {
+                "Won"
+            } else {
+                "Lost"
+            };
+            let date = Local
+                .timestamp_opt(game.finished_at, 0)
+                .single()
+                .map(|date| date.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_default();
+
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+            row.append(&gtk::Label::new(Some(&format!(
+                "vs {}: {result}, {date}, {} moves",
+                game.opponent, game.moves
+            ))));
+
+            let review = gtk::Button::with_label("Review");
+            let sender = sender.clone();
+            let game_id = game.game_id;
+            review.connect_clicked(move |_| {
+                sender.input(AppMessage::ReviewGame(game_id));
+            });
+            row.append(&review);
+
+            self.history_list.append(&row);
+        }
+    }
+
+    fn push_chat_line(&mut self, in_game: bool, line: String) {
+        let log = if in_game {
+            &mut self.game_chat
+        } else {
+            &mut self.lobby_chat
+        };
+        log.push(line);
+        if log.len() > CHAT_HISTORY {
+            log.remove(0);
+        }
+    }
+
+    fn show_players(&self, usernames: Vec<String>, sender: &AsyncComponentSender<Self>) {
+        while let Some(row) = self.players_list.first_child() {
+            self.players_list.remove(&row);
+        }
+
+        for username in usernames {
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+            row.append(&gtk::Label::new(Some(&username)));
+
+            let challenge = gtk::Button::with_label("Challenge");
+            let sender = sender.clone();
+            challenge.connect_clicked(move |_| {
+                sender.input(AppMessage::Challenge(username.clone()));
+            });
+            row.append(&challenge);
+
+            self.players_list.append(&row);
+        }
+    }
 }
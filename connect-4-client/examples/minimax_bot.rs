@@ -0,0 +1,34 @@
+use connect_4_client::bot::{run_bot, BotOptions};
+use connect_4_core::bot::MinimaxPlayer;
+use connect_4_core::logger::{system_logger, LoggerOptions};
+use log::LevelFilter;
+use std::time::Duration;
+
+/// Usage: `minimax_bot [username] [depth] [address]`
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    system_logger(LoggerOptions {
+        log_level: LevelFilter::Info,
+        log_file: None,
+    })?
+    .apply()?;
+
+    let mut args = std::env::args().skip(1);
+    let username = args.next().unwrap_or_else(|| "MinimaxBot".to_string());
+    let depth = match args.next() {
+        Some(depth) => depth.parse()?,
+        None => 6,
+    };
+    let address = args.next().unwrap_or_else(|| "localhost:3000".to_string());
+
+    run_bot(
+        address,
+        BotOptions {
+            username,
+            games: None,
+            move_time: Some(Duration::from_secs(5)),
+        },
+        MinimaxPlayer::new(depth),
+    )
+    .await
+}
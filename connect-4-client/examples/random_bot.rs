@@ -0,0 +1,29 @@
+use connect_4_client::bot::{run_bot, BotOptions};
+use connect_4_core::bot::RandomPlayer;
+use connect_4_core::logger::{system_logger, LoggerOptions};
+use log::LevelFilter;
+
+/// Usage: `random_bot [username] [address]`
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    system_logger(LoggerOptions {
+        log_level: LevelFilter::Info,
+        log_file: None,
+    })?
+    .apply()?;
+
+    let mut args = std::env::args().skip(1);
+    let username = args.next().unwrap_or_else(|| "RandomBot".to_string());
+    let address = args.next().unwrap_or_else(|| "localhost:3000".to_string());
+
+    run_bot(
+        address,
+        BotOptions {
+            username,
+            games: None,
+            move_time: None,
+        },
+        RandomPlayer::new(),
+    )
+    .await
+}
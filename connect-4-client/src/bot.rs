@@ -0,0 +1,108 @@
+//! Puts a [`Player`] on a server through the same login, lobby and game protocol people use.
+
+use crate::{Connection, Event};
+use connect_4_core::board::{self, Board};
+use connect_4_core::bot::{Clock, Player};
+use std::time::Duration;
+use tokio::net::ToSocketAddrs;
+
+/// How long to wait for the server to accept a move before choosing one again, it doesn't answer
+/// moves it rejects.
+const ACK_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct BotOptions {
+    pub username: String,
+    /// How many games to play before disconnecting, `None` to keep queueing forever.
+    pub games: Option<usize>,
+    pub move_time: Option<Duration>,
+}
+
+/// Logs in as a bot and keeps looking for games, asking the player for a move whenever it's its
+/// turn. Returns once the requested number of games has been played or the server went away.
+pub async fn run_bot<A, P>(address: A, options: BotOptions, mut player: P) -> anyhow::Result<()>
+where
+    A: ToSocketAddrs + Send + 'static,
+    P: Player + Send + 'static,
+{
+    let mut connection = Connection::connect(address).await?;
+    if !connection.login_bot(&options.username).await? {
+        anyhow::bail!("The server refused the username {}.", options.username);
+    }
+    log::info!(
+        "Logged in as {} playing {}.",
+        options.username,
+        player.name()
+    );
+    connection.find_game()?;
+
+    let mut board = Board::new();
+    let mut me = None;
+    let mut awaiting_ack = false;
+    let mut games_played = 0;
+    loop {
+        let event = if awaiting_ack {
+            tokio::time::timeout(ACK_TIMEOUT, connection.next_event()).await
+        } else {
+            Ok(connection.next_event().await)
+        };
+        let event = match event {
+            Ok(Some(event)) => Some(event),
+            Ok(None) => break,
+            Err(_) => {
+                log::warn!("The server didn't accept the last move, choosing again.");
+                awaiting_ack = false;
+                None
+            }
+        };
+        match event {
+            Some(Event::GameFound) => {
+                board = Board::new();
+                me = None;
+                awaiting_ack = false;
+            }
+            Some(Event::OpponentJoined {
+                username,
+                i_go_first,
+                ..
+            }) => {
+                log::info!("Playing against {}.", username);
+                me = Some(if i_go_first {
+                    board::Player::First
+                } else {
+                    board::Player::Second
+                });
+            }
+            Some(Event::PiecePlaced { column, .. }) => {
+                // the opponent only moves once our move went through
+                awaiting_ack = false;
+                if let Err(err) = board.drop_piece(column) {
+                    log::warn!("The server placed an illegal piece: {}", err);
+                }
+            }
+            Some(Event::GameOver(outcome)) => {
+                games_played += 1;
+                log::info!("Game {} finished: {:?}.", games_played, outcome);
+                me = None;
+                if matches!(options.games, Some(games) if games_played >= games) {
+                    return Ok(());
+                }
+                connection.find_game()?;
+            }
+            _ => {}
+        }
+
+        if !awaiting_ack && me == Some(board.next_player()) && board.outcome().is_none() {
+            let clock = options.move_time.map_or_else(Clock::unlimited, Clock::new);
+            // searching can take a while, keep it off the runtime
+            let (returned, column) = tokio::task::spawn_blocking(move || {
+                let column = player.choose_move(&board, &clock);
+                (player, column)
+            })
+            .await?;
+            player = returned;
+            connection.place(column)?;
+            awaiting_ack = true;
+        }
+    }
+    Ok(())
+}
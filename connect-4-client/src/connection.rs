@@ -0,0 +1,725 @@
+use crate::event::{Event, GameOutcome};
+use connect_4_core::drax::prelude::DraxReadExt;
+use connect_4_core::encode;
+use connect_4_core::packets::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::runtime::Builder;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+use tokio::task::LocalSet;
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Which set of packets one side of the connection is currently speaking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConnectionState {
+    Login,
+    Lobby,
+    Game,
+    Spectate,
+}
+
+#[derive(Debug)]
+enum Command {
+    RequestUsername {
+        username: String,
+        bot: bool,
+        result: oneshot::Sender<bool>,
+    },
+    Lobby(ServerboundLobbyPacket),
+    Chat {
+        message: String,
+    },
+    PlacePiece {
+        column: u8,
+    },
+    RequestHint,
+    LeaveSpectate,
+    AcquireUsername,
+    AcquireGame,
+    AcquireSpectate,
+    AcquireLobby,
+}
+
+#[derive(Default)]
+struct Transactions {
+    last_id: i32,
+    usernames: HashMap<i32, (String, oneshot::Sender<bool>)>,
+    placements: HashMap<i32, u8>,
+}
+
+impl Transactions {
+    fn next_id(&mut self) -> i32 {
+        self.last_id = self.last_id.wrapping_add(1);
+        self.last_id
+    }
+}
+
+/// A connection to a Connect 4 server.
+///
+/// The socket is driven on its own thread, which answers keep-alives and follows the server
+/// between the login, lobby, game and spectator states, so callers only deal in typed requests
+/// and [`Event`]s. Requests that don't make sense in the current state are dropped.
+pub struct Connection {
+    commands: UnboundedSender<Command>,
+    events: UnboundedReceiver<Event>,
+}
+
+impl Connection {
+    pub async fn connect<A: ToSocketAddrs + Send + 'static>(address: A) -> anyhow::Result<Self> {
+        let (command_sender, command_receiver) = unbounded_channel();
+        let (event_sender, event_receiver) = unbounded_channel();
+        let (connected_sender, connected_receiver) = oneshot::channel();
+        let rt = Builder::new_current_thread().enable_all().build()?;
+
+        std::thread::spawn(move || {
+            let local = LocalSet::new();
+            local.block_on(&rt, async move {
+                match TcpStream::connect(address).await {
+                    Ok(stream) => {
+                        let _ = connected_sender.send(Ok(()));
+                        drive(stream, command_receiver, event_sender).await;
+                    }
+                    Err(err) => {
+                        let _ = connected_sender.send(Err(err));
+                    }
+                }
+            });
+        });
+
+        connected_receiver.await??;
+        Ok(Self {
+            commands: command_sender,
+            events: event_receiver,
+        })
+    }
+
+    /// Asks for a username and waits for the server's answer, moving into the lobby on success.
+    pub async fn login(&self, username: &str) -> anyhow::Result<bool> {
+        self.request_username(username, false).await
+    }
+
+    /// Like [`Connection::login`], but tells the server the account is played by a program so
+    /// opponents can be told they're facing a bot.
+    pub async fn login_bot(&self, username: &str) -> anyhow::Result<bool> {
+        self.request_username(username, true).await
+    }
+
+    async fn request_username(&self, username: &str, bot: bool) -> anyhow::Result<bool> {
+        let (result, answer) = oneshot::channel();
+        self.send(Command::RequestUsername {
+            username: username.to_string(),
+            bot,
+            result,
+        })?;
+        // a dropped answer means another login attempt won the race
+        Ok(answer.await.unwrap_or(false))
+    }
+
+    /// Receives the next event, `None` once the server closed the connection.
+    pub async fn next_event(&mut self) -> Option<Event> {
+        self.events.recv().await
+    }
+
+    pub fn find_game(&self) -> anyhow::Result<()> {
+        self.lobby(ServerboundLobbyPacket::RequestGame)
+    }
+
+    pub fn cancel_search(&self) -> anyhow::Result<()> {
+        self.lobby(ServerboundLobbyPacket::CancelSearch)
+    }
+
+    pub fn create_room(&self) -> anyhow::Result<()> {
+        self.lobby(ServerboundLobbyPacket::CreateRoom)
+    }
+
+    pub fn join_room(&self, code: &str) -> anyhow::Result<()> {
+        self.lobby(ServerboundLobbyPacket::JoinRoom {
+            code: code.to_string(),
+        })
+    }
+
+    pub fn close_room(&self) -> anyhow::Result<()> {
+        self.lobby(ServerboundLobbyPacket::CloseRoom)
+    }
+
+    pub fn list_players(&self) -> anyhow::Result<()> {
+        self.lobby(ServerboundLobbyPacket::ListPlayers)
+    }
+
+    pub fn challenge(&self, username: &str, rules: GameRules) -> anyhow::Result<()> {
+        self.lobby(ServerboundLobbyPacket::Challenge {
+            username: username.to_string(),
+            rules,
+        })
+    }
+
+    pub fn accept_challenge(&self, username: &str) -> anyhow::Result<()> {
+        self.lobby(ServerboundLobbyPacket::AcceptChallenge {
+            username: username.to_string(),
+        })
+    }
+
+    pub fn decline_challenge(&self, username: &str) -> anyhow::Result<()> {
+        self.lobby(ServerboundLobbyPacket::DeclineChallenge {
+            username: username.to_string(),
+        })
+    }
+
+    pub fn list_games(&self) -> anyhow::Result<()> {
+        self.lobby(ServerboundLobbyPacket::ListGames)
+    }
+
+    pub fn spectate(&self, game_id: i32) -> anyhow::Result<()> {
+        self.lobby(ServerboundLobbyPacket::Spectate { game_id })
+    }
+
+    pub fn leave_spectate(&self) -> anyhow::Result<()> {
+        self.send(Command::LeaveSpectate)
+    }
+
+    pub fn request_history(&self, username: &str, page: i32) -> anyhow::Result<()> {
+        self.lobby(ServerboundLobbyPacket::RequestHistory {
+            username: username.to_string(),
+            page,
+        })
+    }
+
+    pub fn request_game_record(&self, game_id: i32) -> anyhow::Result<()> {
+        self.lobby(ServerboundLobbyPacket::RequestGameRecord { game_id })
+    }
+
+    pub fn request_leaderboard(&self, page: i32) -> anyhow::Result<()> {
+        self.lobby(ServerboundLobbyPacket::RequestLeaderboard { page })
+    }
+
+    pub fn request_profile(&self, username: &str) -> anyhow::Result<()> {
+        self.lobby(ServerboundLobbyPacket::RequestProfile {
+            username: username.to_string(),
+        })
+    }
+
+    /// Asks the server to go over an archived game with the solver, which can take a while.
+    pub fn request_analysis(&self, game_id: i32) -> anyhow::Result<()> {
+        self.lobby(ServerboundLobbyPacket::RequestAnalysis { game_id })
+    }
+
+    /// Sends a chat message to the lobby or, while playing, to the opponent.
+    pub fn chat(&self, message: &str) -> anyhow::Result<()> {
+        self.send(Command::Chat {
+            message: message.to_string(),
+        })
+    }
+
+    pub fn place(&self, column: u8) -> anyhow::Result<()> {
+        self.send(Command::PlacePiece { column })
+    }
+
+    /// Asks the server for the move it would play, answered by [`Event::Hint`] or
+    /// [`Event::HintUnavailable`] when the game doesn't allow hints or it isn't our turn.
+    pub fn request_hint(&self) -> anyhow::Result<()> {
+        self.send(Command::RequestHint)
+    }
+
+    fn lobby(&self, packet: ServerboundLobbyPacket) -> anyhow::Result<()> {
+        self.send(Command::Lobby(packet))
+    }
+
+    fn send(&self, command: Command) -> anyhow::Result<()> {
+        self.commands
+            .send(command)
+            .map_err(|_| anyhow::anyhow!("The connection to the server is closed."))
+    }
+}
+
+async fn drive(
+    stream: TcpStream,
+    mut commands: UnboundedReceiver<Command>,
+    events: UnboundedSender<Event>,
+) {
+    let (read, write) = stream.into_split();
+    let transactions = Rc::new(RefCell::new(Transactions::default()));
+    let (acquire_sender, mut acquire_receiver) = unbounded_channel();
+    let mut reader = tokio::task::spawn_local(
+        Reader {
+            state: ConnectionState::Login,
+            transactions: transactions.clone(),
+            acquire: acquire_sender,
+            events,
+        }
+        .run(read),
+    );
+    let mut writer = Writer {
+        write,
+        state: ConnectionState::Login,
+        transactions,
+    };
+
+    let mut keep_alive = tokio::time::interval(KEEP_ALIVE_INTERVAL);
+    loop {
+        tokio::select! {
+            // state changes the reader asked for have to reach the server before anything the
+            // caller sent in response to the event that came with them
+            biased;
+            Some(command) = acquire_receiver.recv() => writer.handle(command).await,
+            command = commands.recv() => match command {
+                Some(command) => writer.handle(command).await,
+                None => break,
+            },
+            _ = keep_alive.tick() => writer.keep_alive().await,
+            _ = &mut reader => break,
+        }
+    }
+    reader.abort();
+}
+
+struct Writer {
+    write: OwnedWriteHalf,
+    state: ConnectionState,
+    transactions: Rc<RefCell<Transactions>>,
+}
+
+impl Writer {
+    async fn keep_alive(&mut self) {
+        match self.state {
+            ConnectionState::Login => {
+                encode!(
+                    self.write,
+                    ServerboundLoginPacket,
+                    ServerboundLoginPacket::KeepAlive
+                );
+            }
+            ConnectionState::Lobby => {
+                encode!(
+                    self.write,
+                    ServerboundLobbyPacket,
+                    ServerboundLobbyPacket::KeepAlive
+                );
+            }
+            ConnectionState::Game => {
+                encode!(
+                    self.write,
+                    ServerboundGamePacket,
+                    ServerboundGamePacket::KeepAlive
+                );
+            }
+            ConnectionState::Spectate => {
+                encode!(
+                    self.write,
+                    ServerboundSpectatorPacket,
+                    ServerboundSpectatorPacket::KeepAlive
+                );
+            }
+        }
+    }
+
+    async fn handle(&mut self, command: Command) {
+        match (self.state, command) {
+            (
+                ConnectionState::Login,
+                Command::RequestUsername {
+                    username,
+                    bot,
+                    result,
+                },
+            ) => {
+                let transaction_id = {
+                    let mut transactions = self.transactions.borrow_mut();
+                    let transaction_id = transactions.next_id();
+                    transactions
+                        .usernames
+                        .insert(transaction_id, (username.clone(), result));
+                    transaction_id
+                };
+                encode!(
+                    self.write,
+                    ServerboundLoginPacket,
+                    ServerboundLoginPacket::RequestUsername {
+                        username,
+                        transaction_id,
+                        bot
+                    }
+                );
+            }
+            (ConnectionState::Login, Command::AcquireUsername) => {
+                encode!(
+                    self.write,
+                    ServerboundLoginPacket,
+                    ServerboundLoginPacket::AcquireUsername
+                );
+                self.state = ConnectionState::Lobby;
+            }
+            (ConnectionState::Lobby, Command::Lobby(packet)) => {
+                encode!(self.write, ServerboundLobbyPacket, packet);
+            }
+            (ConnectionState::Lobby, Command::Chat { message }) => {
+                encode!(
+                    self.write,
+                    ServerboundLobbyPacket,
+                    ServerboundLobbyPacket::Chat { message }
+                );
+            }
+            (ConnectionState::Lobby, Command::AcquireGame) => {
+                encode!(
+                    self.write,
+                    ServerboundLobbyPacket,
+                    ServerboundLobbyPacket::AcquireGame
+                );
+                self.state = ConnectionState::Game;
+            }
+            (ConnectionState::Lobby, Command::AcquireSpectate) => {
+                encode!(
+                    self.write,
+                    ServerboundLobbyPacket,
+                    ServerboundLobbyPacket::AcquireSpectate
+                );
+                self.state = ConnectionState::Spectate;
+            }
+            (ConnectionState::Game, Command::PlacePiece { column }) => {
+                let transaction_id = {
+                    let mut transactions = self.transactions.borrow_mut();
+                    let transaction_id = transactions.next_id();
+                    transactions.placements.insert(transaction_id, column);
+                    transaction_id
+                };
+                encode!(
+                    self.write,
+                    ServerboundGamePacket,
+                    ServerboundGamePacket::PlacePiece {
+                        column,
+                        transaction_id
+                    }
+                );
+            }
+            (ConnectionState::Game, Command::RequestHint) => {
+                encode!(
+                    self.write,
+                    ServerboundGamePacket,
+                    ServerboundGamePacket::RequestHint
+                );
+            }
+            (ConnectionState::Game, Command::Chat { message }) => {
+                encode!(
+                    self.write,
+                    ServerboundGamePacket,
+                    ServerboundGamePacket::Chat { message }
+                );
+            }
+            (ConnectionState::Game, Command::AcquireLobby) => {
+                encode!(
+                    self.write,
+                    ServerboundGamePacket,
+                    ServerboundGamePacket::AcquireLobby
+                );
+                self.state = ConnectionState::Lobby;
+            }
+            (ConnectionState::Spectate, Command::LeaveSpectate) => {
+                encode!(
+                    self.write,
+                    ServerboundSpectatorPacket,
+                    ServerboundSpectatorPacket::AcquireLobby
+                );
+                self.state = ConnectionState::Lobby;
+            }
+            (state, command) => {
+                log::warn!(
+                    "Dropping {:?} sent while in the {:?} state.",
+                    command,
+                    state
+                );
+            }
+        }
+    }
+}
+
+struct Reader {
+    state: ConnectionState,
+    transactions: Rc<RefCell<Transactions>>,
+    acquire: UnboundedSender<Command>,
+    events: UnboundedSender<Event>,
+}
+
+macro_rules! decode_or_quit {
+    ($read:ident, $ty:ty) => {
+        match $read.decode_component::<(), $ty>(&mut ()).await {
+            Ok(packet) => packet,
+            Err(err) => {
+                log::error!("Error during packet reads: {}", err);
+                return;
+            }
+        }
+    };
+}
+
+impl Reader {
+    async fn run(mut self, mut read: OwnedReadHalf) {
+        loop {
+            // the state is switched as soon as the packet that changes it is read, so the next
+            // packet is always decoded as the right type
+            let event = match self.state {
+                ConnectionState::Login => {
+                    self.on_login(decode_or_quit!(read, ClientboundLoginPacket));
+                    None
+                }
+                ConnectionState::Lobby => {
+                    self.on_lobby(decode_or_quit!(read, ClientboundLobbyPacket))
+                }
+                ConnectionState::Game => self.on_game(decode_or_quit!(read, ClientboundGamePacket)),
+                ConnectionState::Spectate => {
+                    self.on_spectator(decode_or_quit!(read, ClientboundSpectatorPacket))
+                }
+            };
+            if let Some(event) = event {
+                if self.events.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn switch(&mut self, state: ConnectionState, acquire: Option<Command>) {
+        if let Some(acquire) = acquire {
+            let _ = self.acquire.send(acquire);
+        }
+        self.state = state;
+    }
+
+    fn on_login(&mut self, packet: ClientboundLoginPacket) {
+        match packet {
+            ClientboundLoginPacket::KeepAlive => {}
+            ClientboundLoginPacket::UsernameResult {
+                success,
+                transaction_id,
+            } => {
+                let mut transactions = self.transactions.borrow_mut();
+                let Some((username, result)) = transactions.usernames.remove(&transaction_id)
+                else {
+                    log::warn!(
+                        "Username result for unknown transaction {}.",
+                        transaction_id
+                    );
+                    return;
+                };
+                if success {
+                    transactions.usernames.clear();
+                    drop(transactions);
+                    log::debug!("Logged in as {}.", username);
+                    self.switch(ConnectionState::Lobby, Some(Command::AcquireUsername));
+                }
+                let _ = result.send(success);
+            }
+        }
+    }
+
+    fn on_lobby(&mut self, packet: ClientboundLobbyPacket) -> Option<Event> {
+        Some(match packet {
+            ClientboundLobbyPacket::KeepAlive => return None,
+            ClientboundLobbyPacket::GameFound => {
+                self.switch(ConnectionState::Game, Some(Command::AcquireGame));
+                Event::GameFound
+            }
+            ClientboundLobbyPacket::QueueUpdate {
+                position,
+                estimated_wait_seconds,
+            } => Event::QueueUpdate {
+                position,
+                estimated_wait_seconds,
+            },
+            ClientboundLobbyPacket::SearchCancelled => Event::SearchCancelled,
+            ClientboundLobbyPacket::RoomCreated { code } => Event::RoomCreated { code },
+            ClientboundLobbyPacket::RoomJoinFailed { code } => Event::RoomJoinFailed { code },
+            ClientboundLobbyPacket::RoomClosed { expired } => Event::RoomClosed { expired },
+            ClientboundLobbyPacket::PlayerList { usernames } => Event::PlayerList { usernames },
+            ClientboundLobbyPacket::ChallengeReceived { username, rules } => {
+                Event::ChallengeReceived { username, rules }
+            }
+            ClientboundLobbyPacket::ChallengeDeclined { username } => {
+                Event::ChallengeDeclined { username }
+            }
+            ClientboundLobbyPacket::ChallengeFailed { username } => {
+                Event::ChallengeFailed { username }
+            }
+            ClientboundLobbyPacket::Chat { username, message } => Event::Chat {
+                username,
+                message,
+                in_game: false,
+            },
+            ClientboundLobbyPacket::ChatRejected { reason } => Event::ChatRejected {
+                reason,
+                in_game: false,
+            },
+            ClientboundLobbyPacket::GameList { games } => Event::GameList { games },
+            ClientboundLobbyPacket::SpectateStarted { game_id } => {
+                self.switch(ConnectionState::Spectate, Some(Command::AcquireSpectate));
+                Event::SpectateStarted { game_id }
+            }
+            ClientboundLobbyPacket::SpectateFailed { game_id } => Event::SpectateFailed { game_id },
+            ClientboundLobbyPacket::HistoryPage {
+                username,
+                page,
+                has_more,
+                games,
+            } => Event::HistoryPage {
+                username,
+                page,
+                has_more,
+                games,
+            },
+            ClientboundLobbyPacket::GameRecord {
+                game_id,
+                first_player,
+                second_player,
+                winner,
+                abandoned,
+                moves,
+                finished_at,
+            } => Event::GameRecord {
+                game_id,
+                first_player,
+                second_player,
+                winner,
+                abandoned,
+                moves,
+                finished_at,
+            },
+            ClientboundLobbyPacket::GameRecordNotFound { game_id } => {
+                Event::GameRecordNotFound { game_id }
+            }
+            ClientboundLobbyPacket::Leaderboard {
+                page,
+                has_more,
+                entries,
+            } => Event::Leaderboard {
+                page,
+                has_more,
+                entries,
+            },
+            ClientboundLobbyPacket::Profile {
+                username,
+                rank,
+                rating,
+                wins,
+                losses,
+                draws,
+                current_streak,
+                best_streak,
+                recent_form,
+            } => Event::Profile {
+                username,
+                rank,
+                rating,
+                wins,
+                losses,
+                draws,
+                current_streak,
+                best_streak,
+                recent_form,
+            },
+            ClientboundLobbyPacket::ProfileNotFound { username } => {
+                Event::ProfileNotFound { username }
+            }
+            ClientboundLobbyPacket::GameAnalysis { game_id, moves } => {
+                Event::GameAnalysis { game_id, moves }
+            }
+            ClientboundLobbyPacket::GameAnalysisNotFound { game_id } => {
+                Event::GameAnalysisNotFound { game_id }
+            }
+        })
+    }
+
+    fn on_game(&mut self, packet: ClientboundGamePacket) -> Option<Event> {
+        Some(match packet {
+            ClientboundGamePacket::KeepAlive => return None,
+            ClientboundGamePacket::OpponentJoin {
+                username,
+                i_go_first,
+                opponent_is_bot,
+                hints_allowed,
+            } => Event::OpponentJoined {
+                username,
+                i_go_first,
+                opponent_is_bot,
+                hints_allowed,
+            },
+            ClientboundGamePacket::PlacePieceAck { transaction_id } => {
+                let mut transactions = self.transactions.borrow_mut();
+                let column = transactions.placements.remove(&transaction_id)?;
+                transactions.placements.clear();
+                Event::PiecePlaced { column, mine: true }
+            }
+            ClientboundGamePacket::OpponentPlacedPiece { column } => Event::PiecePlaced {
+                column,
+                mine: false,
+            },
+            ClientboundGamePacket::EarlyExit => self.finish_game(GameOutcome::OpponentLeft),
+            ClientboundGamePacket::PlayerWin { me } => self.finish_game(if me {
+                GameOutcome::Won
+            } else {
+                GameOutcome::Lost
+            }),
+            ClientboundGamePacket::Draw => self.finish_game(GameOutcome::Draw),
+            ClientboundGamePacket::Chat { username, message } => Event::Chat {
+                username,
+                message,
+                in_game: true,
+            },
+            ClientboundGamePacket::ChatRejected { reason } => Event::ChatRejected {
+                reason,
+                in_game: true,
+            },
+            ClientboundGamePacket::SpectatorCount { count } => Event::SpectatorCount { count },
+            ClientboundGamePacket::Hint {
+                column,
+                score,
+                exact,
+            } => Event::Hint {
+                column,
+                score,
+                exact,
+            },
+            ClientboundGamePacket::HintUnavailable => Event::HintUnavailable,
+        })
+    }
+
+    fn finish_game(&mut self, outcome: GameOutcome) -> Event {
+        self.transactions.borrow_mut().placements.clear();
+        self.switch(ConnectionState::Lobby, Some(Command::AcquireLobby));
+        Event::GameOver(outcome)
+    }
+
+    fn on_spectator(&mut self, packet: ClientboundSpectatorPacket) -> Option<Event> {
+        Some(match packet {
+            ClientboundSpectatorPacket::KeepAlive => return None,
+            ClientboundSpectatorPacket::BoardSnapshot {
+                first_player,
+                second_player,
+                board,
+                first_player_to_move,
+            } => Event::SpectateSnapshot {
+                first_player,
+                second_player,
+                board,
+                first_player_to_move,
+            },
+            ClientboundSpectatorPacket::PiecePlaced {
+                column,
+                first_player,
+            } => Event::SpectatePiecePlaced {
+                column,
+                first_player,
+            },
+            ClientboundSpectatorPacket::GameOver { winner, abandoned } => {
+                Event::SpectateGameOver { winner, abandoned }
+            }
+            ClientboundSpectatorPacket::ReturnedToLobby => {
+                self.switch(ConnectionState::Lobby, None);
+                Event::SpectateLeft
+            }
+        })
+    }
+}
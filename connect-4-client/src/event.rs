@@ -0,0 +1,146 @@
+use connect_4_core::packets::{
+    GameRules, GameSummary, HistoryEntry, LeaderboardEntry, MoveEvaluation,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOutcome {
+    Won,
+    Lost,
+    Draw,
+    OpponentLeft,
+}
+
+/// Everything the server can tell a connected client, after the connection has taken care of
+/// keep-alives and state transitions.
+#[derive(Debug)]
+pub enum Event {
+    QueueUpdate {
+        position: i32,
+        estimated_wait_seconds: i32,
+    },
+    SearchCancelled,
+    RoomCreated {
+        code: String,
+    },
+    RoomJoinFailed {
+        code: String,
+    },
+    RoomClosed {
+        expired: bool,
+    },
+    PlayerList {
+        usernames: Vec<String>,
+    },
+    ChallengeReceived {
+        username: String,
+        rules: GameRules,
+    },
+    ChallengeDeclined {
+        username: String,
+    },
+    ChallengeFailed {
+        username: String,
+    },
+    Chat {
+        username: String,
+        message: String,
+        in_game: bool,
+    },
+    ChatRejected {
+        reason: String,
+        in_game: bool,
+    },
+    GameList {
+        games: Vec<GameSummary>,
+    },
+    HistoryPage {
+        username: String,
+        page: i32,
+        has_more: bool,
+        games: Vec<HistoryEntry>,
+    },
+    GameRecord {
+        game_id: i32,
+        first_player: String,
+        second_player: String,
+        winner: String,
+        abandoned: bool,
+        moves: Vec<u8>,
+        finished_at: i64,
+    },
+    GameRecordNotFound {
+        game_id: i32,
+    },
+    Leaderboard {
+        page: i32,
+        has_more: bool,
+        entries: Vec<LeaderboardEntry>,
+    },
+    Profile {
+        username: String,
+        rank: i32,
+        rating: i32,
+        wins: i32,
+        losses: i32,
+        draws: i32,
+        current_streak: i32,
+        best_streak: i32,
+        recent_form: String,
+    },
+    ProfileNotFound {
+        username: String,
+    },
+    /// The solver's verdict on every move of an archived game, see
+    /// [`connect_4_core::analysis`].
+    GameAnalysis {
+        game_id: i32,
+        moves: Vec<MoveEvaluation>,
+    },
+    GameAnalysisNotFound {
+        game_id: i32,
+    },
+    GameFound,
+    OpponentJoined {
+        username: String,
+        i_go_first: bool,
+        opponent_is_bot: bool,
+        hints_allowed: bool,
+    },
+    PiecePlaced {
+        column: u8,
+        mine: bool,
+    },
+    SpectatorCount {
+        count: i32,
+    },
+    /// The column the server suggests, with a solver score when `exact` and a heuristic guess
+    /// otherwise.
+    Hint {
+        column: u8,
+        score: i32,
+        exact: bool,
+    },
+    HintUnavailable,
+    GameOver(GameOutcome),
+    SpectateStarted {
+        game_id: i32,
+    },
+    SpectateFailed {
+        game_id: i32,
+    },
+    SpectateSnapshot {
+        first_player: String,
+        second_player: String,
+        board: Vec<u8>,
+        first_player_to_move: bool,
+    },
+    SpectatePiecePlaced {
+        column: u8,
+        first_player: bool,
+    },
+    SpectateGameOver {
+        winner: String,
+        abandoned: bool,
+    },
+    SpectateLeft,
+}
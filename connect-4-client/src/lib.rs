@@ -0,0 +1,6 @@
+pub mod bot;
+pub mod connection;
+pub mod event;
+
+pub use connection::Connection;
+pub use event::{Event, GameOutcome};
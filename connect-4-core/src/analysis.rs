@@ -0,0 +1,134 @@
+//! Going over a finished game with the solver.
+//!
+//! Every move is compared against the best one in its position. A move that keeps the result the
+//! best move would have had but takes longer to win, or loses sooner, is an inaccuracy. Turning a
+//! win into a draw is a mistake, and turning a win or a draw into a loss is a blunder.
+
+use crate::board::{Board, DropError};
+use crate::book::OpeningBook;
+use crate::solver::{self, ParallelSolver};
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Judgement {
+    Best,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Judgement {
+    /// Judges a move scoring `score` in a position where the best move scores `best_score`.
+    pub fn of(best_score: i32, score: i32) -> Self {
+        if score >= best_score {
+            Judgement::Best
+        } else if score.signum() == best_score.signum() {
+            Judgement::Inaccuracy
+        } else if score < 0 {
+            Judgement::Blunder
+        } else {
+            Judgement::Mistake
+        }
+    }
+
+    /// The usual annotation symbol, empty for the best move.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Judgement::Best => "",
+            Judgement::Inaccuracy => "?!",
+            Judgement::Mistake => "?",
+            Judgement::Blunder => "??",
+        }
+    }
+}
+
+impl Display for Judgement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Judgement::Best => write!(f, "best"),
+            Judgement::Inaccuracy => write!(f, "inaccuracy"),
+            Judgement::Mistake => write!(f, "mistake"),
+            Judgement::Blunder => write!(f, "blunder"),
+        }
+    }
+}
+
+/// A move of the game next to the one the solver prefers, scored from the mover's side.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MoveAnalysis {
+    pub column: u8,
+    pub score: i32,
+    pub best_move: u8,
+    pub best_score: i32,
+}
+
+impl MoveAnalysis {
+    pub fn judgement(&self) -> Judgement {
+        Judgement::of(self.best_score, self.score)
+    }
+}
+
+/// Scores every move of a game, giving the solver up to `time_per_move` for each position the
+/// book doesn't cover. Moves whose position couldn't be solved in time are left as `None`.
+///
+/// Positions are solved from the last move back, so the ones near the end fill the solver's table
+/// for the harder ones before them.
+pub fn analyze_game(
+    moves: &[u8],
+    solver: &mut ParallelSolver,
+    book: Option<&OpeningBook>,
+    time_per_move: Duration,
+) -> Result<Vec<Option<MoveAnalysis>>, DropError> {
+    let mut positions = Vec::with_capacity(moves.len());
+    let mut board = Board::new();
+    for column in moves {
+        positions.push(board);
+        board.drop_piece(*column)?;
+    }
+
+    let mut analysis = vec![None; moves.len()];
+    for (index, board) in positions.iter().enumerate().rev() {
+        let scores = match book.and_then(|book| book.analyze(board)) {
+            Some(scores) => Some(scores),
+            None => solver.analyze_until(board, Instant::now() + time_per_move),
+        };
+        let Some(scores) = scores else {
+            continue;
+        };
+        let column = moves[index];
+        let best_move = solver::best_move(&scores).expect("the game wasn't over before the move");
+        analysis[index] = Some(MoveAnalysis {
+            column,
+            score: scores[column as usize].expect("the move was legal"),
+            best_move,
+            best_score: scores[best_move as usize].expect("the best move is legal"),
+        });
+    }
+    Ok(analysis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn judges_moves_by_what_they_give_away() {
+        assert_eq!(Judgement::of(5, 5), Judgement::Best);
+        assert_eq!(Judgement::of(5, 2), Judgement::Inaccuracy);
+        assert_eq!(Judgement::of(-2, -5), Judgement::Inaccuracy);
+        assert_eq!(Judgement::of(5, 0), Judgement::Mistake);
+        assert_eq!(Judgement::of(5, -1), Judgement::Blunder);
+        assert_eq!(Judgement::of(0, -3), Judgement::Blunder);
+    }
+
+    #[test]
+    fn rejects_illegal_games() {
+        let mut solver = ParallelSolver::new(1);
+        let moves = [0; 7];
+        assert_eq!(
+            analyze_game(&moves, &mut solver, None, Duration::ZERO),
+            Err(DropError::ColumnFull(0))
+        );
+    }
+}
@@ -0,0 +1,393 @@
+use crate::zobrist;
+use std::fmt::{Display, Formatter};
+
+pub const WIDTH: usize = 7;
+pub const HEIGHT: usize = 6;
+
+pub(crate) const COLUMN_BITS: usize = HEIGHT + 1;
+pub(crate) const BOTTOM_ROW: u64 = bottom_row();
+pub(crate) const BOARD_MASK: u64 = BOTTOM_ROW * ((1 << HEIGHT) - 1);
+
+const fn bottom_row() -> u64 {
+    let mut mask = 0;
+    let mut column = 0;
+    while column < WIDTH {
+        mask |= 1 << (column * COLUMN_BITS);
+        column += 1;
+    }
+    mask
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Player {
+    First,
+    Second,
+}
+
+impl Player {
+    pub fn other(self) -> Self {
+        match self {
+            Player::First => Player::Second,
+            Player::Second => Player::First,
+        }
+    }
+
+    pub(crate) fn index(self) -> usize {
+        match self {
+            Player::First => 0,
+            Player::Second => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    Win(Player),
+    Draw,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropError {
+    ColumnOutOfRange(u8),
+    ColumnFull(u8),
+    GameOver,
+}
+
+impl Display for DropError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DropError::ColumnOutOfRange(column) => write!(
+                f,
+                "column {} doesn't exist, columns go from 1 to {WIDTH}",
+                *column as usize + 1
+            ),
+            DropError::ColumnFull(column) => write!(f, "column {} is full", column + 1),
+            DropError::GameOver => write!(f, "the game is already over"),
+        }
+    }
+}
+
+impl std::error::Error for DropError {}
+
+/// A connect 4 position stored as one bitboard per player.
+///
+/// Every column takes up `HEIGHT + 1` bits, the spare bit on top keeps the win checks from
+/// wrapping between columns. Columns are zero-indexed from the left and rows from the bottom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Board {
+    discs: [u64; 2],
+    heights: [u8; WIDTH],
+    moves: u8,
+    /// Follows the discs, so it never makes two equal positions compare unequal.
+    hash: u64,
+}
+
+impl Board {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn next_player(&self) -> Player {
+        if self.moves & 1 == 0 {
+            Player::First
+        } else {
+            Player::Second
+        }
+    }
+
+    pub fn move_count(&self) -> usize {
+        self.moves as usize
+    }
+
+    /// The Zobrist hash of the position, kept up to date as discs are dropped and popped. See
+    /// [`zobrist`] for how it's made up.
+    pub fn zobrist(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn height(&self, column: usize) -> usize {
+        self.heights[column] as usize
+    }
+
+    pub fn get(&self, column: usize, row: usize) -> Option<Player> {
+        if column >= WIDTH || row >= HEIGHT {
+            return None;
+        }
+        let bit = 1 << (column * COLUMN_BITS + row);
+        if self.discs[0] & bit != 0 {
+            Some(Player::First)
+        } else if self.discs[1] & bit != 0 {
+            Some(Player::Second)
+        } else {
+            None
+        }
+    }
+
+    pub fn can_drop(&self, column: u8) -> bool {
+        (column as usize) < WIDTH && self.height(column as usize) < HEIGHT
+    }
+
+    pub fn legal_moves(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..WIDTH as u8).filter(|column| self.can_drop(*column))
+    }
+
+    /// Drops a disc for the player to move into the column and returns the row it landed on.
+    pub fn drop_piece(&mut self, column: u8) -> Result<usize, DropError> {
+        if column as usize >= WIDTH {
+            return Err(DropError::ColumnOutOfRange(column));
+        }
+        if self.outcome().is_some() {
+            return Err(DropError::GameOver);
+        }
+        if !self.can_drop(column) {
+            return Err(DropError::ColumnFull(column));
+        }
+        let row = self.height(column as usize);
+        self.place(column as usize, self.next_player());
+        Ok(row)
+    }
+
+    /// Takes the top disc back out of the column, undoing the drop that put it there.
+    pub fn pop_piece(&mut self, column: u8) -> Option<Player> {
+        let column = column as usize;
+        if column >= WIDTH || self.heights[column] == 0 {
+            return None;
+        }
+        let row = self.height(column) - 1;
+        let player = self.get(column, row)?;
+        self.discs[player.index()] &= !(1 << (column * COLUMN_BITS + row));
+        self.heights[column] -= 1;
+        self.moves -= 1;
+        self.hash ^= zobrist::cell_key(player, column, row) ^ zobrist::side_key();
+        Some(player)
+    }
+
+    /// The same position reflected left to right.
+    pub fn mirrored(&self) -> Board {
+        let column_mask = (1 << COLUMN_BITS) - 1;
+        let mut mirrored = *self;
+        mirrored.discs = [0; 2];
+        mirrored.hash = self.hash;
+        for column in 0..WIDTH {
+            let target = WIDTH - 1 - column;
+            mirrored.heights[target] = self.heights[column];
+            for (player, discs) in self.discs.iter().enumerate() {
+                let bits = (discs >> (column * COLUMN_BITS)) & column_mask;
+                mirrored.discs[player] |= bits << (target * COLUMN_BITS);
+            }
+            for row in 0..self.height(column) {
+                let player = self
+                    .get(column, row)
+                    .expect("cells below the height are filled");
+                mirrored.hash ^=
+                    zobrist::cell_key(player, column, row) ^ zobrist::cell_key(player, target, row);
+            }
+        }
+        mirrored
+    }
+
+    /// A number that identifies the position, which side is to move included.
+    pub(crate) fn key(&self) -> u64 {
+        let (current, mask) = self.bitboards();
+        current + mask
+    }
+
+    /// The discs of the player to move and the discs of both players.
+    pub(crate) fn bitboards(&self) -> (u64, u64) {
+        (
+            self.discs[self.next_player().index()],
+            self.discs[0] | self.discs[1],
+        )
+    }
+
+    pub(crate) fn place(&mut self, column: usize, player: Player) {
+        let row = self.height(column);
+        self.discs[player.index()] |= 1 << (column * COLUMN_BITS + row);
+        self.heights[column] += 1;
+        self.moves += 1;
+        self.hash ^= zobrist::cell_key(player, column, row) ^ zobrist::side_key();
+    }
+
+    /// Whether dropping into the column would connect four for the player to move.
+    pub fn is_winning_move(&self, column: u8) -> bool {
+        self.wins_with(self.next_player(), column)
+    }
+
+    /// Whether a disc of the player dropped into the column would connect four, no matter whose
+    /// turn it is.
+    pub fn wins_with(&self, player: Player, column: u8) -> bool {
+        if !self.can_drop(column) {
+            return false;
+        }
+        let column = column as usize;
+        let discs = self.discs[player.index()] | 1 << (column * COLUMN_BITS + self.height(column));
+        connects_four(discs)
+    }
+
+    pub fn has_won(&self, player: Player) -> bool {
+        connects_four(self.discs[player.index()])
+    }
+
+    pub fn winner(&self) -> Option<Player> {
+        if self.has_won(Player::First) {
+            Some(Player::First)
+        } else if self.has_won(Player::Second) {
+            Some(Player::Second)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.move_count() == WIDTH * HEIGHT
+    }
+
+    pub fn outcome(&self) -> Option<Outcome> {
+        match self.winner() {
+            Some(player) => Some(Outcome::Win(player)),
+            None if self.is_full() => Some(Outcome::Draw),
+            None => None,
+        }
+    }
+
+    /// Returns the `(column, row)` cells of a connected four, if either player has one.
+    pub fn winning_line(&self) -> Option<[(usize, usize); 4]> {
+        const DIRECTIONS: [(isize, isize); 4] = [(1, 0), (0, 1), (1, 1), (1, -1)];
+        for column in 0..WIDTH {
+            for row in 0..HEIGHT {
+                let Some(player) = self.get(column, row) else {
+                    continue;
+                };
+                'direction: for (dx, dy) in DIRECTIONS {
+                    let mut line = [(column, row); 4];
+                    for (step, cell) in line.iter_mut().enumerate().skip(1) {
+                        let x = column as isize + dx * step as isize;
+                        let y = row as isize + dy * step as isize;
+                        if x < 0 || y < 0 || self.get(x as usize, y as usize) != Some(player) {
+                            continue 'direction;
+                        }
+                        *cell = (x as usize, y as usize);
+                    }
+                    return Some(line);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Draws the board as text from the top row down, `x` for the first player and `o` for the
+/// second, with the one-indexed column numbers underneath.
+impl Display for Board {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for row in (0..HEIGHT).rev() {
+            let cells = (0..WIDTH)
+                .map(|column| match self.get(column, row) {
+                    Some(Player::First) => "x",
+                    Some(Player::Second) => "o",
+                    None => ".",
+                })
+                .collect::<Vec<_>>();
+            writeln!(f, "{}", cells.join(" "))?;
+        }
+        let columns = (1..=WIDTH)
+            .map(|column| column.to_string())
+            .collect::<Vec<_>>();
+        write!(f, "{}", columns.join(" "))
+    }
+}
+
+fn connects_four(discs: u64) -> bool {
+    debug_assert_eq!(discs & !BOARD_MASK, 0);
+    for shift in [1, COLUMN_BITS, COLUMN_BITS - 1, COLUMN_BITS + 1] {
+        let pairs = discs & (discs >> shift);
+        if pairs & (pairs >> (2 * shift)) != 0 {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_game;
+
+    const DRAWN_GAME: &str = "547125662261271266215743771576315353334444";
+
+    #[test]
+    fn wins_in_every_direction() {
+        for (moves, line) in [
+            ("1122334", [(0, 0), (1, 0), (2, 0), (3, 0)]),
+            ("1212121", [(0, 0), (0, 1), (0, 2), (0, 3)]),
+            ("12233434474", [(0, 0), (1, 1), (2, 2), (3, 3)]),
+            ("76655454414", [(3, 3), (4, 2), (5, 1), (6, 0)]),
+        ] {
+            let board = parse_game(moves).unwrap();
+            assert_eq!(board.winner(), Some(Player::First), "{moves}");
+            assert_eq!(board.outcome(), Some(Outcome::Win(Player::First)));
+            assert_eq!(board.winning_line(), Some(line), "{moves}");
+            let mut before = parse_game(&moves[..moves.len() - 1]).unwrap();
+            let column = moves.as_bytes()[moves.len() - 1] - b'1';
+            assert_eq!(before.winner(), None);
+            assert!(before.is_winning_move(column), "{moves}");
+            let row = before.height(column as usize);
+            assert_eq!(before.drop_piece(column), Ok(row));
+            assert_eq!(before, board);
+        }
+    }
+
+    #[test]
+    fn does_not_wrap_between_columns() {
+        // three at the top of column one and one at the bottom of column two
+        let board = parse_game("21315117171").unwrap();
+        assert_eq!(board.get(0, HEIGHT - 1), Some(Player::First));
+        assert_eq!(board.get(1, 0), Some(Player::First));
+        assert_eq!(board.winner(), None);
+        assert_eq!(board.winning_line(), None);
+        assert!(!board.wins_with(Player::First, 1));
+    }
+
+    #[test]
+    fn pop_undoes_drop() {
+        let mut board = parse_game("4453").unwrap();
+        let before = board;
+        assert_eq!(board.drop_piece(2), Ok(1));
+        assert_eq!(board.pop_piece(2), Some(Player::First));
+        assert_eq!(board, before);
+        assert_eq!(board.next_player(), Player::First);
+        assert_eq!(board.pop_piece(0), None);
+        assert_eq!(board.pop_piece(WIDTH as u8), None);
+        for (column, player) in [(2, Player::Second), (4, Player::First)] {
+            assert_eq!(board.pop_piece(column), Some(player));
+        }
+        assert_eq!(board.pop_piece(3), Some(Player::Second));
+        assert_eq!(board.pop_piece(3), Some(Player::First));
+        assert_eq!(board, Board::new());
+    }
+
+    #[test]
+    fn full_boards_without_a_line_are_draws() {
+        let mut board = parse_game(&DRAWN_GAME[..DRAWN_GAME.len() - 1]).unwrap();
+        assert_eq!(board.outcome(), None);
+        assert_eq!(board.drop_piece(3), Ok(HEIGHT - 1));
+        assert!(board.is_full());
+        assert_eq!(board.winner(), None);
+        assert_eq!(board.winning_line(), None);
+        assert_eq!(board.outcome(), Some(Outcome::Draw));
+        assert_eq!(board.legal_moves().count(), 0);
+        assert_eq!(board.drop_piece(3), Err(DropError::GameOver));
+    }
+
+    #[test]
+    fn rejects_illegal_drops() {
+        let mut board = parse_game("444444").unwrap();
+        assert_eq!(board.drop_piece(3), Err(DropError::ColumnFull(3)));
+        assert_eq!(
+            board.drop_piece(WIDTH as u8),
+            Err(DropError::ColumnOutOfRange(WIDTH as u8))
+        );
+        let mut won = parse_game("1212121").unwrap();
+        assert_eq!(won.drop_piece(1), Err(DropError::GameOver));
+    }
+}
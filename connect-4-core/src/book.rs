@@ -0,0 +1,416 @@
+//! Solved opening positions.
+//!
+//! A book holds the solver's score of every position reachable within its depth, stored once per
+//! pair of mirrored positions. The file format is little endian:
+//!
+//! | bytes | contents                                               |
+//! |-------|--------------------------------------------------------|
+//! | 4     | magic, `C4BK`                                          |
+//! | 2     | format version                                         |
+//! | 1 + 1 | board width and height                                 |
+//! | 1     | depth in moves                                         |
+//! | 4     | number of entries                                      |
+//! | 8 × n | entries sorted by key, a 7 byte key and a signed score |
+//! | 4     | CRC-32 of everything before it                         |
+
+use crate::board::{Board, HEIGHT, WIDTH};
+use crate::solver::{self, Solver};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+
+const MAGIC: &[u8; 4] = b"C4BK";
+pub const FORMAT_VERSION: u16 = 1;
+const HEADER_LEN: usize = 13;
+const ENTRY_LEN: usize = 8;
+const KEY_LEN: usize = 7;
+const CHECKSUM_LEN: usize = 4;
+
+#[derive(Debug)]
+pub enum BookError {
+    Io(std::io::Error),
+    NotABook,
+    UnsupportedVersion(u16),
+    WrongBoardSize { width: u8, height: u8 },
+    Truncated,
+    ChecksumMismatch { expected: u32, found: u32 },
+}
+
+impl Display for BookError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BookError::Io(err) => write!(f, "{err}"),
+            BookError::NotABook => write!(f, "the file isn't an opening book"),
+            BookError::UnsupportedVersion(version) => {
+                write!(
+                    f,
+                    "book format version {version} isn't supported, expected {FORMAT_VERSION}"
+                )
+            }
+            BookError::WrongBoardSize { width, height } => {
+                write!(
+                    f,
+                    "the book is for a {width}x{height} board, not {WIDTH}x{HEIGHT}"
+                )
+            }
+            BookError::Truncated => write!(f, "the book is cut short"),
+            BookError::ChecksumMismatch { expected, found } => write!(
+                f,
+                "the book is corrupted, its checksum is {found:08x} instead of {expected:08x}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BookError {}
+
+impl From<std::io::Error> for BookError {
+    fn from(err: std::io::Error) -> Self {
+        BookError::Io(err)
+    }
+}
+
+pub struct OpeningBook {
+    depth: u8,
+    /// Sorted by key.
+    entries: Vec<(u64, i8)>,
+}
+
+/// The key shared by a position and its mirror image, they always have the same score.
+fn canonical_key(board: &Board) -> u64 {
+    board.key().min(board.mirrored().key())
+}
+
+impl OpeningBook {
+    /// Solves every position up to `depth` moves in. Only the deepest positions go through the
+    /// solver, split over `threads`, and `progress` hears how many of them are done. The ones
+    /// above are worked out from their children.
+    pub fn build(depth: u8, threads: usize, progress: impl FnMut(usize, usize)) -> Self {
+        Self::build_from(Board::new(), depth, threads, progress)
+    }
+
+    /// Like [`OpeningBook::build`], but only for the positions following `root`, `depth` moves
+    /// past it.
+    fn build_from(
+        root: Board,
+        depth: u8,
+        threads: usize,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Self {
+        let depth = depth.min((WIDTH * HEIGHT - root.move_count()) as u8 - 1);
+        let mut levels = vec![vec![root]];
+        for _ in 0..depth {
+            let mut seen = HashSet::new();
+            let mut next = Vec::new();
+            for board in levels.last().unwrap() {
+                for column in board.legal_moves() {
+                    if board.is_winning_move(column) {
+                        continue;
+                    }
+                    let mut child = *board;
+                    let _ = child.drop_piece(column);
+                    if seen.insert(canonical_key(&child)) {
+                        next.push(child);
+                    }
+                }
+            }
+            levels.push(next);
+        }
+
+        let deepest = levels.pop().unwrap_or_default();
+        let mut scores = solve_all(&deepest, threads, &mut progress);
+        for level in levels.iter().rev() {
+            for board in level {
+                let score = board
+                    .legal_moves()
+                    .map(|column| move_score(board, column, &scores))
+                    .max()
+                    .expect("positions in the book aren't over");
+                scores.insert(canonical_key(board), score as i8);
+            }
+        }
+
+        let mut entries = scores.into_iter().collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|(key, _)| *key);
+        Self {
+            depth: root.move_count() as u8 + depth,
+            entries,
+        }
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The solver's score of the position, if it's in the book.
+    pub fn score(&self, board: &Board) -> Option<i32> {
+        let key = canonical_key(board);
+        self.entries
+            .binary_search_by_key(&key, |(key, _)| *key)
+            .ok()
+            .map(|index| self.entries[index].1 as i32)
+    }
+
+    /// The score of every column like [`Solver::analyze`], if all of the positions they lead to
+    /// are in the book.
+    pub fn analyze(&self, board: &Board) -> Option<[Option<i32>; WIDTH]> {
+        if board.outcome().is_some() {
+            return None;
+        }
+        let mut scores = [None; WIDTH];
+        for column in board.legal_moves() {
+            scores[column as usize] = Some(if board.is_winning_move(column) {
+                solver::win_score(board)
+            } else {
+                let mut child = *board;
+                let _ = child.drop_piece(column);
+                -self.score(&child)?
+            });
+        }
+        Some(scores)
+    }
+
+    pub fn best_move(&self, board: &Board) -> Option<u8> {
+        solver::best_move(&self.analyze(board)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, BookError> {
+        Self::read_from(std::fs::File::open(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BookError> {
+        self.write_to(std::fs::File::create(path)?)
+    }
+
+    pub fn write_to(&self, mut writer: impl Write) -> Result<(), BookError> {
+        let mut bytes =
+            Vec::with_capacity(HEADER_LEN + self.entries.len() * ENTRY_LEN + CHECKSUM_LEN);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&[WIDTH as u8, HEIGHT as u8, self.depth]);
+        bytes.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        for (key, score) in &self.entries {
+            bytes.extend_from_slice(&key.to_le_bytes()[..KEY_LEN]);
+            bytes.push(*score as u8);
+        }
+        bytes.extend_from_slice(&crc32(&bytes).to_le_bytes());
+        writer.write_all(&bytes)?;
+        Ok(())
+    }
+
+    pub fn read_from(mut reader: impl Read) -> Result<Self, BookError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(BookError::NotABook);
+        }
+        if bytes.len() < HEADER_LEN + CHECKSUM_LEN {
+            return Err(BookError::Truncated);
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != FORMAT_VERSION {
+            return Err(BookError::UnsupportedVersion(version));
+        }
+        let (width, height, depth) = (bytes[6], bytes[7], bytes[8]);
+        if (width as usize, height as usize) != (WIDTH, HEIGHT) {
+            return Err(BookError::WrongBoardSize { width, height });
+        }
+        let count = u32::from_le_bytes([bytes[9], bytes[10], bytes[11], bytes[12]]) as usize;
+        let body_len = HEADER_LEN + count * ENTRY_LEN;
+        if bytes.len() != body_len + CHECKSUM_LEN {
+            return Err(BookError::Truncated);
+        }
+
+        let (body, checksum) = bytes.split_at(body_len);
+        let found = crc32(body);
+        let expected = u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+        if found != expected {
+            return Err(BookError::ChecksumMismatch { expected, found });
+        }
+
+        let entries = body[HEADER_LEN..]
+            .chunks_exact(ENTRY_LEN)
+            .map(|entry| {
+                let mut key = [0; 8];
+                key[..KEY_LEN].copy_from_slice(&entry[..KEY_LEN]);
+                (u64::from_le_bytes(key), entry[KEY_LEN] as i8)
+            })
+            .collect();
+        Ok(Self { depth, entries })
+    }
+}
+
+fn move_score(board: &Board, column: u8, scores: &HashMap<u64, i8>) -> i32 {
+    if board.is_winning_move(column) {
+        return solver::win_score(board);
+    }
+    let mut child = *board;
+    let _ = child.drop_piece(column);
+    -(scores[&canonical_key(&child)] as i32)
+}
+
+fn solve_all(
+    boards: &[Board],
+    threads: usize,
+    progress: &mut impl FnMut(usize, usize),
+) -> HashMap<u64, i8> {
+    let next = AtomicUsize::new(0);
+    let scores = Mutex::new(HashMap::with_capacity(boards.len()));
+    let (done_sender, done_receiver) = mpsc::channel();
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            let done_sender = done_sender.clone();
+            let (next, scores) = (&next, &scores);
+            scope.spawn(move || {
+                let mut solver = Solver::new();
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(board) = boards.get(index) else {
+                        return;
+                    };
+                    let score = solver.solve(board) as i8;
+                    scores.lock().unwrap().insert(canonical_key(board), score);
+                    let _ = done_sender.send(());
+                }
+            });
+        }
+        drop(done_sender);
+        for (done, _) in done_receiver.iter().enumerate() {
+            progress(done + 1, boards.len());
+        }
+    });
+    scores.into_inner().unwrap()
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
+
+/// The CRC-32 used by zip and png.
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, byte| {
+        CRC_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_game;
+
+    /// Positions near the start take too long to solve for a test, so the book starts later.
+    const ROOT: &str = "65465375611266";
+
+    fn book() -> OpeningBook {
+        OpeningBook::build_from(parse_game(ROOT).unwrap(), 2, 2, |_, _| {})
+    }
+
+    fn bytes(book: &OpeningBook) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        book.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// Writes the bytes back with a checksum that matches them, so only the change is checked.
+    fn rechecksum(mut bytes: Vec<u8>) -> Vec<u8> {
+        let body_len = bytes.len() - CHECKSUM_LEN;
+        let checksum = crc32(&bytes[..body_len]);
+        bytes[body_len..].copy_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let book = book();
+        let read = OpeningBook::read_from(bytes(&book).as_slice()).unwrap();
+        assert_eq!(read.depth(), book.depth());
+        assert_eq!(read.entries, book.entries);
+        for moves in ["", "4", "1", "7"] {
+            let board = parse_game(&format!("{ROOT}{moves}")).unwrap();
+            assert_eq!(
+                read.analyze(&board),
+                book.analyze(&board),
+                "after {moves:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_corrupted_bytes() {
+        let bytes = bytes(&book());
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert!(matches!(
+            OpeningBook::read_from(wrong_magic.as_slice()),
+            Err(BookError::NotABook)
+        ));
+
+        for len in [HEADER_LEN, bytes.len() - 1] {
+            assert!(matches!(
+                OpeningBook::read_from(&bytes[..len]),
+                Err(BookError::Truncated)
+            ));
+        }
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4..6].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            OpeningBook::read_from(rechecksum(wrong_version).as_slice()),
+            Err(BookError::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1
+        ));
+
+        let mut flipped = bytes.clone();
+        flipped[HEADER_LEN + KEY_LEN] ^= 1;
+        assert!(matches!(
+            OpeningBook::read_from(flipped.as_slice()),
+            Err(BookError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn agrees_with_the_solver() {
+        let book = book();
+        let mut solver = Solver::new();
+        for moves in ["", "1", "2", "3", "4", "5", "7"] {
+            let board = parse_game(&format!("{ROOT}{moves}")).unwrap();
+            assert_eq!(
+                book.analyze(&board),
+                Some(solver.analyze(&board)),
+                "after {moves:?}"
+            );
+        }
+        let too_deep = parse_game(&format!("{ROOT}12")).unwrap();
+        assert_eq!(book.analyze(&too_deep), None);
+    }
+}
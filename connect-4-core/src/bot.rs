@@ -0,0 +1,384 @@
+//! Computer players.
+//!
+//! A bot implements [`Player`] and is asked for a column whenever it's its turn. The same trait is
+//! used to put bots on the server and to play them against each other offline.
+
+use crate::board::{Board, WIDTH};
+use crate::book::OpeningBook;
+use crate::eval::{self, Weights};
+use crate::solver::{self, ParallelSolver};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Columns from the centre outwards, the order moves are worth trying in.
+pub const CENTRE_FIRST: [u8; WIDTH] = [3, 2, 4, 1, 5, 0, 6];
+
+const WIN_SCORE: i32 = 1_000_000;
+
+/// The time a player has to think about its current move.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    started: Instant,
+    budget: Option<Duration>,
+    /// Set when the move isn't wanted anymore, which runs the clock out at once.
+    cancel: Option<Arc<AtomicBool>>,
+}
+
+impl Clock {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            started: Instant::now(),
+            budget: Some(budget),
+            cancel: None,
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self {
+            started: Instant::now(),
+            budget: None,
+            cancel: None,
+        }
+    }
+
+    /// The same clock, running out as soon as `cancel` is set.
+    pub fn cancelled_by(self, cancel: Arc<AtomicBool>) -> Self {
+        Self {
+            cancel: Some(cancel),
+            ..self
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// The time left for this move, `None` if there's no limit.
+    pub fn remaining(&self) -> Option<Duration> {
+        if self.is_cancelled() {
+            return Some(Duration::ZERO);
+        }
+        self.budget
+            .map(|budget| budget.saturating_sub(self.elapsed()))
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.remaining() == Some(Duration::ZERO)
+    }
+}
+
+pub trait Player {
+    fn name(&self) -> String;
+
+    /// Picks the column to play, zero-indexed. It's only called when the board has legal moves
+    /// and it's this player's turn.
+    fn choose_move(&mut self, board: &Board, clock: &Clock) -> u8;
+}
+
+/// Plays a uniformly random legal move.
+pub struct RandomPlayer {
+    rng: StdRng,
+}
+
+impl RandomPlayer {
+    pub fn new() -> Self {
+        Self {
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Default for RandomPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Player for RandomPlayer {
+    fn name(&self) -> String {
+        "random".to_string()
+    }
+
+    fn choose_move(&mut self, board: &Board, _clock: &Clock) -> u8 {
+        let moves = board.legal_moves().collect::<Vec<_>>();
+        *moves.choose(&mut self.rng).expect("no legal moves")
+    }
+}
+
+/// Looks a single move ahead: takes a win, blocks the opponent's and otherwise plays the move
+/// [`evaluate`] likes best.
+pub struct HeuristicPlayer;
+
+impl Player for HeuristicPlayer {
+    fn name(&self) -> String {
+        "heuristic".to_string()
+    }
+
+    fn choose_move(&mut self, board: &Board, _clock: &Clock) -> u8 {
+        if let Some(column) = board
+            .legal_moves()
+            .find(|column| board.is_winning_move(*column))
+        {
+            return column;
+        }
+        let mut best = None;
+        for column in CENTRE_FIRST {
+            if !board.can_drop(column) {
+                continue;
+            }
+            let mut child = *board;
+            let _ = child.drop_piece(column);
+            let score = if child
+                .legal_moves()
+                .any(|reply| child.is_winning_move(reply))
+            {
+                -WIN_SCORE
+            } else {
+                -evaluate(&child)
+            };
+            match best {
+                Some((_, best_score)) if best_score >= score => {}
+                _ => best = Some((column, score)),
+            }
+        }
+        best.expect("no legal moves").0
+    }
+}
+
+/// Searches a fixed number of moves ahead with alpha-beta pruning and scores the positions it
+/// stops at with [`evaluate`], or with [`eval::evaluate`] and weights of its own.
+pub struct MinimaxPlayer {
+    depth: u32,
+    weights: Weights,
+}
+
+impl MinimaxPlayer {
+    pub fn new(depth: u32) -> Self {
+        Self::with_weights(depth, Weights::default())
+    }
+
+    pub fn with_weights(depth: u32, weights: Weights) -> Self {
+        Self {
+            depth: depth.max(1),
+            weights,
+        }
+    }
+}
+
+impl Player for MinimaxPlayer {
+    fn name(&self) -> String {
+        format!("minimax-{}", self.depth)
+    }
+
+    fn choose_move(&mut self, board: &Board, clock: &Clock) -> u8 {
+        let mut board = *board;
+        let mut best = None;
+        let mut alpha = -WIN_SCORE - 1;
+        for column in CENTRE_FIRST {
+            if !board.can_drop(column) {
+                continue;
+            }
+            if board.is_winning_move(column) {
+                return column;
+            }
+            // keep whatever was found so far once the time is up
+            if best.is_some() && clock.is_expired() {
+                break;
+            }
+            let _ = board.drop_piece(column);
+            let score = -negamax(
+                &mut board,
+                self.depth - 1,
+                -WIN_SCORE - 1,
+                -alpha,
+                &self.weights,
+                clock,
+            );
+            board.pop_piece(column);
+            // a search the clock cut short can't be trusted over a finished one
+            if best.is_some() && clock.is_expired() {
+                break;
+            }
+            if best.is_none() || score > alpha {
+                alpha = score;
+                best = Some(column);
+            }
+        }
+        best.expect("no legal moves")
+    }
+}
+
+/// Plays perfectly whenever it can solve the position in half of the time it has left, and falls
+/// back to a depth limited search when it can't, which mostly happens early in the game unless
+/// it has an opening book.
+pub struct SolverPlayer {
+    solver: ParallelSolver,
+    book: Option<Arc<OpeningBook>>,
+    fallback: MinimaxPlayer,
+}
+
+impl SolverPlayer {
+    pub fn new() -> Self {
+        Self::with_threads(1)
+    }
+
+    /// Solves with a [`ParallelSolver`] of `threads` threads.
+    pub fn with_threads(threads: usize) -> Self {
+        Self {
+            solver: ParallelSolver::new(threads),
+            book: None,
+            fallback: MinimaxPlayer::new(6),
+        }
+    }
+
+    pub fn with_book(book: Arc<OpeningBook>) -> Self {
+        Self {
+            book: Some(book),
+            ..Self::new()
+        }
+    }
+}
+
+impl Default for SolverPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Player for SolverPlayer {
+    fn name(&self) -> String {
+        match self.solver.threads() {
+            1 => "solver".to_string(),
+            threads => format!("solver-{threads}-threads"),
+        }
+    }
+
+    fn choose_move(&mut self, board: &Board, clock: &Clock) -> u8 {
+        if let Some(column) = self.book.as_ref().and_then(|book| book.best_move(board)) {
+            return column;
+        }
+        let deadline = clock
+            .remaining()
+            .map(|remaining| Instant::now() + remaining / 2);
+        let scores = match (&clock.cancel, deadline) {
+            (Some(cancel), deadline) => self.solver.analyze_cancellable(board, deadline, cancel),
+            (None, Some(deadline)) => self.solver.analyze_until(board, deadline),
+            (None, None) => Some(self.solver.analyze(board)),
+        };
+        match scores.as_ref().and_then(solver::best_move) {
+            Some(column) => column,
+            None => self.fallback.choose_move(board, clock),
+        }
+    }
+}
+
+/// Scores the position for the player to move, higher is better for them. Once the clock runs
+/// out every remaining position is scored statically, so the search unwinds quickly.
+fn negamax(
+    board: &mut Board,
+    depth: u32,
+    mut alpha: i32,
+    beta: i32,
+    weights: &Weights,
+    clock: &Clock,
+) -> i32 {
+    if board.is_full() {
+        return 0;
+    }
+    if board
+        .legal_moves()
+        .any(|column| board.is_winning_move(column))
+    {
+        return WIN_SCORE - board.move_count() as i32;
+    }
+    if depth == 0 || clock.is_expired() {
+        return eval::evaluate(board, weights);
+    }
+
+    let mut best = -WIN_SCORE - 1;
+    for column in CENTRE_FIRST {
+        if !board.can_drop(column) {
+            continue;
+        }
+        let _ = board.drop_piece(column);
+        let score = -negamax(board, depth - 1, -beta, -alpha, weights, clock);
+        board.pop_piece(column);
+        best = best.max(score);
+        alpha = alpha.max(score);
+        if alpha >= beta {
+            break;
+        }
+    }
+    best
+}
+
+/// A static guess at how good the position is for the player to move, see [`eval`] for what it
+/// looks at.
+pub fn evaluate(board: &Board) -> i32 {
+    eval::evaluate(board, &Weights::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_game;
+
+    #[test]
+    fn minimax_keeps_to_its_clock() {
+        let mut player = MinimaxPlayer::new(14);
+        let clock = Clock::new(Duration::from_millis(50));
+        let column = player.choose_move(&Board::new(), &clock);
+        assert!(Board::new().can_drop(column));
+        assert!(
+            clock.elapsed() < Duration::from_millis(500),
+            "{:?}",
+            clock.elapsed()
+        );
+    }
+
+    #[test]
+    fn solver_gives_up_once_cancelled() {
+        let mut player = SolverPlayer::with_threads(2);
+        let cancel = Arc::new(AtomicBool::new(false));
+        let clock = Clock::unlimited().cancelled_by(cancel.clone());
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            cancel.store(true, Ordering::Relaxed);
+        });
+        // the empty board takes far longer than this to solve
+        let column = player.choose_move(&Board::new(), &clock);
+        canceller.join().unwrap();
+        assert!(Board::new().can_drop(column));
+        assert!(
+            clock.elapsed() < Duration::from_secs(2),
+            "{:?}",
+            clock.elapsed()
+        );
+    }
+
+    #[test]
+    fn minimax_takes_wins_and_blocks_losses() {
+        let mut player = MinimaxPlayer::new(4);
+        // the first player has three in column one
+        let win = parse_game("121212").unwrap();
+        assert_eq!(player.choose_move(&win, &Clock::unlimited()), 0);
+        let block = parse_game("12131").unwrap();
+        assert_eq!(player.choose_move(&block, &Clock::unlimited()), 0);
+    }
+}
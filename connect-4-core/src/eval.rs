@@ -0,0 +1,295 @@
+//! Static evaluation for depth limited searches.
+//!
+//! A position is described by a handful of [`Features`], each counted for the player to move
+//! minus the same count for their opponent, and scored as their sum weighted by [`Weights`]. The
+//! features are:
+//!
+//! - discs in the centre column and in the two columns next to it,
+//! - lines of four holding two of the player's discs and nothing else,
+//! - threats, the empty cells that would complete a four, and how many of them are on rows that
+//!   suit the player: odd rows, counting from one at the bottom, for the first player and even
+//!   rows for the second,
+//! - threats the opponent has to answer right away because their cell can be played,
+//! - which player controls the zugzwang. The second player does unless the first has an odd
+//!   threat with no threat of the second player's below it in its column.
+//!
+//! Scores are in hundredths of a solver score, see [`crate::solver`], which is what
+//! [`fit`] tunes the weights towards.
+
+use crate::board::{Board, Player, BOARD_MASK, BOTTOM_ROW, COLUMN_BITS, HEIGHT, WIDTH};
+use crate::solver::{column_mask, winning_cells};
+
+pub const FEATURE_COUNT: usize = 7;
+
+/// The names of the features, in the order of [`Features::values`] and [`Weights::values`].
+pub const FEATURE_NAMES: [&str; FEATURE_COUNT] = [
+    "centre",
+    "inner",
+    "twos",
+    "threats",
+    "parity_threats",
+    "playable_threats",
+    "zugzwang",
+];
+
+/// Rows one, three and five counting from the bottom.
+const ODD_ROWS: u64 = BOTTOM_ROW * 0b010101;
+const EVEN_ROWS: u64 = BOTTOM_ROW * 0b101010;
+const CENTRE_COLUMN: u64 = column_mask(WIDTH / 2);
+const INNER_COLUMNS: u64 = column_mask(WIDTH / 2 - 1) | column_mask(WIDTH / 2 + 1);
+
+const WINDOW_COUNT: usize = 69;
+/// Every line of four cells on the board.
+const WINDOWS: [u64; WINDOW_COUNT] = windows();
+
+const fn windows() -> [u64; WINDOW_COUNT] {
+    let mut windows = [0; WINDOW_COUNT];
+    let mut count = 0;
+    let directions: [(isize, isize); 4] = [(1, 0), (0, 1), (1, 1), (1, -1)];
+    let mut direction = 0;
+    while direction < directions.len() {
+        let (column_step, row_step) = directions[direction];
+        let mut column = 0;
+        while column < WIDTH as isize {
+            let mut row = 0;
+            while row < HEIGHT as isize {
+                let end_column = column + 3 * column_step;
+                let end_row = row + 3 * row_step;
+                if end_column >= 0
+                    && end_column < WIDTH as isize
+                    && end_row >= 0
+                    && end_row < HEIGHT as isize
+                {
+                    let mut window = 0;
+                    let mut step = 0;
+                    while step < 4 {
+                        let x = column + step * column_step;
+                        let y = row + step * row_step;
+                        window |= 1 << (x as usize * COLUMN_BITS + y as usize);
+                        step += 1;
+                    }
+                    windows[count] = window;
+                    count += 1;
+                }
+                row += 1;
+            }
+            column += 1;
+        }
+        direction += 1;
+    }
+    assert!(count == WINDOW_COUNT);
+    windows
+}
+
+/// The weight of every feature, the score of a position is the features times their weights.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Weights {
+    pub centre: i32,
+    pub inner: i32,
+    pub twos: i32,
+    pub threats: i32,
+    pub parity_threats: i32,
+    pub playable_threats: i32,
+    pub zugzwang: i32,
+}
+
+/// Fitted by `connect4-tune` with its default options, 10000 random positions between 10 and 24
+/// moves in.
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            centre: 44,
+            inner: 11,
+            twos: 68,
+            threats: 153,
+            parity_threats: 54,
+            playable_threats: 149,
+            zugzwang: -26,
+        }
+    }
+}
+
+impl Weights {
+    pub fn values(&self) -> [i32; FEATURE_COUNT] {
+        [
+            self.centre,
+            self.inner,
+            self.twos,
+            self.threats,
+            self.parity_threats,
+            self.playable_threats,
+            self.zugzwang,
+        ]
+    }
+
+    pub fn from_values(values: [i32; FEATURE_COUNT]) -> Self {
+        let [centre, inner, twos, threats, parity_threats, playable_threats, zugzwang] = values;
+        Self {
+            centre,
+            inner,
+            twos,
+            threats,
+            parity_threats,
+            playable_threats,
+            zugzwang,
+        }
+    }
+}
+
+/// The features of a position, each for the player to move minus their opponent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Features {
+    pub values: [i32; FEATURE_COUNT],
+}
+
+impl Features {
+    pub fn of(board: &Board) -> Self {
+        let (mine, mask) = board.bitboards();
+        let theirs = mine ^ mask;
+        let me = board.next_player();
+        let playable = (mask + BOTTOM_ROW) & BOARD_MASK;
+        let my_threats = winning_cells(mine, mask);
+        let their_threats = winning_cells(theirs, mask);
+        let (my_rows, their_rows) = match me {
+            Player::First => (ODD_ROWS, EVEN_ROWS),
+            Player::Second => (EVEN_ROWS, ODD_ROWS),
+        };
+        let (first_threats, second_threats) = match me {
+            Player::First => (my_threats, their_threats),
+            Player::Second => (their_threats, my_threats),
+        };
+        let zugzwang = match zugzwang_controller(first_threats, second_threats) {
+            player if player == me => 1,
+            _ => -1,
+        };
+
+        let count = |bits: u64| bits.count_ones() as i32;
+        let twos = |discs: u64, other: u64| {
+            WINDOWS
+                .iter()
+                .filter(|window| *window & other == 0 && count(*window & discs) == 2)
+                .count() as i32
+        };
+        Self {
+            values: [
+                count(mine & CENTRE_COLUMN) - count(theirs & CENTRE_COLUMN),
+                count(mine & INNER_COLUMNS) - count(theirs & INNER_COLUMNS),
+                twos(mine, theirs) - twos(theirs, mine),
+                count(my_threats) - count(their_threats),
+                count(my_threats & my_rows) - count(their_threats & their_rows),
+                count(my_threats & playable) - count(their_threats & playable),
+                zugzwang,
+            ],
+        }
+    }
+
+    pub fn score(&self, weights: &Weights) -> i32 {
+        self.values
+            .iter()
+            .zip(weights.values())
+            .map(|(feature, weight)| feature * weight)
+            .sum()
+    }
+}
+
+/// Scores the position for the player to move.
+pub fn evaluate(board: &Board, weights: &Weights) -> i32 {
+    Features::of(board).score(weights)
+}
+
+fn zugzwang_controller(first_threats: u64, second_threats: u64) -> Player {
+    let mut above_second = 0;
+    let mut cells = second_threats;
+    for _ in 1..HEIGHT {
+        cells = (cells << 1) & BOARD_MASK;
+        above_second |= cells;
+    }
+    if first_threats & ODD_ROWS & !above_second != 0 {
+        Player::First
+    } else {
+        Player::Second
+    }
+}
+
+/// Fits weights to positions with known scores by least squares, each sample being the features
+/// of a position and its solver score. Returns `None` when the samples don't pin every weight
+/// down, like when a feature is zero in all of them.
+pub fn fit(samples: &[(Features, i32)]) -> Option<Weights> {
+    // the normal equations, (XᵀX) w = Xᵀy, next to each other in one augmented matrix
+    let mut matrix = [[0f64; FEATURE_COUNT + 1]; FEATURE_COUNT];
+    for (features, score) in samples {
+        for (row, a) in features.values.iter().enumerate() {
+            for (column, b) in features.values.iter().enumerate() {
+                matrix[row][column] += (*a as f64) * (*b as f64);
+            }
+            matrix[row][FEATURE_COUNT] += (*a as f64) * (*score as f64);
+        }
+    }
+
+    // Gaussian elimination with partial pivoting
+    for pivot in 0..FEATURE_COUNT {
+        let best = (pivot..FEATURE_COUNT)
+            .max_by(|a, b| matrix[*a][pivot].abs().total_cmp(&matrix[*b][pivot].abs()))?;
+        if matrix[best][pivot].abs() < 1e-9 {
+            return None;
+        }
+        matrix.swap(pivot, best);
+        let pivot_row = matrix[pivot];
+        for (index, row) in matrix.iter_mut().enumerate() {
+            if index == pivot {
+                continue;
+            }
+            let factor = row[pivot] / pivot_row[pivot];
+            for (cell, pivot_cell) in row.iter_mut().zip(pivot_row).skip(pivot) {
+                *cell -= factor * pivot_cell;
+            }
+        }
+    }
+
+    let mut values = [0; FEATURE_COUNT];
+    for (feature, value) in values.iter_mut().enumerate() {
+        let weight = matrix[feature][FEATURE_COUNT] / matrix[feature][feature];
+        *value = (weight * 100.0).round() as i32;
+    }
+    Some(Weights::from_values(values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_game;
+
+    #[test]
+    fn finds_threats_the_opponent_must_answer() {
+        // x has three in a row along the bottom with the fourth cell open, and o is to move
+        let board = parse_game("11223").unwrap();
+        let features = Features::of(&board);
+        assert_eq!(features.values[3], -1);
+        assert_eq!(features.values[5], -1);
+        let (mine, mask) = board.bitboards();
+        assert_eq!(winning_cells(mine ^ mask, mask), 1 << (3 * COLUMN_BITS));
+        assert_eq!(winning_cells(mine, mask), 0);
+    }
+
+    #[test]
+    fn mirrored_positions_look_the_same() {
+        for moves in ["4455", "1234567", "33517142141522"] {
+            let board = parse_game(moves).unwrap();
+            assert_eq!(Features::of(&board), Features::of(&board.mirrored()));
+        }
+    }
+
+    #[test]
+    fn fitting_recovers_the_weights_behind_the_scores() {
+        let weights = Weights::from_values([100, -200, 300, 400, 0, 700, -100]);
+        let samples = (0..50i32)
+            .map(|seed| {
+                let features = Features {
+                    values: std::array::from_fn(|index| (seed * 7 + index as i32 * 13) % 11 - 5),
+                };
+                (features, features.score(&weights) / 100)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(fit(&samples), Some(weights));
+    }
+}
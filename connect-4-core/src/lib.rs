@@ -16,19 +16,75 @@ macro_rules! encode {
     }
 }
 
+pub mod analysis;
+pub mod board;
+pub mod book;
+pub mod bot;
+pub mod eval;
 pub mod logger;
+pub mod mcts;
+pub mod notation;
+pub mod record;
+pub mod solver;
+pub mod transposition;
+pub mod zobrist;
 
 pub type Username = LimitedString<16>;
 
+pub type RoomCode = LimitedString<8>;
+
+pub type ChatText = LimitedString<256>;
+
 pub mod packets {
     use drax::transport::packet::primitive::VarInt;
 
     drax::components! {
+        struct GameRules {
+            challenger_goes_first: bool,
+            hints_allowed: bool
+        },
+
+        struct GameSummary {
+            game_id: i32,
+            first_player: super::Username,
+            second_player: super::Username,
+            moves: VarInt,
+            spectators: VarInt
+        },
+
+        struct HistoryEntry {
+            game_id: i32,
+            opponent: super::Username,
+            went_first: bool,
+            winner: super::Username,
+            abandoned: bool,
+            moves: VarInt,
+            finished_at: i64
+        },
+
+        struct LeaderboardEntry {
+            rank: VarInt,
+            username: super::Username,
+            rating: VarInt,
+            wins: VarInt,
+            losses: VarInt,
+            draws: VarInt
+        },
+
+        struct MoveEvaluation {
+            column: u8,
+            solved: bool,
+            score: VarInt,
+            best_move: u8,
+            best_score: VarInt
+        },
+
         enum ServerboundLoginPacket<key: VarInt> {
             KeepAlive {},
             RequestUsername {
                 username: super::Username,
-                transaction_id: VarInt
+                transaction_id: VarInt,
+                bot: bool
             },
             AcquireUsername {}
         },
@@ -44,12 +100,140 @@ pub mod packets {
         enum ServerboundLobbyPacket<key: VarInt> {
             KeepAlive {},
             RequestGame {},
-            AcquireGame {}
+            AcquireGame {},
+            CancelSearch {},
+            CreateRoom {},
+            JoinRoom {
+                code: super::RoomCode
+            },
+            CloseRoom {},
+            ListPlayers {},
+            Challenge {
+                username: super::Username,
+                rules: GameRules
+            },
+            AcceptChallenge {
+                username: super::Username
+            },
+            DeclineChallenge {
+                username: super::Username
+            },
+            Chat {
+                message: super::ChatText
+            },
+            ListGames {},
+            Spectate {
+                game_id: i32
+            },
+            AcquireSpectate {},
+            RequestHistory {
+                username: super::Username,
+                page: VarInt
+            },
+            RequestGameRecord {
+                game_id: i32
+            },
+            RequestLeaderboard {
+                page: VarInt
+            },
+            RequestProfile {
+                username: super::Username
+            },
+            RequestAnalysis {
+                game_id: i32
+            }
         },
 
         enum ClientboundLobbyPacket<key: VarInt> {
             KeepAlive {},
-            GameFound {}
+            GameFound {},
+            QueueUpdate {
+                position: VarInt,
+                estimated_wait_seconds: VarInt
+            },
+            SearchCancelled {},
+            RoomCreated {
+                code: super::RoomCode
+            },
+            RoomJoinFailed {
+                code: super::RoomCode
+            },
+            RoomClosed {
+                expired: bool
+            },
+            PlayerList {
+                usernames: Vec<super::Username>
+            },
+            ChallengeReceived {
+                username: super::Username,
+                rules: GameRules
+            },
+            ChallengeDeclined {
+                username: super::Username
+            },
+            ChallengeFailed {
+                username: super::Username
+            },
+            Chat {
+                username: super::Username,
+                message: super::ChatText
+            },
+            ChatRejected {
+                reason: super::ChatText
+            },
+            GameList {
+                games: Vec<GameSummary>
+            },
+            SpectateStarted {
+                game_id: i32
+            },
+            SpectateFailed {
+                game_id: i32
+            },
+            HistoryPage {
+                username: super::Username,
+                page: VarInt,
+                has_more: bool,
+                games: Vec<HistoryEntry>
+            },
+            GameRecord {
+                game_id: i32,
+                first_player: super::Username,
+                second_player: super::Username,
+                winner: super::Username,
+                abandoned: bool,
+                moves: Vec<u8>,
+                finished_at: i64
+            },
+            GameRecordNotFound {
+                game_id: i32
+            },
+            Leaderboard {
+                page: VarInt,
+                has_more: bool,
+                entries: Vec<LeaderboardEntry>
+            },
+            Profile {
+                username: super::Username,
+                rank: VarInt,
+                rating: VarInt,
+                wins: VarInt,
+                losses: VarInt,
+                draws: VarInt,
+                current_streak: VarInt,
+                best_streak: VarInt,
+                recent_form: super::LimitedString<16>
+            },
+            ProfileNotFound {
+                username: super::Username
+            },
+            GameAnalysis {
+                game_id: i32,
+                moves: Vec<MoveEvaluation>
+            },
+            GameAnalysisNotFound {
+                game_id: i32
+            }
         },
 
         enum ServerboundGamePacket<key: VarInt> {
@@ -58,14 +242,20 @@ pub mod packets {
                 column: u8,
                 transaction_id: i32
             },
-            AcquireLobby {}
+            AcquireLobby {},
+            Chat {
+                message: super::ChatText
+            },
+            RequestHint {}
         },
 
         enum ClientboundGamePacket<key: VarInt> {
             KeepAlive {},
             OpponentJoin {
                 username: super::Username,
-                i_go_first: bool
+                i_go_first: bool,
+                opponent_is_bot: bool,
+                hints_allowed: bool
             },
             PlacePieceAck {
                 transaction_id: i32
@@ -76,6 +266,56 @@ pub mod packets {
             EarlyExit {},
             PlayerWin {
                 me: bool
+            },
+            Draw {},
+            Chat {
+                username: super::Username,
+                message: super::ChatText
+            },
+            ChatRejected {
+                reason: super::ChatText
+            },
+            SpectatorCount {
+                count: VarInt
+            },
+            Hint {
+                column: u8,
+                score: VarInt,
+                exact: bool
+            },
+            HintUnavailable {}
+        },
+
+        enum ServerboundSpectatorPacket<key: VarInt> {
+            KeepAlive {},
+            AcquireLobby {}
+        },
+
+        enum ClientboundSpectatorPacket<key: VarInt> {
+            KeepAlive {},
+            BoardSnapshot {
+                first_player: super::Username,
+                second_player: super::Username,
+                board: Vec<u8>,
+                first_player_to_move: bool
+            },
+            PiecePlaced {
+                column: u8,
+                first_player: bool
+            },
+            GameOver {
+                winner: super::Username,
+                abandoned: bool
+            },
+            ReturnedToLobby {}
+        }
+    }
+
+    impl GameRules {
+        pub fn copied(&self) -> GameRules {
+            GameRules {
+                challenger_goes_first: self.challenger_goes_first,
+                hints_allowed: self.hints_allowed,
             }
         }
     }
@@ -0,0 +1,354 @@
+//! Monte Carlo tree search.
+//!
+//! The tree grows one node per iteration: it walks down picking children by UCT, adds one
+//! untried move, plays the game out from there and credits the result to every node on the way
+//! back up. The most visited move at the root is played. Whatever part of the tree is still
+//! reachable after both players moved is kept for the next search.
+
+use crate::board::{Board, Outcome, WIDTH};
+use crate::bot::{Clock, Player};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+    Iterations(u32),
+    Time(Duration),
+}
+
+/// How games are finished from a newly added node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Playout {
+    Random,
+    /// Random moves, except that wins are always taken and the opponent's wins blocked.
+    Heuristic,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MctsOptions {
+    /// How long to search for every move. The clock given to the player is respected either way.
+    pub budget: Budget,
+    pub playout: Playout,
+    /// The UCT exploration constant, higher values spread the visits out more.
+    pub exploration: f64,
+}
+
+impl Default for MctsOptions {
+    fn default() -> Self {
+        Self {
+            budget: Budget::Iterations(10_000),
+            playout: Playout::Random,
+            exploration: std::f64::consts::SQRT_2,
+        }
+    }
+}
+
+struct Node {
+    board: Board,
+    column: u8,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<u8>,
+    visits: u32,
+    /// Points for the player who moved into this node, one for a win and a half for a draw.
+    reward: f64,
+}
+
+impl Node {
+    fn new(board: Board, column: u8, parent: Option<usize>) -> Self {
+        let untried = if board.outcome().is_some() {
+            Vec::new()
+        } else {
+            board.legal_moves().collect()
+        };
+        Self {
+            board,
+            column,
+            parent,
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            reward: 0.0,
+        }
+    }
+}
+
+pub struct MctsPlayer {
+    options: MctsOptions,
+    rng: StdRng,
+    /// The tree of the last search, the root being the position that search started from.
+    nodes: Vec<Node>,
+}
+
+impl MctsPlayer {
+    pub fn new(options: MctsOptions) -> Self {
+        Self {
+            options,
+            rng: StdRng::from_entropy(),
+            nodes: Vec::new(),
+        }
+    }
+
+    pub fn seeded(options: MctsOptions, seed: u64) -> Self {
+        Self {
+            options,
+            rng: StdRng::seed_from_u64(seed),
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Visits of the current root, including the ones carried over from earlier searches.
+    pub fn root_visits(&self) -> u32 {
+        self.nodes.first().map_or(0, |root| root.visits)
+    }
+
+    /// Makes the node for `board` the root, keeping its subtree when the last search reached
+    /// that position within its first two moves, and starting over otherwise.
+    fn reroot(&mut self, board: &Board) {
+        let mut found = None;
+        let mut frontier = vec![0];
+        for _ in 0..=2 {
+            if let Some(index) = frontier
+                .iter()
+                .copied()
+                .find(|index| self.nodes.get(*index).map(|node| &node.board) == Some(board))
+            {
+                found = Some(index);
+                break;
+            }
+            frontier = frontier
+                .iter()
+                .filter_map(|index| self.nodes.get(*index))
+                .flat_map(|node| node.children.iter().copied())
+                .collect();
+        }
+
+        let Some(root) = found else {
+            self.nodes = vec![Node::new(*board, 0, None)];
+            return;
+        };
+        let mut old = std::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        let mut stack = vec![(root, None)];
+        while let Some((index, parent)) = stack.pop() {
+            let mut node = old[index].take().expect("every node has a single parent");
+            let new_index = self.nodes.len();
+            node.parent = parent;
+            if let Some(parent) = parent {
+                self.nodes[parent].children.push(new_index);
+            }
+            stack.extend(
+                node.children
+                    .drain(..)
+                    .map(|child| (child, Some(new_index))),
+            );
+            self.nodes.push(node);
+        }
+    }
+
+    fn search(&mut self, clock: &Clock) {
+        let started = Instant::now();
+        let mut iterations = 0;
+        loop {
+            let done = match self.options.budget {
+                Budget::Iterations(budget) => iterations >= budget,
+                Budget::Time(budget) => started.elapsed() >= budget,
+            };
+            // always search a little, there's nothing to choose from otherwise
+            if iterations > 0 && (done || clock.is_expired()) {
+                break;
+            }
+            self.iterate();
+            iterations += 1;
+        }
+    }
+
+    fn iterate(&mut self) {
+        let mut index = 0;
+        while self.nodes[index].untried.is_empty() && !self.nodes[index].children.is_empty() {
+            index = self.select_child(index);
+        }
+
+        if !self.nodes[index].untried.is_empty() {
+            let pick = self.rng.gen_range(0..self.nodes[index].untried.len());
+            let column = self.nodes[index].untried.swap_remove(pick);
+            let mut board = self.nodes[index].board;
+            let _ = board.drop_piece(column);
+            let child = self.nodes.len();
+            self.nodes.push(Node::new(board, column, Some(index)));
+            self.nodes[index].children.push(child);
+            index = child;
+        }
+
+        let outcome = self.playout(self.nodes[index].board);
+        let mut current = Some(index);
+        while let Some(index) = current {
+            let node = &mut self.nodes[index];
+            node.visits += 1;
+            node.reward += match outcome {
+                Outcome::Draw => 0.5,
+                Outcome::Win(winner) if winner != node.board.next_player() => 1.0,
+                Outcome::Win(_) => 0.0,
+            };
+            current = node.parent;
+        }
+    }
+
+    fn select_child(&self, index: usize) -> usize {
+        let parent = &self.nodes[index];
+        let log_visits = (parent.visits.max(1) as f64).ln();
+        let uct = |child: &usize| {
+            let child = &self.nodes[*child];
+            let visits = child.visits.max(1) as f64;
+            child.reward / visits + self.options.exploration * (log_visits / visits).sqrt()
+        };
+        *parent
+            .children
+            .iter()
+            .max_by(|a, b| uct(a).total_cmp(&uct(b)))
+            .expect("only called on nodes with children")
+    }
+
+    fn playout(&mut self, mut board: Board) -> Outcome {
+        let mut moves = Vec::with_capacity(WIDTH);
+        loop {
+            if let Some(outcome) = board.outcome() {
+                return outcome;
+            }
+            moves.clear();
+            moves.extend(board.legal_moves());
+            let column = match self.options.playout {
+                Playout::Random => moves[self.rng.gen_range(0..moves.len())],
+                Playout::Heuristic => match heuristic_move(&board, &moves) {
+                    Some(column) => column,
+                    None => moves[self.rng.gen_range(0..moves.len())],
+                },
+            };
+            let _ = board.drop_piece(column);
+        }
+    }
+}
+
+/// A move that wins, or one that stops the opponent from winning on their next move.
+fn heuristic_move(board: &Board, moves: &[u8]) -> Option<u8> {
+    if let Some(column) = moves.iter().find(|column| board.is_winning_move(**column)) {
+        return Some(*column);
+    }
+    let opponent = board.next_player().other();
+    moves
+        .iter()
+        .copied()
+        .find(|column| board.wins_with(opponent, *column))
+}
+
+impl Player for MctsPlayer {
+    fn name(&self) -> String {
+        let budget = match self.options.budget {
+            Budget::Iterations(iterations) => iterations.to_string(),
+            Budget::Time(time) => format!("{}ms", time.as_millis()),
+        };
+        match self.options.playout {
+            Playout::Random => format!("mcts-{budget}"),
+            Playout::Heuristic => format!("mcts-heuristic-{budget}"),
+        }
+    }
+
+    fn choose_move(&mut self, board: &Board, clock: &Clock) -> u8 {
+        self.reroot(board);
+        self.search(clock);
+        let root = &self.nodes[0];
+        let best = root
+            .children
+            .iter()
+            .max_by_key(|child| self.nodes[**child].visits)
+            .expect("the search expands the root at least once");
+        self.nodes[*best].column
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_game;
+
+    fn player() -> MctsPlayer {
+        MctsPlayer::seeded(
+            MctsOptions {
+                budget: Budget::Iterations(2_000),
+                ..MctsOptions::default()
+            },
+            7,
+        )
+    }
+
+    fn child(player: &MctsPlayer, index: usize, column: u8) -> usize {
+        *player.nodes[index]
+            .children
+            .iter()
+            .find(|child| player.nodes[**child].column == column)
+            .expect("the search tried every move at the top of the tree")
+    }
+
+    /// Visits of every node below `index`, in the order of the moves leading to them.
+    fn subtree(player: &MctsPlayer, index: usize) -> Vec<(Vec<u8>, u32)> {
+        let mut nodes = vec![];
+        let mut stack = vec![(index, vec![])];
+        while let Some((index, path)) = stack.pop() {
+            let node = &player.nodes[index];
+            nodes.push((path.clone(), node.visits));
+            for child in &node.children {
+                let mut path = path.clone();
+                path.push(player.nodes[*child].column);
+                stack.push((*child, path));
+            }
+        }
+        nodes.sort();
+        nodes
+    }
+
+    #[test]
+    fn reroot_keeps_the_subtree_two_moves_down() {
+        let mut player = player();
+        let mut board = Board::new();
+        let first = player.choose_move(&board, &Clock::unlimited());
+        let _ = board.drop_piece(first);
+        let _ = board.drop_piece(3);
+        let kept = child(&player, child(&player, 0, first), 3);
+        let expected = subtree(&player, kept);
+
+        player.reroot(&board);
+        assert_eq!(subtree(&player, 0), expected);
+        assert_eq!(player.nodes.len(), expected.len());
+        assert_eq!(player.root_visits(), expected[0].1);
+        assert_eq!(player.nodes[0].parent, None);
+        for (index, node) in player.nodes.iter().enumerate() {
+            for child in &node.children {
+                let child = &player.nodes[*child];
+                assert_eq!(child.parent, Some(index));
+                let mut board = node.board;
+                let _ = board.drop_piece(child.column);
+                assert_eq!(child.board, board);
+            }
+        }
+    }
+
+    #[test]
+    fn reroot_starts_over_for_unknown_positions() {
+        let mut player = player();
+        player.choose_move(&Board::new(), &Clock::unlimited());
+        player.reroot(&parse_game("4444").unwrap());
+        assert_eq!(player.nodes.len(), 1);
+        assert_eq!(player.root_visits(), 0);
+    }
+
+    #[test]
+    fn takes_wins_and_blocks_losses() {
+        let win = parse_game("121212").unwrap();
+        assert_eq!(player().choose_move(&win, &Clock::unlimited()), 0);
+        let block = parse_game("12131").unwrap();
+        assert_eq!(player().choose_move(&block, &Clock::unlimited()), 0);
+    }
+}
@@ -0,0 +1,391 @@
+//! Text formats for games and positions.
+//!
+//! Games are written as a string of one-indexed column digits in the order they were played, so
+//! `"4453"` is the centre column twice followed by the fifth and third columns.
+//!
+//! Positions are written FEN style as the six rows from top to bottom separated by `/`, where `x`
+//! is a disc of the first player, `o` a disc of the second player and a digit a run of empty
+//! cells, followed by a space and the side to move. The empty board is `"7/7/7/7/7/7 x"`.
+
+use crate::board::{Board, DropError, Player, HEIGHT, WIDTH};
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotationError {
+    InvalidColumn {
+        index: usize,
+        found: char,
+    },
+    IllegalMove {
+        move_number: usize,
+        column: u8,
+        error: DropError,
+    },
+    MissingSideToMove,
+    InvalidSideToMove(String),
+    WrongRowCount(usize),
+    WrongRowWidth {
+        row: usize,
+        width: usize,
+    },
+    InvalidCell {
+        row: usize,
+        found: char,
+    },
+    FloatingDisc {
+        column: usize,
+    },
+    ImpossibleDiscCount {
+        first: usize,
+        second: usize,
+    },
+    WrongSideToMove {
+        expected: Player,
+    },
+    BothPlayersConnected,
+    /// Discs were dropped after a player had already connected four.
+    PlayedOnAfterWin,
+}
+
+impl Display for NotationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotationError::InvalidColumn { index, found } => write!(
+                f,
+                "'{found}' at position {} isn't a column, expected a digit from 1 to {WIDTH}",
+                index + 1
+            ),
+            NotationError::IllegalMove {
+                move_number,
+                column,
+                error,
+            } => write!(
+                f,
+                "move {move_number} in column {} is illegal: {error}",
+                *column as usize + 1
+            ),
+            NotationError::MissingSideToMove => {
+                write!(
+                    f,
+                    "the position is missing the side to move, expected 'x' or 'o'"
+                )
+            }
+            NotationError::InvalidSideToMove(found) => {
+                write!(f, "'{found}' isn't a side to move, expected 'x' or 'o'")
+            }
+            NotationError::WrongRowCount(rows) => {
+                write!(f, "the position has {rows} rows, expected {HEIGHT}")
+            }
+            NotationError::WrongRowWidth { row, width } => write!(
+                f,
+                "row {} from the top is {width} cells wide, expected {WIDTH}",
+                row + 1
+            ),
+            NotationError::InvalidCell { row, found } => write!(
+                f,
+                "'{found}' in row {} from the top isn't 'x', 'o' or a digit",
+                row + 1
+            ),
+            NotationError::FloatingDisc { column } => {
+                write!(f, "column {} has a disc above an empty cell", column + 1)
+            }
+            NotationError::ImpossibleDiscCount { first, second } => write!(
+                f,
+                "the first player has {first} discs and the second player {second}, \
+                 the first player must have as many or one more"
+            ),
+            NotationError::WrongSideToMove { expected } => write!(
+                f,
+                "the disc counts mean it's {} to move",
+                side_to_move(*expected)
+            ),
+            NotationError::BothPlayersConnected => {
+                write!(f, "both players have connected four")
+            }
+            NotationError::PlayedOnAfterWin => {
+                write!(f, "the game went on after four were connected")
+            }
+        }
+    }
+}
+
+impl std::error::Error for NotationError {}
+
+/// Parses a column digit string into zero-indexed columns, whitespace is ignored.
+pub fn parse_moves(moves: &str) -> Result<Vec<u8>, NotationError> {
+    moves
+        .chars()
+        .enumerate()
+        .filter(|(_, found)| !found.is_whitespace())
+        .map(|(index, found)| match found.to_digit(10) {
+            Some(digit @ 1..) if digit as usize <= WIDTH => Ok(digit as u8 - 1),
+            _ => Err(NotationError::InvalidColumn { index, found }),
+        })
+        .collect()
+}
+
+pub fn format_moves(moves: &[u8]) -> String {
+    moves
+        .iter()
+        .map(|column| char::from(b'1' + column))
+        .collect()
+}
+
+/// Plays the moves from the empty board, failing on the first move that can't be played.
+pub fn play_moves(moves: &[u8]) -> Result<Board, NotationError> {
+    let mut board = Board::new();
+    for (index, column) in moves.iter().enumerate() {
+        board
+            .drop_piece(*column)
+            .map_err(|error| NotationError::IllegalMove {
+                move_number: index + 1,
+                column: *column,
+                error,
+            })?;
+    }
+    Ok(board)
+}
+
+pub fn parse_game(moves: &str) -> Result<Board, NotationError> {
+    play_moves(&parse_moves(moves)?)
+}
+
+pub fn parse_position(position: &str) -> Result<Board, NotationError> {
+    let (rows, side) = position
+        .trim()
+        .split_once(char::is_whitespace)
+        .ok_or(NotationError::MissingSideToMove)?;
+    let side = match side.trim() {
+        "x" | "X" => Player::First,
+        "o" | "O" => Player::Second,
+        side => return Err(NotationError::InvalidSideToMove(side.to_string())),
+    };
+
+    let rows = rows.split('/').collect::<Vec<_>>();
+    if rows.len() != HEIGHT {
+        return Err(NotationError::WrongRowCount(rows.len()));
+    }
+
+    let mut cells = [[None; HEIGHT]; WIDTH];
+    for (row_from_top, row) in rows.iter().enumerate() {
+        let y = HEIGHT - 1 - row_from_top;
+        let mut x = 0;
+        for found in row.chars() {
+            let (player, run) = match found {
+                'x' | 'X' => (Some(Player::First), 1),
+                'o' | 'O' => (Some(Player::Second), 1),
+                _ => match found.to_digit(10) {
+                    Some(run @ 1..) => (None, run as usize),
+                    _ => {
+                        return Err(NotationError::InvalidCell {
+                            row: row_from_top,
+                            found,
+                        })
+                    }
+                },
+            };
+            if x + run <= WIDTH {
+                if let Some(player) = player {
+                    cells[x][y] = Some(player);
+                }
+            }
+            x += run;
+        }
+        if x != WIDTH {
+            return Err(NotationError::WrongRowWidth {
+                row: row_from_top,
+                width: x,
+            });
+        }
+    }
+
+    let mut board = Board::new();
+    let mut counts = [0usize; 2];
+    for (column, cells) in cells.iter().enumerate() {
+        let height = cells.iter().take_while(|cell| cell.is_some()).count();
+        if cells[height..].iter().any(|cell| cell.is_some()) {
+            return Err(NotationError::FloatingDisc { column });
+        }
+        for player in cells.iter().flatten() {
+            board.place(column, *player);
+            counts[matches!(player, Player::Second) as usize] += 1;
+        }
+    }
+
+    let [first, second] = counts;
+    let expected = match first.checked_sub(second) {
+        Some(0) => Player::First,
+        Some(1) => Player::Second,
+        _ => return Err(NotationError::ImpossibleDiscCount { first, second }),
+    };
+    if side != expected {
+        return Err(NotationError::WrongSideToMove { expected });
+    }
+    if board.has_won(Player::First) && board.has_won(Player::Second) {
+        return Err(NotationError::BothPlayersConnected);
+    }
+    if let Some(winner) = board.winner() {
+        // the winner's last disc has to be one on top that every four of theirs runs through
+        let won_last = winner != side
+            && (0..WIDTH as u8).any(|column| {
+                let mut before = board;
+                before.pop_piece(column) == Some(winner) && !before.has_won(winner)
+            });
+        if !won_last {
+            return Err(NotationError::PlayedOnAfterWin);
+        }
+    }
+    Ok(board)
+}
+
+pub fn format_position(board: &Board) -> String {
+    let rows = (0..HEIGHT)
+        .rev()
+        .map(|y| {
+            let mut row = String::new();
+            let mut empty = 0;
+            for x in 0..WIDTH {
+                let cell = match board.get(x, y) {
+                    Some(Player::First) => 'x',
+                    Some(Player::Second) => 'o',
+                    None => {
+                        empty += 1;
+                        continue;
+                    }
+                };
+                if empty > 0 {
+                    row.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                row.push(cell);
+            }
+            if empty > 0 {
+                row.push_str(&empty.to_string());
+            }
+            row
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    format!("{rows} {}", side_to_move(board.next_player()))
+}
+
+fn side_to_move(player: Player) -> char {
+    match player {
+        Player::First => 'x',
+        Player::Second => 'o',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_round_trip() {
+        for moves in ["", "4", "4453", "1234567", "44444434"] {
+            assert_eq!(format_moves(&parse_moves(moves).unwrap()), moves);
+        }
+    }
+
+    #[test]
+    fn position_round_trip() {
+        for moves in ["", "4", "4453", "1122334", "4444443333332"] {
+            let board = parse_game(moves).unwrap();
+            let position = format_position(&board);
+            assert_eq!(parse_position(&position).unwrap(), board, "{position}");
+        }
+    }
+
+    #[test]
+    fn formats_positions() {
+        assert_eq!(format_position(&Board::new()), "7/7/7/7/7/7 x");
+        assert_eq!(
+            format_position(&parse_game("4453").unwrap()),
+            "7/7/7/7/3o3/2oxx2 x"
+        );
+    }
+
+    #[test]
+    fn rejects_bad_columns() {
+        assert_eq!(
+            parse_moves("448"),
+            Err(NotationError::InvalidColumn {
+                index: 2,
+                found: '8'
+            })
+        );
+        assert!(matches!(
+            parse_moves("40"),
+            Err(NotationError::InvalidColumn { index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_illegal_sequences() {
+        assert_eq!(
+            parse_game("4444444"),
+            Err(NotationError::IllegalMove {
+                move_number: 7,
+                column: 3,
+                error: DropError::ColumnFull(3)
+            })
+        );
+        assert_eq!(
+            parse_game("12121211"),
+            Err(NotationError::IllegalMove {
+                move_number: 8,
+                column: 0,
+                error: DropError::GameOver
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_bad_positions() {
+        assert_eq!(
+            parse_position("7/7/7/7/7/7"),
+            Err(NotationError::MissingSideToMove)
+        );
+        assert_eq!(
+            parse_position("7/7/7/7/7 x"),
+            Err(NotationError::WrongRowCount(5))
+        );
+        assert_eq!(
+            parse_position("7/7/7/7/7/6 x"),
+            Err(NotationError::WrongRowWidth { row: 5, width: 6 })
+        );
+        assert_eq!(
+            parse_position("7/7/7/7/x6/7 o"),
+            Err(NotationError::FloatingDisc { column: 0 })
+        );
+        assert_eq!(
+            parse_position("7/7/7/7/7/xx5 o"),
+            Err(NotationError::ImpossibleDiscCount {
+                first: 2,
+                second: 0
+            })
+        );
+        assert_eq!(
+            parse_position("7/7/7/7/7/x6 x"),
+            Err(NotationError::WrongSideToMove {
+                expected: Player::Second
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_moves_after_a_win() {
+        // the second player moved after the first connected four in column one
+        assert_eq!(
+            parse_position("7/7/x6/xo5/xo5/xo3o1 x"),
+            Err(NotationError::PlayedOnAfterWin)
+        );
+        // no single disc could have finished both fours
+        assert_eq!(
+            parse_position("7/7/x5x/xoo3x/xoo3x/xooo2x o"),
+            Err(NotationError::PlayedOnAfterWin)
+        );
+        let won = parse_game("1122334").unwrap();
+        assert_eq!(parse_position(&format_position(&won)), Ok(won));
+    }
+}
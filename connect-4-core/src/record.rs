@@ -0,0 +1,320 @@
+//! Records of finished games.
+//!
+//! A record is written on a single line as space separated `key=value` pairs, so a file of them
+//! can be appended to as games finish and read back a line at a time. The server keeps its
+//! games this way and the client opens the same lines as replays.
+
+use crate::packets::{GameRules, HistoryEntry};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordError {
+    MissingField(&'static str),
+    InvalidValue { key: &'static str, value: String },
+    MalformedMove(String),
+}
+
+impl Display for RecordError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordError::MissingField(key) => write!(f, "the record has no {key}"),
+            RecordError::InvalidValue { key, value } => {
+                write!(f, "'{value}' isn't a valid {key}")
+            }
+            RecordError::MalformedMove(played) => {
+                write!(
+                    f,
+                    "'{played}' isn't a move, expected <column>@<milliseconds>"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+pub struct MoveRecord {
+    pub column: u8,
+    pub first_player: bool,
+    pub username: String,
+    pub played_at: SystemTime,
+}
+
+pub enum GameResult {
+    FirstPlayerWin,
+    SecondPlayerWin,
+    Draw,
+    Abandoned { by: String },
+}
+
+/// Everything needed to replay or audit a finished game.
+pub struct GameRecord {
+    pub id: i32,
+    pub first_player: String,
+    pub second_player: String,
+    pub rules: GameRules,
+    pub started_at: SystemTime,
+    pub finished_at: SystemTime,
+    pub moves: Vec<MoveRecord>,
+    pub result: GameResult,
+    /// Whether the game counts towards the players' ratings.
+    pub rated: bool,
+}
+
+impl GameRecord {
+    pub fn winner(&self) -> Option<&str> {
+        match self.result {
+            GameResult::FirstPlayerWin => Some(&self.first_player),
+            GameResult::SecondPlayerWin => Some(&self.second_player),
+            _ => None,
+        }
+    }
+
+    pub fn involves(&self, username: &str) -> bool {
+        self.first_player.eq_ignore_ascii_case(username)
+            || self.second_player.eq_ignore_ascii_case(username)
+    }
+
+    /// Summarises the game from the point of view of one of its players.
+    pub fn history_entry(&self, username: &str) -> HistoryEntry {
+        let went_first = self.first_player.eq_ignore_ascii_case(username);
+        HistoryEntry {
+            game_id: self.id,
+            opponent: if went_first {
+                self.second_player.clone()
+            } else {
+                self.first_player.clone()
+            },
+            went_first,
+            winner: self.winner().unwrap_or_default().to_string(),
+            abandoned: matches!(self.result, GameResult::Abandoned { .. }),
+            moves: self.moves.len() as i32,
+            finished_at: unix_seconds(self.finished_at),
+        }
+    }
+}
+
+fn unix_millis(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default()
+}
+
+pub fn unix_seconds(time: SystemTime) -> i64 {
+    (unix_millis(time) / 1000) as i64
+}
+
+fn from_unix_millis(millis: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis)
+}
+
+impl Display for GameResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameResult::FirstPlayerWin => write!(f, "first"),
+            GameResult::SecondPlayerWin => write!(f, "second"),
+            GameResult::Draw => write!(f, "draw"),
+            GameResult::Abandoned { by } => write!(f, "abandoned:{by}"),
+        }
+    }
+}
+
+/// Writes the record on a single line as space separated `key=value` pairs, moves are written as
+/// `column@milliseconds` offsets from the start of the game.
+impl Display for GameRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let started_at = unix_millis(self.started_at);
+        let moves = self
+            .moves
+            .iter()
+            .map(|record| {
+                format!(
+                    "{}@{}",
+                    record.column,
+                    unix_millis(record.played_at).saturating_sub(started_at)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        write!(
+            f,
+            "id={} first={} second={} challenger_goes_first={} hints_allowed={} rated={} started={} finished={} result={} moves={}",
+            self.id,
+            self.first_player,
+            self.second_player,
+            self.rules.challenger_goes_first,
+            self.rules.hints_allowed,
+            self.rated,
+            started_at,
+            unix_millis(self.finished_at),
+            self.result,
+            moves
+        )
+    }
+}
+
+impl FromStr for GameResult {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "first" => GameResult::FirstPlayerWin,
+            "second" => GameResult::SecondPlayerWin,
+            "draw" => GameResult::Draw,
+            _ => match s.strip_prefix("abandoned:") {
+                Some(by) => GameResult::Abandoned { by: by.to_string() },
+                None => return Err(invalid("result", s)),
+            },
+        })
+    }
+}
+
+/// Reads a record back from the line its [`Display`] implementation writes.
+impl FromStr for GameRecord {
+    type Err = RecordError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let first_player = field(s, "first")?.to_string();
+        let second_player = field(s, "second")?.to_string();
+        let started_at: u64 = parse_field(s, "started")?;
+        let moves = field(s, "moves")?
+            .split(',')
+            .filter(|played| !played.is_empty())
+            .enumerate()
+            .map(|(index, played)| {
+                let malformed = || RecordError::MalformedMove(played.to_string());
+                let (column, offset) = played.split_once('@').ok_or_else(malformed)?;
+                let column = column.parse().map_err(|_| malformed())?;
+                let offset = offset.parse::<u64>().map_err(|_| malformed())?;
+                // the players take turns, so the parity of the move says who played it
+                let first = index % 2 == 0;
+                Ok(MoveRecord {
+                    column,
+                    first_player: first,
+                    username: if first {
+                        first_player.clone()
+                    } else {
+                        second_player.clone()
+                    },
+                    played_at: from_unix_millis(started_at + offset),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(GameRecord {
+            id: parse_field(s, "id")?,
+            first_player,
+            second_player,
+            rules: GameRules {
+                challenger_goes_first: parse_field(s, "challenger_goes_first")?,
+                hints_allowed: parse_field(s, "hints_allowed")?,
+            },
+            started_at: from_unix_millis(started_at),
+            finished_at: from_unix_millis(parse_field(s, "finished")?),
+            moves,
+            result: field(s, "result")?.parse()?,
+            // records written before games could be unrated don't say
+            rated: field(s, "rated").map_or(Ok(false), |_| parse_field(s, "rated"))?,
+        })
+    }
+}
+
+fn field<'a>(line: &'a str, key: &'static str) -> Result<&'a str, RecordError> {
+    line.split_whitespace()
+        .find_map(|pair| pair.strip_prefix(key)?.strip_prefix('='))
+        .ok_or(RecordError::MissingField(key))
+}
+
+fn parse_field<T: FromStr>(line: &str, key: &'static str) -> Result<T, RecordError> {
+    let value = field(line, key)?;
+    value.parse().map_err(|_| invalid(key, value))
+}
+
+fn invalid(key: &'static str, value: &str) -> RecordError {
+    RecordError::InvalidValue {
+        key,
+        value: value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(result: GameResult) -> GameRecord {
+        let started_at = from_unix_millis(1_676_000_000_000);
+        GameRecord {
+            id: 7,
+            first_player: "Alice".to_string(),
+            second_player: "bob".to_string(),
+            rules: GameRules {
+                challenger_goes_first: false,
+                hints_allowed: true,
+            },
+            started_at,
+            finished_at: started_at + Duration::from_secs(60),
+            moves: [3, 3, 4]
+                .into_iter()
+                .enumerate()
+                .map(|(index, column)| MoveRecord {
+                    column,
+                    first_player: index % 2 == 0,
+                    username: if index % 2 == 0 { "Alice" } else { "bob" }.to_string(),
+                    played_at: started_at + Duration::from_millis(1500 * index as u64),
+                })
+                .collect(),
+            result,
+            rated: false,
+        }
+    }
+
+    #[test]
+    fn records_round_trip_through_their_line() {
+        for result in [
+            GameResult::FirstPlayerWin,
+            GameResult::SecondPlayerWin,
+            GameResult::Draw,
+            GameResult::Abandoned {
+                by: "bob".to_string(),
+            },
+        ] {
+            let line = record(result).to_string();
+            let parsed = line.parse::<GameRecord>().unwrap();
+            assert_eq!(parsed.to_string(), line);
+            assert_eq!(parsed.moves[1].username, "bob");
+            assert!(!parsed.moves[1].first_player);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let line = record(GameResult::Draw).to_string();
+        assert_eq!(
+            line.replace("result=draw", "result=won")
+                .parse::<GameRecord>()
+                .err(),
+            Some(RecordError::InvalidValue {
+                key: "result",
+                value: "won".to_string()
+            })
+        );
+        assert_eq!(
+            line.replace("4@3000", "4").parse::<GameRecord>().err(),
+            Some(RecordError::MalformedMove("4".to_string()))
+        );
+        assert_eq!(
+            line.replace("id=7 ", "").parse::<GameRecord>().err(),
+            Some(RecordError::MissingField("id"))
+        );
+        assert!("".parse::<GameRecord>().is_err());
+    }
+
+    #[test]
+    fn reads_records_from_before_rated_games() {
+        let line = record(GameResult::Draw)
+            .to_string()
+            .replace("rated=false ", "");
+        assert!(!line.parse::<GameRecord>().unwrap().rated);
+    }
+}
@@ -0,0 +1,661 @@
+//! Perfect play.
+//!
+//! Scores follow the usual convention for connect 4 solvers: zero is a draw, a positive score
+//! means the player to move can force a win and a negative one that they lose against perfect
+//! play. A win is worth 22 minus the number of discs the winner needed, so winning with a fourth
+//! disc scores 18 and winning with the last disc of the board scores 1.
+//!
+//! The search is a negamax with alpha-beta pruning over bitboards, narrowing the score down with
+//! null windows and remembering bounds in a transposition table between searches.
+//! [`ParallelSolver`] runs the same search on several threads that share their table.
+
+use crate::board::{Board, Outcome, Player, BOARD_MASK, BOTTOM_ROW, COLUMN_BITS, HEIGHT, WIDTH};
+use crate::bot::CENTRE_FIRST;
+use crate::transposition::{Bound, Entry, Replacement, TranspositionTable};
+use crate::zobrist;
+use std::cmp::Reverse;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+const CELLS: i32 = (WIDTH * HEIGHT) as i32;
+pub const MIN_SCORE: i32 = -CELLS / 2 + 3;
+pub const MAX_SCORE: i32 = (CELLS + 1) / 2 - 3;
+
+/// 64MB of entries.
+const TABLE_SIZE: usize = 1 << 22;
+/// Kept a power of two so checking it is a mask.
+const DEADLINE_CHECK_INTERVAL: u64 = 4096;
+
+pub struct Solver {
+    table: Arc<TranspositionTable>,
+    nodes: u64,
+    deadline: Option<Instant>,
+    /// Set by whoever wants the search to end early.
+    stop: Option<Arc<AtomicBool>>,
+    /// Set by the caller to give up on the search. Unlike `stop`, no search ever sets it.
+    cancel: Option<Arc<AtomicBool>>,
+    stopped: bool,
+    /// How far along [`CENTRE_FIRST`] the move ordering starts, so the threads of a parallel
+    /// search don't all walk the tree in the same order.
+    rotation: usize,
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Self::sharing(Arc::new(new_table()), 0)
+    }
+
+    fn sharing(table: Arc<TranspositionTable>, rotation: usize) -> Self {
+        Self {
+            table,
+            nodes: 0,
+            deadline: None,
+            stop: None,
+            cancel: None,
+            stopped: false,
+            rotation,
+        }
+    }
+
+    /// The number of positions searched since the solver was created.
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Forgets every position searched so far.
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    /// The exact score of the position for the player to move. Positions close to the start of
+    /// the game can take a long time.
+    pub fn solve(&mut self, board: &Board) -> i32 {
+        self.table.new_search();
+        self.search(board, None, None, None)
+            .expect("a search without a deadline always finishes")
+    }
+
+    /// Like [`Solver::solve`], giving up once the deadline passes.
+    pub fn solve_until(&mut self, board: &Board, deadline: Instant) -> Option<i32> {
+        self.table.new_search();
+        self.search(board, Some(deadline), None, None)
+    }
+
+    /// The score of playing each column, from the point of view of the player to move. Full
+    /// columns have no score.
+    pub fn analyze(&mut self, board: &Board) -> [Option<i32>; WIDTH] {
+        self.table.new_search();
+        analyze_moves(board, |child| self.search(child, None, None, None))
+            .expect("a search without a deadline always finishes")
+    }
+
+    /// Like [`Solver::analyze`], giving up once the deadline passes.
+    pub fn analyze_until(
+        &mut self,
+        board: &Board,
+        deadline: Instant,
+    ) -> Option<[Option<i32>; WIDTH]> {
+        self.table.new_search();
+        analyze_moves(board, |child| {
+            self.search(child, Some(deadline), None, None)
+        })
+    }
+
+    pub fn best_move(&mut self, board: &Board) -> Option<u8> {
+        best_move(&self.analyze(board))
+    }
+
+    fn search(
+        &mut self,
+        board: &Board,
+        deadline: Option<Instant>,
+        stop: Option<Arc<AtomicBool>>,
+        cancel: Option<Arc<AtomicBool>>,
+    ) -> Option<i32> {
+        let moves = board.move_count() as i32;
+        match board.outcome() {
+            Some(Outcome::Draw) => return Some(0),
+            // the player to move is the one who lost
+            Some(Outcome::Win(_)) => return Some(-(CELLS + 2 - moves) / 2),
+            None => {}
+        }
+
+        self.deadline = deadline;
+        self.stop = stop;
+        self.cancel = cancel;
+        self.stopped = false;
+        let position = Position::from_board(board);
+        if position.can_win_next() {
+            return Some((CELLS + 1 - moves) / 2);
+        }
+
+        let mut min = -(CELLS - moves) / 2;
+        let mut max = (CELLS + 1 - moves) / 2;
+        while min < max {
+            // try windows closer to zero first, they're cheaper to refute
+            let mut middle = min + (max - min) / 2;
+            if middle <= 0 && min / 2 < middle {
+                middle = min / 2;
+            } else if middle >= 0 && max / 2 > middle {
+                middle = max / 2;
+            }
+            let score = self.negamax(position, middle, middle + 1);
+            if self.stopped {
+                return None;
+            }
+            if score <= middle {
+                max = score;
+            } else {
+                min = score;
+            }
+        }
+        Some(min)
+    }
+
+    fn negamax(&mut self, position: Position, mut alpha: i32, mut beta: i32) -> i32 {
+        self.nodes += 1;
+        if self.nodes & (DEADLINE_CHECK_INTERVAL - 1) == 0 {
+            if let Some(deadline) = self.deadline {
+                self.stopped |= Instant::now() >= deadline;
+            }
+            if let Some(stop) = &self.stop {
+                self.stopped |= stop.load(Ordering::Relaxed);
+            }
+            if let Some(cancel) = &self.cancel {
+                self.stopped |= cancel.load(Ordering::Relaxed);
+            }
+        }
+        if self.stopped {
+            return alpha;
+        }
+
+        let next = position.non_losing_moves();
+        if next == 0 {
+            return -(CELLS - position.moves) / 2;
+        }
+        if position.moves >= CELLS - 2 {
+            return 0;
+        }
+
+        let min = -(CELLS - 2 - position.moves) / 2;
+        if alpha < min {
+            alpha = min;
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+        let mut max = (CELLS - 1 - position.moves) / 2;
+        match self.table.get(position.hash) {
+            Some(Entry {
+                score,
+                bound: Bound::Lower,
+                ..
+            }) => {
+                alpha = alpha.max(score as i32);
+                if alpha >= beta {
+                    return alpha;
+                }
+            }
+            Some(Entry { score, .. }) => max = max.min(score as i32),
+            None => {}
+        }
+        if beta > max {
+            beta = max;
+            if alpha >= beta {
+                return beta;
+            }
+        }
+
+        // moves that set up the most threats first, the centre breaking ties
+        let mut candidates = [(0, 0); WIDTH];
+        let mut count = 0;
+        for offset in 0..WIDTH {
+            let column = CENTRE_FIRST[(offset + self.rotation) % WIDTH];
+            let bit = next & column_mask(column as usize);
+            if bit != 0 {
+                candidates[count] = (bit, position.move_score(bit));
+                count += 1;
+            }
+        }
+        let candidates = &mut candidates[..count];
+        candidates.sort_by_key(|(_, score)| Reverse(*score));
+
+        for (bit, _) in candidates.iter() {
+            let mut child = position;
+            child.play(*bit);
+            let score = -self.negamax(child, -beta, -alpha);
+            if score >= beta {
+                self.remember(&position, score, Bound::Lower);
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+
+        self.remember(&position, alpha, Bound::Upper);
+        alpha
+    }
+
+    fn remember(&self, position: &Position, score: i32, bound: Bound) {
+        // a stopped search returns made up scores
+        if !self.stopped {
+            self.table.put(
+                position.hash,
+                Entry {
+                    score: score as i16,
+                    bound,
+                    depth: (CELLS - position.moves) as u8,
+                    best_move: None,
+                },
+            );
+        }
+    }
+}
+
+/// Scores are exact bounds that stay true, so what's worth keeping is the entries that took the
+/// most work, the ones with the most empty cells below them. Entries of earlier searches make
+/// way, positions from old games are rarely seen again.
+fn new_table() -> TranspositionTable {
+    TranspositionTable::new(TABLE_SIZE, Replacement::DepthPreferred)
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lazy SMP: every thread searches the same position, sharing what they learn through one
+/// transposition table and trying the moves in different orders so they end up in different
+/// parts of the tree. The first thread to finish answers for all of them. Scores are exact, so
+/// the answers don't depend on the number of threads or on which one finished first.
+///
+/// The calling thread searches too, the helper threads are started once and wait for work in
+/// between searches.
+pub struct ParallelSolver {
+    solver: Solver,
+    helpers: Vec<Helper>,
+    helper_nodes: u64,
+}
+
+struct Helper {
+    jobs: Option<mpsc::Sender<Job>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Job {
+    board: Board,
+    deadline: Option<Instant>,
+    stop: Arc<AtomicBool>,
+    cancel: Option<Arc<AtomicBool>>,
+    /// The score if the helper finished, and the positions it searched.
+    result: mpsc::Sender<(Option<i32>, u64)>,
+}
+
+impl ParallelSolver {
+    /// A solver searching with `threads` threads in total, one of them being the caller's.
+    pub fn new(threads: usize) -> Self {
+        let table = Arc::new(new_table());
+        let helpers = (1..threads.max(1))
+            .map(|rotation| {
+                let (jobs, job_receiver) = mpsc::channel::<Job>();
+                let mut solver = Solver::sharing(table.clone(), rotation);
+                let thread = std::thread::spawn(move || {
+                    for job in job_receiver {
+                        let nodes = solver.nodes;
+                        let score = solver.search(
+                            &job.board,
+                            job.deadline,
+                            Some(job.stop.clone()),
+                            job.cancel.clone(),
+                        );
+                        if score.is_some() {
+                            job.stop.store(true, Ordering::Relaxed);
+                        }
+                        let _ = job.result.send((score, solver.nodes - nodes));
+                    }
+                });
+                Helper {
+                    jobs: Some(jobs),
+                    thread: Some(thread),
+                }
+            })
+            .collect();
+        Self {
+            solver: Solver::sharing(table, 0),
+            helpers,
+            helper_nodes: 0,
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.helpers.len() + 1
+    }
+
+    /// The number of positions searched by all of the threads together.
+    pub fn nodes(&self) -> u64 {
+        self.solver.nodes + self.helper_nodes
+    }
+
+    pub fn clear(&mut self) {
+        self.solver.clear();
+    }
+
+    pub fn solve(&mut self, board: &Board) -> i32 {
+        self.search(board, None, None)
+            .expect("a search without a deadline always finishes")
+    }
+
+    pub fn solve_until(&mut self, board: &Board, deadline: Instant) -> Option<i32> {
+        self.search(board, Some(deadline), None)
+    }
+
+    pub fn analyze(&mut self, board: &Board) -> [Option<i32>; WIDTH] {
+        analyze_moves(board, |child| self.search(child, None, None))
+            .expect("a search without a deadline always finishes")
+    }
+
+    pub fn analyze_until(
+        &mut self,
+        board: &Board,
+        deadline: Instant,
+    ) -> Option<[Option<i32>; WIDTH]> {
+        analyze_moves(board, |child| self.search(child, Some(deadline), None))
+    }
+
+    /// Like [`ParallelSolver::analyze_until`], but the deadline is optional and the search also
+    /// gives up as soon as `cancel` is set.
+    pub fn analyze_cancellable(
+        &mut self,
+        board: &Board,
+        deadline: Option<Instant>,
+        cancel: &Arc<AtomicBool>,
+    ) -> Option<[Option<i32>; WIDTH]> {
+        analyze_moves(board, |child| self.search(child, deadline, Some(cancel)))
+    }
+
+    pub fn best_move(&mut self, board: &Board) -> Option<u8> {
+        best_move(&self.analyze(board))
+    }
+
+    fn search(
+        &mut self,
+        board: &Board,
+        deadline: Option<Instant>,
+        cancel: Option<&Arc<AtomicBool>>,
+    ) -> Option<i32> {
+        self.solver.table.new_search();
+        let stop = Arc::new(AtomicBool::new(false));
+        // every search has its own channel, so a helper that panicked drops its sender and
+        // can't leave the search waiting for an answer that never comes
+        let (result, results) = mpsc::channel();
+        for helper in &self.helpers {
+            let job = Job {
+                board: *board,
+                deadline,
+                stop: stop.clone(),
+                cancel: cancel.cloned(),
+                result: result.clone(),
+            };
+            if let Some(jobs) = &helper.jobs {
+                let _ = jobs.send(job);
+            }
+        }
+        drop(result);
+
+        let mut score = self
+            .solver
+            .search(board, deadline, Some(stop.clone()), cancel.cloned());
+        stop.store(true, Ordering::Relaxed);
+        // the helpers notice the stop quickly, and waiting for them keeps them from carrying
+        // on into the next search
+        for (helper_score, nodes) in results {
+            score = score.or(helper_score);
+            self.helper_nodes += nodes;
+        }
+        score
+    }
+}
+
+impl Drop for ParallelSolver {
+    fn drop(&mut self) {
+        for helper in &mut self.helpers {
+            // closing the channel ends the helper's loop
+            helper.jobs.take();
+            if let Some(thread) = helper.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+/// Scores every column of the board with `search`, which solves the positions they lead to.
+fn analyze_moves(
+    board: &Board,
+    mut search: impl FnMut(&Board) -> Option<i32>,
+) -> Option<[Option<i32>; WIDTH]> {
+    let mut scores = [None; WIDTH];
+    if board.outcome().is_some() {
+        return Some(scores);
+    }
+    for column in board.legal_moves() {
+        let score = if board.is_winning_move(column) {
+            win_score(board)
+        } else {
+            let mut child = *board;
+            let _ = child.drop_piece(column);
+            -search(&child)?
+        };
+        scores[column as usize] = Some(score);
+    }
+    Some(scores)
+}
+
+/// The score of winning with the next disc.
+pub fn win_score(board: &Board) -> i32 {
+    (CELLS + 1 - board.move_count() as i32) / 2
+}
+
+/// Puts the score of a move, as [`Solver::analyze`] gives it for a board with `moves` discs on
+/// it, into words.
+pub fn describe_score(moves: usize, score: i32) -> String {
+    let moves = moves as i32;
+    let (verb, remaining) = match score {
+        0 => return "draws".to_string(),
+        // the mover's own discs and the opponent's discs up to the one that wins
+        score if score > 0 => ("wins", CELLS / 2 + 1 - score - moves / 2),
+        score => ("loses", CELLS / 2 + 1 + score - (moves + 1) / 2),
+    };
+    match (verb, remaining) {
+        ("wins", 1) => "wins right away".to_string(),
+        (verb, 1) => format!("{verb} next move"),
+        (verb, remaining) => format!("{verb} in {remaining} moves"),
+    }
+}
+
+/// The best scoring column, preferring the centre between equally good ones.
+pub fn best_move(scores: &[Option<i32>; WIDTH]) -> Option<u8> {
+    CENTRE_FIRST
+        .into_iter()
+        .rev()
+        .filter_map(|column| scores[column as usize].map(|score| (column, score)))
+        .max_by_key(|(_, score)| *score)
+        .map(|(column, _)| column)
+}
+
+#[derive(Clone, Copy)]
+struct Position {
+    current: u64,
+    mask: u64,
+    moves: i32,
+    /// The Zobrist hash, see [`Board::zobrist`].
+    hash: u64,
+}
+
+impl Position {
+    fn from_board(board: &Board) -> Self {
+        let (current, mask) = board.bitboards();
+        Self {
+            current,
+            mask,
+            moves: board.move_count() as i32,
+            hash: board.zobrist(),
+        }
+    }
+
+    fn possible(&self) -> u64 {
+        (self.mask + BOTTOM_ROW) & BOARD_MASK
+    }
+
+    fn can_win_next(&self) -> bool {
+        winning_cells(self.current, self.mask) & self.possible() != 0
+    }
+
+    fn play(&mut self, bit: u64) {
+        let player = if self.moves & 1 == 0 {
+            Player::First
+        } else {
+            Player::Second
+        };
+        let cell = bit.trailing_zeros() as usize;
+        self.hash ^=
+            zobrist::cell_key(player, cell / COLUMN_BITS, cell % COLUMN_BITS) ^ zobrist::side_key();
+        self.current ^= self.mask;
+        self.mask |= bit;
+        self.moves += 1;
+    }
+
+    /// The moves that don't let the opponent win straight away, assuming the player to move
+    /// can't win straight away either.
+    fn non_losing_moves(&self) -> u64 {
+        let mut possible = self.possible();
+        let opponent_wins = winning_cells(self.current ^ self.mask, self.mask);
+        let forced = possible & opponent_wins;
+        if forced != 0 {
+            if forced & (forced - 1) != 0 {
+                // two threats can't both be blocked
+                return 0;
+            }
+            possible = forced;
+        }
+        // and never play right under a cell the opponent wins with
+        possible & !(opponent_wins >> 1)
+    }
+
+    fn move_score(&self, bit: u64) -> u32 {
+        winning_cells(self.current | bit, self.mask).count_ones()
+    }
+}
+
+pub(crate) const fn column_mask(column: usize) -> u64 {
+    ((1 << HEIGHT) - 1) << (column * COLUMN_BITS)
+}
+
+/// The empty cells that would connect four for the owner of `discs`.
+pub(crate) fn winning_cells(discs: u64, mask: u64) -> u64 {
+    let mut cells = (discs << 1) & (discs << 2) & (discs << 3);
+    for shift in [COLUMN_BITS, COLUMN_BITS - 1, COLUMN_BITS + 1] {
+        let pair = (discs << shift) & (discs << (2 * shift));
+        cells |= pair & (discs << (3 * shift));
+        cells |= pair & (discs >> shift);
+        let pair = (discs >> shift) & (discs >> (2 * shift));
+        cells |= pair & (discs << shift);
+        cells |= pair & (discs >> (3 * shift));
+    }
+    cells & (BOARD_MASK ^ mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::parse_game;
+
+    const POSITIONS: [(&str, [Option<i32>; WIDTH]); 3] = [
+        (
+            "65465375611266",
+            [
+                Some(4),
+                Some(3),
+                Some(5),
+                Some(3),
+                Some(4),
+                Some(3),
+                Some(3),
+            ],
+        ),
+        (
+            "1173321324713161",
+            [
+                None,
+                Some(-13),
+                Some(-13),
+                Some(-13),
+                Some(0),
+                Some(-13),
+                Some(-13),
+            ],
+        ),
+        (
+            "33517142141522",
+            [
+                Some(13),
+                Some(-10),
+                Some(-2),
+                Some(1),
+                Some(0),
+                Some(14),
+                Some(-9),
+            ],
+        ),
+    ];
+
+    #[test]
+    fn analyzes_positions() {
+        let mut solver = Solver::new();
+        for (moves, scores) in POSITIONS {
+            assert_eq!(
+                solver.analyze(&parse_game(moves).unwrap()),
+                scores,
+                "{moves}"
+            );
+        }
+    }
+
+    #[test]
+    fn parallel_search_gives_the_same_scores() {
+        for threads in [1, 2, 4] {
+            let mut solver = ParallelSolver::new(threads);
+            for (moves, scores) in POSITIONS {
+                let board = parse_game(moves).unwrap();
+                assert_eq!(
+                    solver.analyze(&board),
+                    scores,
+                    "{moves} on {threads} threads"
+                );
+                assert_eq!(solver.best_move(&board), best_move(&scores));
+            }
+        }
+    }
+
+    #[test]
+    fn searches_carry_on_after_a_helper_panics() {
+        let mut solver = ParallelSolver::new(2);
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let thread = std::thread::spawn(move || {
+            let _job = job_receiver.recv();
+            panic!("helper failed");
+        });
+        solver.helpers.push(Helper {
+            jobs: Some(jobs),
+            thread: Some(thread),
+        });
+        for (moves, scores) in POSITIONS {
+            assert_eq!(
+                solver.analyze(&parse_game(moves).unwrap()),
+                scores,
+                "{moves}"
+            );
+        }
+    }
+}
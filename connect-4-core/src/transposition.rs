@@ -0,0 +1,212 @@
+//! A transposition table that several search threads can share.
+//!
+//! Every slot is two atomics, the entry itself and the entry XORed with the Zobrist hash of its
+//! position. Reads and writes never lock, so two threads writing the same slot at once can leave
+//! the halves of different entries in it, but a torn slot no longer passes the XOR check and
+//! reads as empty instead of handing out another position's entry.
+
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    /// The score is at least this high.
+    Lower,
+    /// The score is at most this high.
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub score: i16,
+    pub bound: Bound,
+    /// How deep the search below the position went, searches that run to the end of the game
+    /// can use the number of empty cells.
+    pub depth: u8,
+    pub best_move: Option<u8>,
+}
+
+/// Which of two entries competing for a slot stays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Replacement {
+    /// The newest entry always takes the slot.
+    Always,
+    /// The deeper entry keeps the slot, unless it was stored before the current search started
+    /// or is for the same position.
+    DepthPreferred,
+}
+
+const NO_MOVE: u64 = 0xf;
+
+pub struct TranspositionTable {
+    slots: Vec<[AtomicU64; 2]>,
+    replacement: Replacement,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    /// A table of `size` entries, 16 bytes each.
+    pub fn new(size: usize, replacement: Replacement) -> Self {
+        Self {
+            slots: (0..size.max(1))
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+            replacement,
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn replacement(&self) -> Replacement {
+        self.replacement
+    }
+
+    /// Marks the entries stored so far as old, so [`Replacement::DepthPreferred`] lets them go.
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Empties the table. Entries stored by searches still running at the time may survive.
+    pub fn clear(&self) {
+        for [check, data] in &self.slots {
+            check.store(0, Ordering::Relaxed);
+            data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn get(&self, hash: u64) -> Option<Entry> {
+        let data = self.load(hash)?;
+        Some(Entry {
+            score: data as u16 as i16,
+            bound: match (data >> 16) & 0b11 {
+                1 => Bound::Exact,
+                2 => Bound::Lower,
+                _ => Bound::Upper,
+            },
+            depth: (data >> 18) as u8,
+            best_move: match (data >> 26) & 0xf {
+                NO_MOVE => None,
+                column => Some(column as u8),
+            },
+        })
+    }
+
+    pub fn put(&self, hash: u64, entry: Entry) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let [check, data] = &self.slots[self.index(hash)];
+        if self.replacement == Replacement::DepthPreferred {
+            let old = data.load(Ordering::Relaxed);
+            let same_position = check.load(Ordering::Relaxed) ^ old == hash;
+            if old != 0
+                && !same_position
+                && (old >> 30) as u8 == generation
+                && ((old >> 18) as u8) > entry.depth
+            {
+                return;
+            }
+        }
+
+        let bound = match entry.bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+        let best_move = entry
+            .best_move
+            .map_or(NO_MOVE, |column| column as u64 & 0xf);
+        let new = entry.score as u16 as u64
+            | bound << 16
+            | (entry.depth as u64) << 18
+            | best_move << 26
+            | (generation as u64) << 30;
+        data.store(new, Ordering::Relaxed);
+        check.store(hash ^ new, Ordering::Relaxed);
+    }
+
+    fn load(&self, hash: u64) -> Option<u64> {
+        let [check, data] = &self.slots[self.index(hash)];
+        let data = data.load(Ordering::Relaxed);
+        let check = check.load(Ordering::Relaxed);
+        (data != 0 && check ^ data == hash).then_some(data)
+    }
+
+    /// Zobrist hashes are evenly spread over every bit, so the high half of the product maps
+    /// them evenly onto any table size.
+    fn index(&self, hash: u64) -> usize {
+        ((hash as u128 * self.slots.len() as u128) >> 64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: u64 = 0x0123_4567_89ab_cdef;
+    /// Lands in the same slot as [`HASH`] in a table of one entry.
+    const OTHER_HASH: u64 = 0xfedc_ba98_7654_3210;
+
+    fn entry(depth: u8) -> Entry {
+        Entry {
+            score: 5,
+            bound: Bound::Exact,
+            depth,
+            best_move: Some(3),
+        }
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let table = TranspositionTable::new(64, Replacement::Always);
+        assert_eq!(table.get(HASH), None);
+        for bound in [Bound::Exact, Bound::Lower, Bound::Upper] {
+            for best_move in [None, Some(0), Some(6)] {
+                for score in [i16::MIN, -21, -1, 0, 1, 21, i16::MAX] {
+                    let entry = Entry {
+                        score,
+                        bound,
+                        depth: 42,
+                        best_move,
+                    };
+                    table.put(HASH, entry);
+                    assert_eq!(table.get(HASH), Some(entry));
+                }
+            }
+        }
+        assert_eq!(table.get(HASH ^ 1), None);
+        table.clear();
+        assert_eq!(table.get(HASH), None);
+    }
+
+    #[test]
+    fn always_replaces() {
+        let table = TranspositionTable::new(1, Replacement::Always);
+        table.put(HASH, entry(20));
+        table.put(OTHER_HASH, entry(10));
+        assert_eq!(table.get(HASH), None);
+        assert_eq!(table.get(OTHER_HASH), Some(entry(10)));
+    }
+
+    #[test]
+    fn depth_preferred_keeps_deeper_entries_of_the_current_search() {
+        let table = TranspositionTable::new(1, Replacement::DepthPreferred);
+        table.put(HASH, entry(20));
+        table.put(OTHER_HASH, entry(10));
+        assert_eq!(table.get(HASH), Some(entry(20)));
+        assert_eq!(table.get(OTHER_HASH), None);
+
+        // the same position is always updated
+        table.put(HASH, entry(5));
+        assert_eq!(table.get(HASH), Some(entry(5)));
+        table.put(OTHER_HASH, entry(10));
+        assert_eq!(table.get(OTHER_HASH), Some(entry(10)));
+
+        table.put(HASH, entry(20));
+        table.new_search();
+        table.put(OTHER_HASH, entry(1));
+        assert_eq!(table.get(HASH), None);
+        assert_eq!(table.get(OTHER_HASH), Some(entry(1)));
+    }
+}
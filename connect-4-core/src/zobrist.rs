@@ -0,0 +1,97 @@
+//! Zobrist hashing.
+//!
+//! Every cell has a random key for each player and a position hashes to the keys of its discs
+//! XORed together, plus one more key when the second player is to move. Dropping or popping a
+//! disc only has to XOR two keys in or out, which is what [`Board`](crate::board::Board) does
+//! to keep its hash up to date.
+//!
+//! The keys are generated at compile time from a fixed seed, so hashes are the same in every
+//! build and can be stored.
+
+use crate::board::{Player, HEIGHT, WIDTH};
+
+const SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+const CELL_KEYS: [[[u64; HEIGHT]; WIDTH]; 2] = cell_keys();
+const SECOND_TO_MOVE_KEY: u64 = splitmix64(SEED ^ 0xffff_ffff);
+
+/// The splitmix64 finaliser, a cheap way to turn a counter into well mixed bits.
+const fn splitmix64(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+const fn cell_keys() -> [[[u64; HEIGHT]; WIDTH]; 2] {
+    let mut keys = [[[0; HEIGHT]; WIDTH]; 2];
+    let mut counter = SEED;
+    let mut player = 0;
+    while player < 2 {
+        let mut column = 0;
+        while column < WIDTH {
+            let mut row = 0;
+            while row < HEIGHT {
+                counter = counter.wrapping_add(1);
+                keys[player][column][row] = splitmix64(counter);
+                row += 1;
+            }
+            column += 1;
+        }
+        player += 1;
+    }
+    keys
+}
+
+/// The key of a disc of the player in the cell.
+pub fn cell_key(player: Player, column: usize, row: usize) -> u64 {
+    CELL_KEYS[player.index()][column][row]
+}
+
+/// Toggled in and out of the hash on every move.
+pub fn side_key() -> u64 {
+    SECOND_TO_MOVE_KEY
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::board::Board;
+    use crate::notation::parse_game;
+
+    /// Swaps every column in the notation for its mirror image.
+    fn mirror_moves(moves: &str) -> String {
+        moves
+            .chars()
+            .map(|column| (b'8' - (column as u8 - b'0')) as char)
+            .collect()
+    }
+
+    #[test]
+    fn popping_a_drop_restores_the_hash() {
+        let mut board = parse_game("4453").unwrap();
+        let before = board.zobrist();
+        for column in board.legal_moves().collect::<Vec<_>>() {
+            board.drop_piece(column).unwrap();
+            assert_ne!(board.zobrist(), before);
+            board.pop_piece(column).unwrap();
+            assert_eq!(board.zobrist(), before, "after dropping into {column}");
+        }
+        let mut empty = parse_game("4").unwrap();
+        empty.pop_piece(3).unwrap();
+        assert_eq!(empty.zobrist(), Board::new().zobrist());
+    }
+
+    #[test]
+    fn mirroring_hashes_like_playing_the_mirrored_moves() {
+        for moves in ["", "1", "4", "1123", "7654321", "3344562"] {
+            let board = parse_game(moves).unwrap();
+            let mirrored = parse_game(&mirror_moves(moves)).unwrap();
+            assert_eq!(board.mirrored(), mirrored, "{moves}");
+            assert_eq!(board.mirrored().zobrist(), mirrored.zobrist(), "{moves}");
+        }
+        assert_ne!(
+            parse_game("1").unwrap().zobrist(),
+            parse_game("7").unwrap().zobrist()
+        );
+    }
+}
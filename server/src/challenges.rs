@@ -0,0 +1,28 @@
+use connect_4_core::packets::GameRules;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Challenges sent between online players that haven't been answered yet, keyed by the
+/// challenger and the challenged player.
+#[derive(Default)]
+pub struct PendingChallenges {
+    challenges: HashMap<(Uuid, Uuid), GameRules>,
+}
+
+impl PendingChallenges {
+    /// Records a challenge, replacing any earlier challenge between the same two players.
+    pub fn issue(&mut self, challenger: Uuid, target: Uuid, rules: GameRules) {
+        self.challenges.insert((challenger, target), rules);
+    }
+
+    /// Removes a challenge and returns the rules it was sent with.
+    pub fn take(&mut self, challenger: Uuid, target: Uuid) -> Option<GameRules> {
+        self.challenges.remove(&(challenger, target))
+    }
+
+    /// Drops every challenge sent by or to the given client.
+    pub fn remove_involving(&mut self, client: &Uuid) {
+        self.challenges
+            .retain(|(challenger, target), _| !challenger.eq(client) && !target.eq(client));
+    }
+}
@@ -0,0 +1,148 @@
+use regex::Regex;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const MAX_CHAT_LENGTH: usize = 200;
+const RATE_LIMIT_MESSAGES: usize = 5;
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(10);
+
+/// Rewrites chat messages before they're relayed to other players.
+pub trait ChatFilter {
+    fn filter(&self, message: &str) -> String;
+}
+
+pub struct NoFilter;
+
+impl ChatFilter for NoFilter {
+    fn filter(&self, message: &str) -> String {
+        message.to_string()
+    }
+}
+
+/// Masks every whole-word, case-insensitive match of a blocked word with asterisks.
+pub struct WordListFilter {
+    pattern: Regex,
+}
+
+impl WordListFilter {
+    pub fn new<S: AsRef<str>>(words: &[S]) -> Result<Self, regex::Error> {
+        let alternatives = words
+            .iter()
+            .map(|word| regex::escape(word.as_ref().trim()))
+            .filter(|word| !word.is_empty())
+            .collect::<Vec<_>>()
+            .join("|");
+        Ok(Self {
+            pattern: Regex::new(&format!(r"(?i)\b(?:{alternatives})\b"))?,
+        })
+    }
+
+    /// Loads a blocked word list with one word per line.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(Self::new(&contents.lines().collect::<Vec<_>>())?)
+    }
+}
+
+impl ChatFilter for WordListFilter {
+    fn filter(&self, message: &str) -> String {
+        self.pattern
+            .replace_all(message, |captures: &regex::Captures| {
+                "*".repeat(captures[0].chars().count())
+            })
+            .into_owned()
+    }
+}
+
+/// Sliding window limit on how many chat messages a single client can send.
+#[derive(Default)]
+pub struct ChatRateLimiter {
+    sent: VecDeque<Instant>,
+}
+
+impl ChatRateLimiter {
+    pub fn try_send(&mut self) -> bool {
+        let now = Instant::now();
+        while let Some(sent) = self.sent.front() {
+            if now.duration_since(*sent) < RATE_LIMIT_WINDOW {
+                break;
+            }
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= RATE_LIMIT_MESSAGES {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
+
+/// Checks a chat message against the length and rate limits and runs it through the filter,
+/// returning the message to relay or the reason it was rejected.
+pub fn prepare_chat_message(
+    message: &str,
+    limiter: &mut ChatRateLimiter,
+    filter: &dyn ChatFilter,
+) -> Result<String, String> {
+    let message = message.trim();
+    if message.is_empty() {
+        return Err("Chat messages can't be empty.".to_string());
+    }
+    if message.chars().count() > MAX_CHAT_LENGTH {
+        return Err(format!(
+            "Chat messages can't be longer than {MAX_CHAT_LENGTH} characters."
+        ));
+    }
+    if !limiter.try_send() {
+        return Err("You're sending messages too quickly.".to_string());
+    }
+    Ok(filter.filter(message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_whole_words_in_any_case() {
+        let filter = WordListFilter::new(&["darn", " heck "]).unwrap();
+        assert_eq!(filter.filter("Darn it"), "**** it");
+        assert_eq!(
+            filter.filter("what the HECK, heck!"),
+            "what the ****, ****!"
+        );
+        assert_eq!(filter.filter("darned checks"), "darned checks");
+    }
+
+    #[test]
+    fn escapes_blocked_words() {
+        let filter = WordListFilter::new(&["a.b", ""]).unwrap();
+        assert_eq!(filter.filter("a.b axb"), "*** axb");
+    }
+
+    #[test]
+    fn limits_messages_per_window() {
+        let mut limiter = ChatRateLimiter::default();
+        for _ in 0..RATE_LIMIT_MESSAGES {
+            assert!(limiter.try_send());
+        }
+        assert!(!limiter.try_send());
+        // old messages leave the window
+        limiter.sent[0] -= RATE_LIMIT_WINDOW;
+        assert!(limiter.try_send());
+        assert!(!limiter.try_send());
+    }
+
+    #[test]
+    fn rejects_empty_and_long_messages() {
+        let mut limiter = ChatRateLimiter::default();
+        assert!(prepare_chat_message("   ", &mut limiter, &NoFilter).is_err());
+        let long = "a".repeat(MAX_CHAT_LENGTH + 1);
+        assert!(prepare_chat_message(&long, &mut limiter, &NoFilter).is_err());
+        assert_eq!(
+            prepare_chat_message(" hi ", &mut limiter, &NoFilter),
+            Ok("hi".to_string())
+        );
+    }
+}
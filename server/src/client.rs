@@ -8,8 +8,10 @@ pub enum ClientState {
     Login,
     Lobby,
     LookingForGame,
+    HostingRoom,
     WaitingForGame,
     Game,
+    Spectating,
 }
 
 pub struct Client {
@@ -62,11 +64,13 @@ impl Client {
                         ServerboundLoginPacket::RequestUsername {
                             username,
                             transaction_id,
+                            bot,
                         } => {
                             log::debug!("Received username req: {username}, {transaction_id}");
                             self.message_sender.send(ClientMessage::RequestUsername {
                                 username,
                                 transaction_id,
+                                bot,
                             })?;
                         }
                         ServerboundLoginPacket::AcquireUsername => {
@@ -75,7 +79,10 @@ impl Client {
                         }
                     }
                 }
-                ClientState::Lobby | ClientState::LookingForGame | ClientState::WaitingForGame => {
+                ClientState::Lobby
+                | ClientState::LookingForGame
+                | ClientState::HostingRoom
+                | ClientState::WaitingForGame => {
                     match watch_eof!(
                         self.read
                             .decode_component::<(), ServerboundLobbyPacket>(&mut ())
@@ -91,6 +98,68 @@ impl Client {
                             self.message_sender.send(ClientMessage::AcquireGame)?;
                             self.state = ClientState::Game;
                         }
+                        ServerboundLobbyPacket::CancelSearch => {
+                            self.message_sender.send(ClientMessage::CancelSearch)?;
+                        }
+                        ServerboundLobbyPacket::CreateRoom => {
+                            self.message_sender.send(ClientMessage::CreateRoom)?;
+                        }
+                        ServerboundLobbyPacket::JoinRoom { code } => {
+                            self.message_sender.send(ClientMessage::JoinRoom { code })?;
+                        }
+                        ServerboundLobbyPacket::CloseRoom => {
+                            self.message_sender.send(ClientMessage::CloseRoom)?;
+                        }
+                        ServerboundLobbyPacket::ListPlayers => {
+                            self.message_sender.send(ClientMessage::ListPlayers)?;
+                        }
+                        ServerboundLobbyPacket::Challenge { username, rules } => {
+                            self.message_sender
+                                .send(ClientMessage::Challenge { username, rules })?;
+                        }
+                        ServerboundLobbyPacket::AcceptChallenge { username } => {
+                            self.message_sender
+                                .send(ClientMessage::AcceptChallenge { username })?;
+                        }
+                        ServerboundLobbyPacket::DeclineChallenge { username } => {
+                            self.message_sender
+                                .send(ClientMessage::DeclineChallenge { username })?;
+                        }
+                        ServerboundLobbyPacket::Chat { message } => {
+                            self.message_sender
+                                .send(ClientMessage::LobbyChat { message })?;
+                        }
+                        ServerboundLobbyPacket::ListGames => {
+                            self.message_sender.send(ClientMessage::ListGames)?;
+                        }
+                        ServerboundLobbyPacket::Spectate { game_id } => {
+                            self.message_sender
+                                .send(ClientMessage::Spectate { game_id })?;
+                        }
+                        ServerboundLobbyPacket::AcquireSpectate => {
+                            self.message_sender.send(ClientMessage::AcquireSpectate)?;
+                            self.state = ClientState::Spectating;
+                        }
+                        ServerboundLobbyPacket::RequestHistory { username, page } => {
+                            self.message_sender
+                                .send(ClientMessage::RequestHistory { username, page })?;
+                        }
+                        ServerboundLobbyPacket::RequestGameRecord { game_id } => {
+                            self.message_sender
+                                .send(ClientMessage::RequestGameRecord { game_id })?;
+                        }
+                        ServerboundLobbyPacket::RequestLeaderboard { page } => {
+                            self.message_sender
+                                .send(ClientMessage::RequestLeaderboard { page })?;
+                        }
+                        ServerboundLobbyPacket::RequestProfile { username } => {
+                            self.message_sender
+                                .send(ClientMessage::RequestProfile { username })?;
+                        }
+                        ServerboundLobbyPacket::RequestAnalysis { game_id } => {
+                            self.message_sender
+                                .send(ClientMessage::RequestAnalysis { game_id })?;
+                        }
                     }
                 }
                 ClientState::Game => {
@@ -115,6 +184,28 @@ impl Client {
                             self.message_sender.send(ClientMessage::AcquireLobby)?;
                             self.state = ClientState::Lobby;
                         }
+                        ServerboundGamePacket::Chat { message } => {
+                            self.message_sender
+                                .send(ClientMessage::GameChat { message })?;
+                        }
+                        ServerboundGamePacket::RequestHint => {
+                            self.message_sender.send(ClientMessage::RequestHint)?;
+                        }
+                    }
+                }
+                ClientState::Spectating => {
+                    match watch_eof!(
+                        self.read
+                            .decode_component::<(), ServerboundSpectatorPacket>(&mut ())
+                            .await
+                    ) {
+                        ServerboundSpectatorPacket::KeepAlive => {
+                            self.message_sender.send(ClientMessage::KeepAlive)?;
+                        }
+                        ServerboundSpectatorPacket::AcquireLobby => {
+                            self.message_sender.send(ClientMessage::AcquireLobby)?;
+                            self.state = ClientState::Lobby;
+                        }
                     }
                 }
             }
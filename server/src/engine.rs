@@ -0,0 +1,227 @@
+use connect_4_core::analysis::{self, MoveAnalysis};
+use connect_4_core::board::Board;
+use connect_4_core::book::OpeningBook;
+use connect_4_core::bot::{evaluate, Clock, MinimaxPlayer, Player};
+use connect_4_core::solver::{self, ParallelSolver};
+use std::sync::{mpsc, Arc};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use uuid::Uuid;
+
+/// How long a hint may take before a depth limited search stands in for the solver.
+const HINT_TIME: Duration = Duration::from_secs(1);
+/// How long the solver gets for each position of a game it analyses.
+const ANALYSIS_TIME_PER_MOVE: Duration = Duration::from_millis(250);
+
+struct HintRequest {
+    client: Uuid,
+    board: Board,
+}
+
+struct AnalysisRequest {
+    client: Uuid,
+    game_id: i32,
+    moves: Vec<u8>,
+}
+
+pub enum Answer {
+    Hint(Hint),
+    Analysis(Analysis),
+}
+
+#[derive(Debug)]
+pub struct Hint {
+    pub client: Uuid,
+    /// The position the hint is for, so hints that arrive after a move can be dropped.
+    pub board: Board,
+    pub column: u8,
+    /// A solver score when `exact`, otherwise a heuristic guess.
+    pub score: i32,
+    pub exact: bool,
+}
+
+#[derive(Debug)]
+pub struct Analysis {
+    pub client: Uuid,
+    pub game_id: i32,
+    pub moves: Vec<u8>,
+    /// One entry per move, `None` for the positions the solver ran out of time on. `None` as a
+    /// whole when the moves of the record aren't a legal game.
+    pub evaluations: Option<Vec<Option<MoveAnalysis>>>,
+}
+
+/// Searches on behalf of players, on threads of its own so the server loop never waits for a
+/// search. Hints and analyses have a worker each, so a hint never waits behind an analysis of a
+/// whole game. Every worker answers its requests one at a time.
+pub struct Engine {
+    hints: mpsc::Sender<HintRequest>,
+    analyses: mpsc::Sender<AnalysisRequest>,
+    answers: UnboundedReceiver<Answer>,
+}
+
+impl Engine {
+    /// Hints search with `threads` threads, analyses with half as many so hints asked for
+    /// during an analysis still get most of the machine.
+    pub fn new(threads: usize, book: Option<OpeningBook>) -> Self {
+        let book = book.map(Arc::new);
+        let (answer_sender, answers) = unbounded_channel();
+
+        let (hints, hint_receiver) = mpsc::channel();
+        let (hint_book, hint_answers) = (book.clone(), answer_sender.clone());
+        std::thread::spawn(move || run_hints(hint_receiver, hint_answers, threads, hint_book));
+
+        let (analyses, analysis_receiver) = mpsc::channel();
+        let analysis_threads = (threads / 2).max(1);
+        std::thread::spawn(move || {
+            run_analyses(analysis_receiver, answer_sender, analysis_threads, book)
+        });
+
+        Self {
+            hints,
+            analyses,
+            answers,
+        }
+    }
+
+    pub fn request_hint(&self, client: Uuid, board: Board) {
+        if self.hints.send(HintRequest { client, board }).is_err() {
+            log::error!("The hint thread is gone, hints can't be answered.");
+        }
+    }
+
+    pub fn request_analysis(&self, client: Uuid, game_id: i32, moves: Vec<u8>) {
+        let request = AnalysisRequest {
+            client,
+            game_id,
+            moves,
+        };
+        if self.analyses.send(request).is_err() {
+            log::error!("The analysis thread is gone, analyses can't be answered.");
+        }
+    }
+
+    pub fn poll_answer(&mut self, cx: &mut Context<'_>) -> Poll<Option<Answer>> {
+        self.answers.poll_recv(cx)
+    }
+}
+
+fn run_hints(
+    requests: mpsc::Receiver<HintRequest>,
+    answers: UnboundedSender<Answer>,
+    threads: usize,
+    book: Option<Arc<OpeningBook>>,
+) {
+    let mut solver = ParallelSolver::new(threads);
+    for HintRequest { client, board } in requests {
+        if board.outcome().is_some() {
+            continue;
+        }
+        let hint = hint(&mut solver, book.as_deref(), client, board);
+        if answers.send(Answer::Hint(hint)).is_err() {
+            return;
+        }
+    }
+}
+
+fn run_analyses(
+    requests: mpsc::Receiver<AnalysisRequest>,
+    answers: UnboundedSender<Answer>,
+    threads: usize,
+    book: Option<Arc<OpeningBook>>,
+) {
+    let mut solver = ParallelSolver::new(threads);
+    for AnalysisRequest {
+        client,
+        game_id,
+        moves,
+    } in requests
+    {
+        let evaluations =
+            analysis::analyze_game(&moves, &mut solver, book.as_deref(), ANALYSIS_TIME_PER_MOVE)
+                .map_err(|err| {
+                    log::error!("Game {game_id} can't be analysed, its record is broken: {err}");
+                })
+                .ok();
+        let analysis = Analysis {
+            client,
+            game_id,
+            moves,
+            evaluations,
+        };
+        if answers.send(Answer::Analysis(analysis)).is_err() {
+            return;
+        }
+    }
+}
+
+fn hint(
+    solver: &mut ParallelSolver,
+    book: Option<&OpeningBook>,
+    client: Uuid,
+    board: Board,
+) -> Hint {
+    let scores = match book.and_then(|book| book.analyze(&board)) {
+        Some(scores) => Some(scores),
+        None => solver.analyze_until(&board, Instant::now() + HINT_TIME),
+    };
+    match scores {
+        Some(scores) => {
+            let column = solver::best_move(&scores).expect("the game isn't over");
+            Hint {
+                client,
+                board,
+                column,
+                score: scores[column as usize].unwrap_or_default(),
+                exact: true,
+            }
+        }
+        None => {
+            let column = MinimaxPlayer::new(8).choose_move(&board, &Clock::new(HINT_TIME));
+            let mut child = board;
+            let _ = child.drop_piece(column);
+            Hint {
+                client,
+                board,
+                column,
+                score: -evaluate(&child),
+                exact: false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use connect_4_core::notation::{parse_game, parse_moves};
+
+    #[test]
+    fn hints_dont_wait_for_analyses() {
+        let mut engine = Engine::new(2, None);
+        let moves = parse_moves("5471256622612712").unwrap();
+        engine.request_analysis(Uuid::new_v4(), 1, moves);
+        let board = parse_game("65465375611266").unwrap();
+        engine.request_hint(Uuid::new_v4(), board);
+
+        let Some(Answer::Hint(hint)) = engine.answers.blocking_recv() else {
+            panic!("the analysis was answered first");
+        };
+        // how deep the hint got depends on the build, only its order is checked
+        assert_eq!(hint.board, board);
+        let Some(Answer::Analysis(analysis)) = engine.answers.blocking_recv() else {
+            panic!("the analysis wasn't answered");
+        };
+        assert_eq!(analysis.evaluations.map(|moves| moves.len()), Some(16));
+    }
+
+    #[test]
+    fn broken_records_have_no_analysis() {
+        let mut engine = Engine::new(1, None);
+        engine.request_analysis(Uuid::new_v4(), 1, vec![3; 7]);
+        let Some(Answer::Analysis(analysis)) = engine.answers.blocking_recv() else {
+            panic!("the analysis wasn't answered");
+        };
+        assert!(analysis.evaluations.is_none());
+    }
+}
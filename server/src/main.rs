@@ -1,19 +1,29 @@
 #![feature(macro_metavar_expr)]
 #![feature(map_many_mut)]
-#![feature(iter_next_chunk)]
 
 use log::LevelFilter;
 use tokio::net::TcpListener;
 use tokio::runtime::Builder;
 use tokio::task::LocalSet;
 
+use connect_4_core::book::OpeningBook;
 use connect_4_core::drax::err_explain;
 use connect_4_core::logger::{system_logger, LoggerOptions};
 
+use crate::chat::{ChatFilter, NoFilter, WordListFilter};
 use crate::client::Client;
+use crate::engine::Engine;
+use crate::record::{FileRecordSink, NullRecordSink, RecordSink};
 use crate::server::{ClientAdd, Connect4Server};
 
+pub mod challenges;
+pub mod chat;
 pub mod client;
+pub mod engine;
+pub mod matchmaking;
+pub mod ratings;
+pub mod record;
+pub mod rooms;
 pub mod server;
 
 #[tokio::main]
@@ -35,7 +45,32 @@ pub async fn main() -> anyhow::Result<()> {
         let local = LocalSet::new();
 
         local.spawn_local(async move {
-            let mut server = Connect4Server::new(receiver);
+            let chat_filter: Box<dyn ChatFilter> =
+                match WordListFilter::from_file("./blocked_words.txt") {
+                    Ok(filter) => Box::new(filter),
+                    Err(err) => {
+                        log::info!("Chat filter disabled, no blocked word list loaded: {err}");
+                        Box::new(NoFilter)
+                    }
+                };
+            let record_sink: Box<dyn RecordSink> = match FileRecordSink::open("./game_records.log")
+            {
+                Ok(sink) => Box::new(sink),
+                Err(err) => {
+                    log::error!("Game records won't be stored, failed to open file: {err}");
+                    Box::new(NullRecordSink)
+                }
+            };
+            let book = match OpeningBook::load("./opening_book.bin") {
+                Ok(book) => Some(book),
+                Err(err) => {
+                    log::info!("Hints will search from scratch, no opening book loaded: {err}");
+                    None
+                }
+            };
+            let threads = std::thread::available_parallelism().map_or(1, usize::from);
+            let engine = Engine::new(threads, book);
+            let mut server = Connect4Server::new(receiver, chat_filter, record_sink, engine);
             loop {
                 if let Err(err) = server.wait_for_server().await {
                     log::error!("Error waiting for server responses: {}", err);
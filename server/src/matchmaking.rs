@@ -0,0 +1,199 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const WAIT_SAMPLE_SIZE: usize = 16;
+const DEFAULT_ESTIMATED_WAIT: Duration = Duration::from_secs(30);
+
+pub struct QueueEntry {
+    pub client: Uuid,
+    joined_at: Instant,
+}
+
+/// First-in first-out queue of clients looking for a public game.
+///
+/// Clients are always paired in the order they joined, and the wait time of every pairing is
+/// sampled to estimate how long newly queued clients can expect to wait.
+#[derive(Default)]
+pub struct MatchmakingQueue {
+    queue: VecDeque<QueueEntry>,
+    recent_waits: VecDeque<Duration>,
+    positions_changed: bool,
+}
+
+impl MatchmakingQueue {
+    /// Adds a client to the back of the queue, returns false if it was already queued.
+    pub fn enqueue(&mut self, client: Uuid) -> bool {
+        if self.contains(&client) {
+            return false;
+        }
+        self.queue.push_back(QueueEntry {
+            client,
+            joined_at: Instant::now(),
+        });
+        self.positions_changed = true;
+        true
+    }
+
+    /// Removes a client from the queue, returns false if it was not queued.
+    pub fn cancel(&mut self, client: &Uuid) -> bool {
+        match self.queue.iter().position(|entry| entry.client.eq(client)) {
+            Some(idx) => {
+                self.queue.remove(idx);
+                self.positions_changed = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn contains(&self, client: &Uuid) -> bool {
+        self.queue.iter().any(|entry| entry.client.eq(client))
+    }
+
+    /// Pops the two longest waiting clients off the front of the queue.
+    pub fn next_pair(&mut self) -> Option<(QueueEntry, QueueEntry)> {
+        if self.queue.len() < 2 {
+            return None;
+        }
+        let first = self.queue.pop_front()?;
+        let second = self.queue.pop_front()?;
+        self.record_wait(first.joined_at.elapsed());
+        self.record_wait(second.joined_at.elapsed());
+        self.positions_changed = true;
+        Some((first, second))
+    }
+
+    /// Puts a client taken from [`MatchmakingQueue::next_pair`] back at the front of the queue,
+    /// used when the game for its pair could not be created. The client keeps the time it
+    /// originally joined at.
+    pub fn requeue_front(&mut self, entry: QueueEntry) {
+        if self.contains(&entry.client) {
+            return;
+        }
+        self.queue.push_front(entry);
+        self.positions_changed = true;
+    }
+
+    fn record_wait(&mut self, wait: Duration) {
+        if self.recent_waits.len() == WAIT_SAMPLE_SIZE {
+            self.recent_waits.pop_front();
+        }
+        self.recent_waits.push_back(wait);
+    }
+
+    pub fn average_wait(&self) -> Duration {
+        if self.recent_waits.is_empty() {
+            return DEFAULT_ESTIMATED_WAIT;
+        }
+        self.recent_waits.iter().sum::<Duration>() / self.recent_waits.len() as u32
+    }
+
+    /// Estimates the wait for a zero-indexed queue position; every pair ahead of the position has
+    /// to be matched first.
+    pub fn estimated_wait(&self, position: usize) -> Duration {
+        self.average_wait() * (position as u32 / 2 + 1)
+    }
+
+    /// Returns the one-indexed position and estimated wait of every queued client if the queue
+    /// changed since the last call.
+    pub fn take_position_updates(&mut self) -> Vec<(Uuid, usize, Duration)> {
+        if !self.positions_changed {
+            return vec![];
+        }
+        self.positions_changed = false;
+        self.queue
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| {
+                (
+                    entry.client,
+                    idx + 1,
+                    self.estimated_wait(idx).saturating_sub(entry.joined_at.elapsed()),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue_of(count: usize) -> (MatchmakingQueue, Vec<Uuid>) {
+        let mut queue = MatchmakingQueue::default();
+        let clients = (0..count).map(|_| Uuid::new_v4()).collect::<Vec<_>>();
+        for client in &clients {
+            assert!(queue.enqueue(*client));
+        }
+        (queue, clients)
+    }
+
+    fn positions(queue: &mut MatchmakingQueue) -> Vec<(Uuid, usize)> {
+        queue
+            .take_position_updates()
+            .into_iter()
+            .map(|(client, position, _)| (client, position))
+            .collect()
+    }
+
+    #[test]
+    fn pairs_clients_in_the_order_they_joined() {
+        let (mut queue, clients) = queue_of(5);
+        assert!(!queue.enqueue(clients[0]));
+        let (first, second) = queue.next_pair().unwrap();
+        assert_eq!((first.client, second.client), (clients[0], clients[1]));
+        let (first, second) = queue.next_pair().unwrap();
+        assert_eq!((first.client, second.client), (clients[2], clients[3]));
+        assert!(queue.next_pair().is_none());
+        assert!(queue.contains(&clients[4]));
+    }
+
+    #[test]
+    fn cancelled_clients_leave_the_queue() {
+        let (mut queue, clients) = queue_of(3);
+        assert!(queue.cancel(&clients[1]));
+        assert!(!queue.cancel(&clients[1]));
+        assert!(!queue.contains(&clients[1]));
+        let (first, second) = queue.next_pair().unwrap();
+        assert_eq!((first.client, second.client), (clients[0], clients[2]));
+    }
+
+    #[test]
+    fn requeued_clients_keep_their_place_and_wait() {
+        let (mut queue, clients) = queue_of(3);
+        let (first, second) = queue.next_pair().unwrap();
+        let joined_at = first.joined_at;
+        queue.requeue_front(second);
+        queue.requeue_front(first);
+        assert_eq!(queue.queue[0].joined_at, joined_at);
+        let order = queue
+            .queue
+            .iter()
+            .map(|entry| entry.client)
+            .collect::<Vec<_>>();
+        assert_eq!(order, clients);
+    }
+
+    #[test]
+    fn reports_positions_only_after_changes() {
+        let (mut queue, clients) = queue_of(4);
+        assert_eq!(
+            positions(&mut queue),
+            vec![
+                (clients[0], 1),
+                (clients[1], 2),
+                (clients[2], 3),
+                (clients[3], 4)
+            ]
+        );
+        assert!(positions(&mut queue).is_empty());
+        queue.cancel(&clients[0]);
+        assert_eq!(
+            positions(&mut queue),
+            vec![(clients[1], 1), (clients[2], 2), (clients[3], 3)]
+        );
+        queue.next_pair();
+        assert_eq!(positions(&mut queue), vec![(clients[3], 1)]);
+    }
+}
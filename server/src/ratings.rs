@@ -0,0 +1,228 @@
+use connect_4_core::record::{GameRecord, GameResult};
+use std::collections::{HashMap, VecDeque};
+
+const STARTING_RATING: f64 = 1200.0;
+const K_FACTOR: f64 = 32.0;
+const RECENT_FORM_LENGTH: usize = 10;
+const LEADERBOARD_PAGE_SIZE: usize = 10;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Form {
+    Win,
+    Loss,
+    Draw,
+}
+
+impl Form {
+    fn score(self) -> f64 {
+        match self {
+            Form::Win => 1.0,
+            Form::Loss => 0.0,
+            Form::Draw => 0.5,
+        }
+    }
+
+    fn opposite(self) -> Self {
+        match self {
+            Form::Win => Form::Loss,
+            Form::Loss => Form::Win,
+            Form::Draw => Form::Draw,
+        }
+    }
+
+    pub fn letter(self) -> char {
+        match self {
+            Form::Win => 'W',
+            Form::Loss => 'L',
+            Form::Draw => 'D',
+        }
+    }
+}
+
+pub struct PlayerStats {
+    pub username: String,
+    pub rating: f64,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    /// Positive while on a winning streak and negative while on a losing streak.
+    pub current_streak: i32,
+    pub best_streak: i32,
+    pub recent_form: VecDeque<Form>,
+}
+
+impl PlayerStats {
+    fn new(username: &str) -> Self {
+        Self {
+            username: username.to_string(),
+            rating: STARTING_RATING,
+            wins: 0,
+            losses: 0,
+            draws: 0,
+            current_streak: 0,
+            best_streak: 0,
+            recent_form: VecDeque::with_capacity(RECENT_FORM_LENGTH),
+        }
+    }
+
+    fn apply(&mut self, form: Form, rating_change: f64) {
+        self.rating += rating_change;
+        match form {
+            Form::Win => {
+                self.wins += 1;
+                self.current_streak = self.current_streak.max(0) + 1;
+            }
+            Form::Loss => {
+                self.losses += 1;
+                self.current_streak = self.current_streak.min(0) - 1;
+            }
+            Form::Draw => {
+                self.draws += 1;
+                self.current_streak = 0;
+            }
+        }
+        self.best_streak = self.best_streak.max(self.current_streak);
+        if self.recent_form.len() == RECENT_FORM_LENGTH {
+            self.recent_form.pop_front();
+        }
+        self.recent_form.push_back(form);
+    }
+
+    pub fn recent_form_string(&self) -> String {
+        self.recent_form.iter().map(|form| form.letter()).collect()
+    }
+}
+
+/// Elo ratings and results of every player with a finished game, keyed by lowercase username.
+#[derive(Default)]
+pub struct Ratings {
+    players: HashMap<String, PlayerStats>,
+}
+
+impl Ratings {
+    /// Updates both players of a finished game, an abandoned game counts as a loss for the
+    /// player that left. Unrated games and games left before the first move are skipped.
+    pub fn record(&mut self, record: &GameRecord) {
+        if !record.rated || record.moves.is_empty() {
+            return;
+        }
+        let first_form = match &record.result {
+            GameResult::FirstPlayerWin => Form::Win,
+            GameResult::SecondPlayerWin => Form::Loss,
+            GameResult::Draw => Form::Draw,
+            GameResult::Abandoned { by } if by.eq_ignore_ascii_case(&record.first_player) => {
+                Form::Loss
+            }
+            GameResult::Abandoned { .. } => Form::Win,
+        };
+
+        let first_rating = self.stats_mut(&record.first_player).rating;
+        let second_rating = self.stats_mut(&record.second_player).rating;
+        let expected = 1.0 / (1.0 + 10f64.powf((second_rating - first_rating) / 400.0));
+        let change = K_FACTOR * (first_form.score() - expected);
+
+        self.stats_mut(&record.first_player)
+            .apply(first_form, change);
+        self.stats_mut(&record.second_player)
+            .apply(first_form.opposite(), -change);
+    }
+
+    fn stats_mut(&mut self, username: &str) -> &mut PlayerStats {
+        self.players
+            .entry(username.to_lowercase())
+            .or_insert_with(|| PlayerStats::new(username))
+    }
+
+    pub fn get(&self, username: &str) -> Option<&PlayerStats> {
+        self.players.get(&username.to_lowercase())
+    }
+
+    fn ranked(&self) -> Vec<&PlayerStats> {
+        let mut ranked = self.players.values().collect::<Vec<_>>();
+        ranked.sort_by(|a, b| {
+            b.rating
+                .total_cmp(&a.rating)
+                .then(b.wins.cmp(&a.wins))
+                .then(a.username.cmp(&b.username))
+        });
+        ranked
+    }
+
+    /// One-indexed position of the player on the leaderboard.
+    pub fn rank_of(&self, username: &str) -> Option<usize> {
+        self.ranked()
+            .iter()
+            .position(|stats| stats.username.eq_ignore_ascii_case(username))
+            .map(|position| position + 1)
+    }
+
+    /// Returns a page of the leaderboard with the rank of every player on it, and whether there
+    /// are more pages.
+    pub fn leaderboard(&self, page: usize) -> (Vec<(usize, &PlayerStats)>, bool) {
+        let ranked = self.ranked();
+        let start = page * LEADERBOARD_PAGE_SIZE;
+        let entries = ranked
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(LEADERBOARD_PAGE_SIZE)
+            .map(|(position, stats)| (position + 1, *stats))
+            .collect();
+        (entries, ranked.len() > start + LEADERBOARD_PAGE_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use connect_4_core::packets::GameRules;
+    use connect_4_core::record::MoveRecord;
+    use std::time::SystemTime;
+
+    fn record(moves: usize, rated: bool, result: GameResult) -> GameRecord {
+        GameRecord {
+            id: 1,
+            first_player: "Alice".to_string(),
+            second_player: "bob".to_string(),
+            rules: GameRules {
+                challenger_goes_first: true,
+                hints_allowed: false,
+            },
+            started_at: SystemTime::now(),
+            finished_at: SystemTime::now(),
+            moves: (0..moves)
+                .map(|index| MoveRecord {
+                    column: 3,
+                    first_player: index % 2 == 0,
+                    username: String::new(),
+                    played_at: SystemTime::now(),
+                })
+                .collect(),
+            result,
+            rated,
+        }
+    }
+
+    #[test]
+    fn rates_finished_games() {
+        let mut ratings = Ratings::default();
+        ratings.record(&record(7, true, GameResult::FirstPlayerWin));
+        let winner = ratings.get("alice").unwrap();
+        assert_eq!((winner.wins, winner.current_streak), (1, 1));
+        assert_eq!(winner.rating, STARTING_RATING + K_FACTOR / 2.0);
+        assert_eq!(ratings.get("Bob").unwrap().recent_form_string(), "L");
+        assert_eq!(ratings.rank_of("bob"), Some(2));
+    }
+
+    #[test]
+    fn skips_unrated_and_unplayed_games() {
+        let mut ratings = Ratings::default();
+        ratings.record(&record(7, false, GameResult::FirstPlayerWin));
+        let abandoned = GameResult::Abandoned {
+            by: "bob".to_string(),
+        };
+        ratings.record(&record(0, true, abandoned));
+        assert!(ratings.get("alice").is_none());
+        assert!(ratings.leaderboard(0).0.is_empty());
+    }
+}
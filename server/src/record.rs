@@ -0,0 +1,101 @@
+use connect_4_core::record::GameRecord;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+const ARCHIVE_CAPACITY: usize = 10_000;
+const HISTORY_PAGE_SIZE: usize = 10;
+
+/// The most recent finished games, kept in memory so players can look them up from the lobby.
+#[derive(Default)]
+pub struct GameArchive {
+    records: VecDeque<GameRecord>,
+}
+
+impl GameArchive {
+    pub fn push(&mut self, record: GameRecord) {
+        if self.records.len() >= ARCHIVE_CAPACITY {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    pub fn get(&self, id: i32) -> Option<&GameRecord> {
+        self.records.iter().rev().find(|record| record.id == id)
+    }
+
+    /// Returns a page of the player's games, newest first, and whether there are older ones.
+    pub fn history(&self, username: &str, page: usize) -> (Vec<&GameRecord>, bool) {
+        let mut games = self
+            .records
+            .iter()
+            .rev()
+            .filter(|record| record.involves(username))
+            .skip(page * HISTORY_PAGE_SIZE);
+        let history = games.by_ref().take(HISTORY_PAGE_SIZE).collect();
+        (history, games.next().is_some())
+    }
+}
+
+/// Destination for finished game records, a database backed store plugs in by implementing this.
+pub trait RecordSink {
+    fn store(&mut self, record: &GameRecord) -> anyhow::Result<()>;
+
+    /// Reads back every record stored so far, oldest first, so the server can pick up where it
+    /// left off after a restart.
+    fn load(&mut self) -> anyhow::Result<Vec<GameRecord>> {
+        Ok(vec![])
+    }
+}
+
+/// Discards every record.
+pub struct NullRecordSink;
+
+impl RecordSink for NullRecordSink {
+    fn store(&mut self, _: &GameRecord) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Appends every record as a line to a file.
+pub struct FileRecordSink {
+    path: PathBuf,
+    file: File,
+}
+
+impl FileRecordSink {
+    pub fn open<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            file: OpenOptions::new().create(true).append(true).open(path)?,
+        })
+    }
+}
+
+impl RecordSink for FileRecordSink {
+    fn store(&mut self, record: &GameRecord) -> anyhow::Result<()> {
+        writeln!(self.file, "{record}")?;
+        Ok(())
+    }
+
+    /// Skips lines that don't parse, a partly written last line shouldn't lose every other game.
+    fn load(&mut self) -> anyhow::Result<Vec<GameRecord>> {
+        let mut records = vec![];
+        for (index, line) in BufReader::new(File::open(&self.path)?).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match line.parse() {
+                Ok(record) => records.push(record),
+                Err(err) => log::warn!(
+                    "Skipping line {} of {}: {err}",
+                    index + 1,
+                    self.path.display()
+                ),
+            }
+        }
+        Ok(records)
+    }
+}
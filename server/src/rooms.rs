@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const ROOM_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const ROOM_CODE_LENGTH: usize = 6;
+const ROOM_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+struct PrivateRoom {
+    host: Uuid,
+    created_at: Instant,
+}
+
+/// Private rooms waiting for a second player, keyed by their invite code.
+#[derive(Default)]
+pub struct PrivateRooms {
+    rooms: HashMap<String, PrivateRoom>,
+}
+
+impl PrivateRooms {
+    /// Opens a room for the host and returns its invite code, any room the host already had open
+    /// is closed.
+    pub fn create(&mut self, host: Uuid) -> String {
+        self.close_hosted_by(&host);
+        let code = loop {
+            let code = generate_room_code();
+            if !self.rooms.contains_key(&code) {
+                break code;
+            }
+        };
+        self.rooms.insert(
+            code.clone(),
+            PrivateRoom {
+                host,
+                created_at: Instant::now(),
+            },
+        );
+        code
+    }
+
+    /// Closes the room with the given code and returns its host, codes are case-insensitive.
+    pub fn join(&mut self, code: &str) -> Option<Uuid> {
+        self.rooms
+            .remove(&code.trim().to_uppercase())
+            .map(|room| room.host)
+    }
+
+    /// Opens a room taken by [`PrivateRooms::join`] again under the same code, for a join that
+    /// fell through. The host gets the full timeout again.
+    pub fn reopen(&mut self, code: &str, host: Uuid) {
+        self.rooms.insert(
+            code.trim().to_uppercase(),
+            PrivateRoom {
+                host,
+                created_at: Instant::now(),
+            },
+        );
+    }
+
+    pub fn close_hosted_by(&mut self, host: &Uuid) -> bool {
+        let before = self.rooms.len();
+        self.rooms.retain(|_, room| !room.host.eq(host));
+        before != self.rooms.len()
+    }
+
+    /// Closes every room older than the room timeout and returns the hosts of those rooms.
+    pub fn take_expired(&mut self) -> Vec<Uuid> {
+        let mut expired = vec![];
+        self.rooms.retain(|_, room| {
+            if room.created_at.elapsed() >= ROOM_TIMEOUT {
+                expired.push(room.host);
+                false
+            } else {
+                true
+            }
+        });
+        expired
+    }
+}
+
+fn generate_room_code() -> String {
+    Uuid::new_v4()
+        .as_bytes()
+        .iter()
+        .take(ROOM_CODE_LENGTH)
+        .map(|byte| ROOM_CODE_ALPHABET[*byte as usize % ROOM_CODE_ALPHABET.len()] as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reopened_rooms_can_be_joined_again() {
+        let mut rooms = PrivateRooms::default();
+        let host = Uuid::new_v4();
+        let code = rooms.create(host);
+        assert_eq!(rooms.join(&code.to_lowercase()), Some(host));
+        assert_eq!(rooms.join(&code), None);
+        rooms.reopen(&format!(" {} ", code.to_lowercase()), host);
+        assert_eq!(rooms.join(&code), Some(host));
+    }
+}
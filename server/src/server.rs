@@ -1,6 +1,15 @@
+use crate::challenges::PendingChallenges;
+use crate::chat::{prepare_chat_message, ChatFilter, ChatRateLimiter};
 use crate::client::ClientState;
+use crate::engine::{Answer, Engine};
+use crate::matchmaking::MatchmakingQueue;
+use crate::ratings::Ratings;
+use crate::record::{GameArchive, RecordSink};
+use crate::rooms::PrivateRooms;
+use connect_4_core::board::{Board, Outcome, Player, HEIGHT, WIDTH};
 use connect_4_core::encode;
 use connect_4_core::packets::*;
+use connect_4_core::record::{unix_seconds, GameRecord, GameResult, MoveRecord};
 use pin_project_lite::pin_project;
 use std::collections::HashMap;
 use std::future::Future;
@@ -18,15 +27,61 @@ pub enum ClientMessage {
     RequestUsername {
         username: String,
         transaction_id: i32,
+        bot: bool,
     },
     KeepAlive,
     AcquireLobby,
     LookForGame,
+    CancelSearch,
+    CreateRoom,
+    JoinRoom {
+        code: String,
+    },
+    CloseRoom,
+    ListPlayers,
+    Challenge {
+        username: String,
+        rules: GameRules,
+    },
+    AcceptChallenge {
+        username: String,
+    },
+    DeclineChallenge {
+        username: String,
+    },
+    LobbyChat {
+        message: String,
+    },
+    GameChat {
+        message: String,
+    },
+    ListGames,
+    Spectate {
+        game_id: i32,
+    },
+    AcquireSpectate,
+    RequestHistory {
+        username: String,
+        page: i32,
+    },
+    RequestGameRecord {
+        game_id: i32,
+    },
+    RequestLeaderboard {
+        page: i32,
+    },
+    RequestProfile {
+        username: String,
+    },
+    RequestAnalysis {
+        game_id: i32,
+    },
     AcquireGame,
     PlacePiece {
         column: u8,
         transaction_id: i32,
     },
+    RequestHint,
     SocketDie,
 }
 
@@ -45,116 +100,83 @@ impl ClientAdd {
 }
 
 pub struct Game {
+    id: i32,
+    usernames: [String; 2],
+    rules: GameRules,
+    rated: bool,
+    started_at: SystemTime,
+    moves: Vec<MoveRecord>,
+    spectators: Vec<Uuid>,
     client_a: Uuid,
     client_a_acquire: bool,
     client_b: Uuid,
     client_b_acquire: bool,
-    turn: u8,
-    connect_4_board: [[u8; 6]; 7],
+    board: Board,
 }
 
 pub enum PlaceResult {
     Success,
     Win,
+    Draw,
     Failure,
 }
 
 impl Game {
-    pub fn insert_piece(&mut self, v: u8, column: u8) -> PlaceResult {
-        if column > 7 {
-            return PlaceResult::Failure;
-        }
-        if self.turn != v {
-            return PlaceResult::Failure;
-        }
-
-        let x = column as usize;
-        let mut y = usize::MAX;
-        for (idx, b) in self.connect_4_board[x].iter_mut().enumerate() {
-            if *b == 0 {
-                *b = v;
-                y = idx;
-                break;
-            }
-        }
-        let y = y;
-        if y == usize::MAX {
-            return PlaceResult::Failure;
-        }
-
-        macro_rules! check_horizontal {
-            ($x:ident, $y:ident, $v:ident, $self_ref:ident, $operation:tt) => {
-                if y >= 3 {
-                    let mut win = true;
-                    for xa in 1..4 {
-                        for ya in 1..4 {
-                            if self.connect_4_board[$x $operation xa][$y - ya] != $v {
-                                win = false;
-                                break;
-                            }
-                        }
-                    }
-                    if win {
-                        return PlaceResult::Win;
-                    }
-                }
-                if y <= 2 {
-                    let mut win = true;
-                    for xa in 1..4 {
-                        for ya in 1..4 {
-                            if self.connect_4_board[$x $operation xa][$y + ya] != $v {
-                                win = false;
-                                break;
-                            }
-                        }
-                    }
-                    if win {
-                        return PlaceResult::Win;
-                    }
-                }
-            }
-        }
+    pub fn move_count(&self) -> usize {
+        self.board.move_count()
+    }
 
-        if x >= 3 {
-            let values_to_win = &self.connect_4_board[(x - 3)..x];
-            if values_to_win[0][y] == v && values_to_win[1][y] == v && values_to_win[2][y] == v {
-                return PlaceResult::Win;
-            }
-            check_horizontal!(x, y, v, self, -);
-        }
-        if x <= 3 {
-            let values_to_win = &self.connect_4_board[(x + 1)..=(x + 3)];
-            if values_to_win[0][y] == v && values_to_win[1][y] == v && values_to_win[2][y] == v {
-                return PlaceResult::Win;
-            }
-            check_horizontal!(x, y, v, self, +);
+    pub fn insert_piece(&mut self, v: u8, column: u8) -> PlaceResult {
+        let result = self.drop_piece(v, column);
+        if !matches!(result, PlaceResult::Failure) {
+            self.moves.push(MoveRecord {
+                column,
+                first_player: v == 1,
+                username: self.usernames[v as usize - 1].clone(),
+                played_at: SystemTime::now(),
+            });
         }
+        result
+    }
 
-        macro_rules! check_up_down {
-            ($y:ident, $v:ident, $operation:tt, $board:expr) => {
-                let inner_board = $board;
-                let mut win = true;
-                for ya in 1..4 {
-                    if inner_board[$y $operation ya] != $v {
-                        win = false;
-                        break;
-                    }
-                }
-                if win {
-                    return PlaceResult::Win;
-                }
-            };
+    /// Moves the history of the game out into a record with the given result.
+    pub fn take_record(&mut self, result: GameResult) -> GameRecord {
+        let [first_player, second_player] = self.usernames.clone();
+        GameRecord {
+            id: self.id,
+            first_player,
+            second_player,
+            rules: self.rules.copied(),
+            started_at: self.started_at,
+            finished_at: SystemTime::now(),
+            moves: std::mem::take(&mut self.moves),
+            result,
+            rated: self.rated,
         }
+    }
 
-        if y >= 3 {
-            check_up_down!(y, v, -, self.connect_4_board[x]);
+    fn drop_piece(&mut self, v: u8, column: u8) -> PlaceResult {
+        let player = if v == 1 {
+            Player::First
+        } else {
+            Player::Second
+        };
+        if self.board.next_player() != player || self.board.drop_piece(column).is_err() {
+            return PlaceResult::Failure;
         }
-        if y <= 2 {
-            check_up_down!(y, v, +, self.connect_4_board[x]);
+        match self.board.outcome() {
+            Some(Outcome::Win(_)) => PlaceResult::Win,
+            Some(Outcome::Draw) => PlaceResult::Draw,
+            None => PlaceResult::Success,
         }
+    }
+}
 
-        self.turn = v ^ 3;
-        PlaceResult::Success
+/// Rules for games that weren't set up through a challenge.
+fn default_rules() -> GameRules {
+    GameRules {
+        challenger_goes_first: true,
+        hints_allowed: false,
     }
 }
 
@@ -163,24 +185,76 @@ pub struct ServerClient {
     state: ClientState,
     write: OwnedWriteHalf,
     game: Option<Arc<RwLock<Game>>>,
+    spectating: Option<i32>,
     in_game_since: Option<SystemTime>,
     username: Option<String>,
+    bot: bool,
     client_receiver: UnboundedReceiver<ClientMessage>,
     queued_message: Option<ClientMessage>,
+    chat_limiter: ChatRateLimiter,
+    hint_pending: bool,
+    analysis_pending: bool,
 }
 
 pub struct Connect4Server {
     acquired_names: HashMap<String, Uuid>,
     clients: HashMap<Uuid, ServerClient>,
     client_receiver: UnboundedReceiver<ClientAdd>,
+    matchmaking: MatchmakingQueue,
+    rooms: PrivateRooms,
+    challenges: PendingChallenges,
+    chat_filter: Box<dyn ChatFilter>,
+    record_sink: Box<dyn RecordSink>,
+    archive: GameArchive,
+    ratings: Ratings,
+    games: HashMap<i32, Arc<RwLock<Game>>>,
+    next_game_id: i32,
+    engine: Engine,
+    answers: Vec<Answer>,
 }
 
 impl Connect4Server {
-    pub fn new(receiver: UnboundedReceiver<ClientAdd>) -> Self {
-        Self {
+    pub fn new(
+        receiver: UnboundedReceiver<ClientAdd>,
+        chat_filter: Box<dyn ChatFilter>,
+        record_sink: Box<dyn RecordSink>,
+        engine: Engine,
+    ) -> Self {
+        let mut server = Self {
             acquired_names: Default::default(),
             clients: Default::default(),
             client_receiver: receiver,
+            matchmaking: Default::default(),
+            rooms: Default::default(),
+            challenges: Default::default(),
+            chat_filter,
+            record_sink,
+            archive: Default::default(),
+            ratings: Default::default(),
+            games: Default::default(),
+            next_game_id: 1,
+            engine,
+            answers: vec![],
+        };
+        server.load_records();
+        server
+    }
+
+    /// Fills the archive and the ratings with the games the record sink already holds and
+    /// continues the game ids after the highest stored one.
+    fn load_records(&mut self) {
+        let records = match self.record_sink.load() {
+            Ok(records) => records,
+            Err(err) => {
+                log::error!("Failed to load stored game records: {}", err);
+                return;
+            }
+        };
+        log::info!("Loaded {} stored game records.", records.len());
+        for record in records {
+            self.next_game_id = self.next_game_id.max(record.id + 1);
+            self.ratings.record(&record);
+            self.archive.push(record);
         }
     }
 
@@ -189,11 +263,16 @@ impl Connect4Server {
             acquired_names,
             clients,
             client_receiver,
+            engine,
+            answers,
+            ..
         } = self;
         Connect4ServerRead {
             acquired_names,
             clients,
             client_receiver,
+            engine,
+            answers,
         }
     }
 
@@ -201,7 +280,19 @@ impl Connect4Server {
         let mut clients_to_remove = vec![];
         let mut client_game_ready = vec![];
         let mut lost_clients = vec![];
+        let mut drawn_clients = vec![];
         let mut piece_informants = vec![];
+        let mut room_games = vec![];
+        let mut player_list_requests = vec![];
+        let mut challenge_notices = vec![];
+        let mut challenge_games = vec![];
+        let mut challenge_declines = vec![];
+        let mut lobby_chat = vec![];
+        let mut game_chat = vec![];
+        let mut game_list_requests = vec![];
+        let mut spectator_moves = vec![];
+        let mut spectator_counts_changed = vec![];
+        let mut finished_games = vec![];
 
         for (id, client) in &mut self.clients {
             if let Some(message) = client.queued_message.take() {
@@ -209,6 +300,7 @@ impl Connect4Server {
                     ClientMessage::RequestUsername {
                         username,
                         transaction_id,
+                        bot,
                     } => {
                         if !username.chars().all(char::is_alphanumeric) {
                             encode!(
@@ -232,6 +324,7 @@ impl Connect4Server {
                             self.acquired_names
                                 .insert(username.clone().to_lowercase(), *id);
                             client.username = Some(username);
+                            client.bot = bot;
                             encode!(
                                 client.write,
                                 ClientboundLoginPacket,
@@ -252,6 +345,7 @@ impl Connect4Server {
                         }
                         ClientState::Lobby
                         | ClientState::LookingForGame
+                        | ClientState::HostingRoom
                         | ClientState::WaitingForGame => {
                             encode!(
                                 client.write,
@@ -266,15 +360,244 @@ impl Connect4Server {
                                 ClientboundGamePacket::KeepAlive
                             );
                         }
+                        ClientState::Spectating => {
+                            encode!(
+                                client.write,
+                                ClientboundSpectatorPacket,
+                                ClientboundSpectatorPacket::KeepAlive
+                            );
+                        }
                     },
                     ClientMessage::AcquireLobby => {
                         if matches!(client.username, None) {
                             clients_to_remove.push(*id);
                             continue;
                         }
+                        if let Some(game_id) = client.spectating.take() {
+                            if let Some(game) = self.games.get(&game_id) {
+                                game.write()
+                                    .await
+                                    .spectators
+                                    .retain(|spectator| !spectator.eq(id));
+                                spectator_counts_changed.push(game_id);
+                            }
+                        }
+                        // the spectator keeps decoding spectator packets until this arrives
+                        if matches!(client.state, ClientState::Spectating) {
+                            encode!(
+                                client.write,
+                                ClientboundSpectatorPacket,
+                                ClientboundSpectatorPacket::ReturnedToLobby
+                            );
+                        }
                         client.state = ClientState::Lobby
                     }
-                    ClientMessage::LookForGame => client.state = ClientState::LookingForGame,
+                    ClientMessage::ListGames => game_list_requests.push(*id),
+                    ClientMessage::RequestHistory { username, page } => {
+                        let (records, has_more) =
+                            self.archive.history(&username, page.max(0) as usize);
+                        let games = records
+                            .into_iter()
+                            .map(|record| record.history_entry(&username))
+                            .collect();
+                        encode!(
+                            client.write,
+                            ClientboundLobbyPacket,
+                            ClientboundLobbyPacket::HistoryPage {
+                                username,
+                                page,
+                                has_more,
+                                games
+                            }
+                        );
+                    }
+                    ClientMessage::RequestGameRecord { game_id } => {
+                        let packet = match self.archive.get(game_id) {
+                            Some(record) => ClientboundLobbyPacket::GameRecord {
+                                game_id,
+                                first_player: record.first_player.clone(),
+                                second_player: record.second_player.clone(),
+                                winner: record.winner().unwrap_or_default().to_string(),
+                                abandoned: matches!(record.result, GameResult::Abandoned { .. }),
+                                moves: record.moves.iter().map(|played| played.column).collect(),
+                                finished_at: unix_seconds(record.finished_at),
+                            },
+                            None => ClientboundLobbyPacket::GameRecordNotFound { game_id },
+                        };
+                        encode!(client.write, ClientboundLobbyPacket, packet);
+                    }
+                    ClientMessage::RequestLeaderboard { page } => {
+                        let (ranked, has_more) = self.ratings.leaderboard(page.max(0) as usize);
+                        let entries = ranked
+                            .into_iter()
+                            .map(|(rank, stats)| LeaderboardEntry {
+                                rank: rank as i32,
+                                username: stats.username.clone(),
+                                rating: stats.rating.round() as i32,
+                                wins: stats.wins as i32,
+                                losses: stats.losses as i32,
+                                draws: stats.draws as i32,
+                            })
+                            .collect();
+                        encode!(
+                            client.write,
+                            ClientboundLobbyPacket,
+                            ClientboundLobbyPacket::Leaderboard {
+                                page,
+                                has_more,
+                                entries
+                            }
+                        );
+                    }
+                    ClientMessage::RequestProfile { username } => {
+                        let packet =
+                            match (self.ratings.get(&username), self.ratings.rank_of(&username)) {
+                                (Some(stats), Some(rank)) => ClientboundLobbyPacket::Profile {
+                                    username: stats.username.clone(),
+                                    rank: rank as i32,
+                                    rating: stats.rating.round() as i32,
+                                    wins: stats.wins as i32,
+                                    losses: stats.losses as i32,
+                                    draws: stats.draws as i32,
+                                    current_streak: stats.current_streak,
+                                    best_streak: stats.best_streak,
+                                    recent_form: stats.recent_form_string(),
+                                },
+                                _ => ClientboundLobbyPacket::ProfileNotFound { username },
+                            };
+                        encode!(client.write, ClientboundLobbyPacket, packet);
+                    }
+                    ClientMessage::RequestAnalysis { game_id } => {
+                        if client.analysis_pending {
+                            continue;
+                        }
+                        match self.archive.get(game_id) {
+                            Some(record) => {
+                                client.analysis_pending = true;
+                                self.engine.request_analysis(
+                                    *id,
+                                    game_id,
+                                    record.moves.iter().map(|played| played.column).collect(),
+                                );
+                            }
+                            None => {
+                                encode!(
+                                    client.write,
+                                    ClientboundLobbyPacket,
+                                    ClientboundLobbyPacket::GameAnalysisNotFound { game_id }
+                                );
+                            }
+                        }
+                    }
+                    ClientMessage::Spectate { game_id } => {
+                        if matches!(client.state, ClientState::Lobby)
+                            && self.games.contains_key(&game_id)
+                        {
+                            client.spectating = Some(game_id);
+                            client.state = ClientState::WaitingForGame;
+                            encode!(
+                                client.write,
+                                ClientboundLobbyPacket,
+                                ClientboundLobbyPacket::SpectateStarted { game_id }
+                            );
+                        } else {
+                            encode!(
+                                client.write,
+                                ClientboundLobbyPacket,
+                                ClientboundLobbyPacket::SpectateFailed { game_id }
+                            );
+                        }
+                    }
+                    ClientMessage::AcquireSpectate => {
+                        client.state = ClientState::Spectating;
+                        let game = client
+                            .spectating
+                            .and_then(|game_id| self.games.get(&game_id));
+                        let Some(game) = game else {
+                            client.spectating = None;
+                            encode!(
+                                client.write,
+                                ClientboundSpectatorPacket,
+                                ClientboundSpectatorPacket::GameOver {
+                                    winner: String::new(),
+                                    abandoned: true
+                                }
+                            );
+                            continue;
+                        };
+                        let mut write_game = game.write().await;
+                        write_game.spectators.push(*id);
+                        spectator_counts_changed.push(write_game.id);
+                        let [first_player, second_player] = write_game.usernames.clone();
+                        let snapshot = ClientboundSpectatorPacket::BoardSnapshot {
+                            first_player,
+                            second_player,
+                            board: (0..WIDTH)
+                                .flat_map(|x| (0..HEIGHT).map(move |y| (x, y)))
+                                .map(|(x, y)| match write_game.board.get(x, y) {
+                                    Some(Player::First) => 1,
+                                    Some(Player::Second) => 2,
+                                    None => 0,
+                                })
+                                .collect(),
+                            first_player_to_move: write_game.board.next_player() == Player::First,
+                        };
+                        drop(write_game);
+                        encode!(client.write, ClientboundSpectatorPacket, snapshot);
+                    }
+                    ClientMessage::LookForGame => {
+                        if matches!(client.state, ClientState::Lobby)
+                            && self.matchmaking.enqueue(*id)
+                        {
+                            client.state = ClientState::LookingForGame;
+                        }
+                    }
+                    ClientMessage::CancelSearch => {
+                        if self.matchmaking.cancel(id) {
+                            client.state = ClientState::Lobby;
+                            encode!(
+                                client.write,
+                                ClientboundLobbyPacket,
+                                ClientboundLobbyPacket::SearchCancelled
+                            );
+                        }
+                    }
+                    ClientMessage::CreateRoom => {
+                        if matches!(client.state, ClientState::Lobby) {
+                            let code = self.rooms.create(*id);
+                            client.state = ClientState::HostingRoom;
+                            encode!(
+                                client.write,
+                                ClientboundLobbyPacket,
+                                ClientboundLobbyPacket::RoomCreated { code }
+                            );
+                        }
+                    }
+                    ClientMessage::JoinRoom { code } => {
+                        if !matches!(client.state, ClientState::Lobby) {
+                            continue;
+                        }
+                        match self.rooms.join(&code) {
+                            Some(host) if !host.eq(id) => room_games.push((host, *id, code)),
+                            _ => {
+                                encode!(
+                                    client.write,
+                                    ClientboundLobbyPacket,
+                                    ClientboundLobbyPacket::RoomJoinFailed { code }
+                                );
+                            }
+                        }
+                    }
+                    ClientMessage::CloseRoom => {
+                        if self.rooms.close_hosted_by(id) {
+                            client.state = ClientState::Lobby;
+                            encode!(
+                                client.write,
+                                ClientboundLobbyPacket,
+                                ClientboundLobbyPacket::RoomClosed { expired: false }
+                            );
+                        }
+                    }
                     ClientMessage::AcquireGame => {
                         client.state = ClientState::Game;
                         if let Some(game) = client.game.as_ref() {
@@ -282,14 +605,20 @@ impl Connect4Server {
                             if write_game.client_a.eq(id) {
                                 write_game.client_a_acquire = true;
                                 if write_game.client_b_acquire {
-                                    client_game_ready
-                                        .push((write_game.client_a, write_game.client_b))
+                                    client_game_ready.push((
+                                        write_game.client_a,
+                                        write_game.client_b,
+                                        write_game.rules.hints_allowed,
+                                    ))
                                 }
                             } else if write_game.client_b.eq(id) {
                                 write_game.client_b_acquire = true;
                                 if write_game.client_a_acquire {
-                                    client_game_ready
-                                        .push((write_game.client_a, write_game.client_b))
+                                    client_game_ready.push((
+                                        write_game.client_a,
+                                        write_game.client_b,
+                                        write_game.rules.hints_allowed,
+                                    ))
                                 }
                             } else {
                                 clients_to_remove.push(*id);
@@ -299,7 +628,103 @@ impl Connect4Server {
                             clients_to_remove.push(*id);
                         }
                     }
+                    ClientMessage::ListPlayers => player_list_requests.push(*id),
+                    ClientMessage::Challenge { username, rules } => {
+                        if !matches!(client.state, ClientState::Lobby) {
+                            continue;
+                        }
+                        match self.acquired_names.get(&username.to_lowercase()) {
+                            Some(target) if !target.eq(id) => {
+                                self.challenges.issue(*id, *target, rules.copied());
+                                challenge_notices.push((*id, *target, rules));
+                            }
+                            _ => {
+                                encode!(
+                                    client.write,
+                                    ClientboundLobbyPacket,
+                                    ClientboundLobbyPacket::ChallengeFailed { username }
+                                );
+                            }
+                        }
+                    }
+                    ClientMessage::AcceptChallenge { username } => {
+                        let challenge = self.acquired_names.get(&username.to_lowercase()).and_then(
+                            |challenger| {
+                                self.challenges
+                                    .take(*challenger, *id)
+                                    .map(|rules| (*challenger, rules))
+                            },
+                        );
+                        match challenge {
+                            Some((challenger, rules))
+                                if matches!(client.state, ClientState::Lobby) =>
+                            {
+                                challenge_games.push((challenger, *id, rules));
+                            }
+                            _ => {
+                                encode!(
+                                    client.write,
+                                    ClientboundLobbyPacket,
+                                    ClientboundLobbyPacket::ChallengeFailed { username }
+                                );
+                            }
+                        }
+                    }
+                    ClientMessage::DeclineChallenge { username } => {
+                        if let Some(challenger) = self.acquired_names.get(&username.to_lowercase())
+                        {
+                            if self.challenges.take(*challenger, *id).is_some() {
+                                challenge_declines.push((*challenger, *id));
+                            }
+                        }
+                    }
+                    ClientMessage::LobbyChat { message } => {
+                        match prepare_chat_message(
+                            &message,
+                            &mut client.chat_limiter,
+                            self.chat_filter.as_ref(),
+                        ) {
+                            Ok(message) => lobby_chat
+                                .push((client.username.clone().unwrap_or_default(), message)),
+                            Err(reason) => {
+                                encode!(
+                                    client.write,
+                                    ClientboundLobbyPacket,
+                                    ClientboundLobbyPacket::ChatRejected { reason }
+                                );
+                            }
+                        }
+                    }
+                    ClientMessage::GameChat { message } => {
+                        let Some(game) = client.game.as_ref() else {
+                            continue;
+                        };
+                        match prepare_chat_message(
+                            &message,
+                            &mut client.chat_limiter,
+                            self.chat_filter.as_ref(),
+                        ) {
+                            Ok(message) => {
+                                let read_game = game.read().await;
+                                game_chat.push((
+                                    [read_game.client_a, read_game.client_b],
+                                    client.username.clone().unwrap_or_default(),
+                                    message,
+                                ));
+                                drop(read_game);
+                            }
+                            Err(reason) => {
+                                encode!(
+                                    client.write,
+                                    ClientboundGamePacket,
+                                    ClientboundGamePacket::ChatRejected { reason }
+                                );
+                            }
+                        }
+                    }
                     ClientMessage::SocketDie => {
+                        self.matchmaking.cancel(id);
+                        self.rooms.close_hosted_by(id);
                         clients_to_remove.push(*id);
                     }
                     ClientMessage::PlacePiece {
@@ -317,43 +742,274 @@ impl Connect4Server {
                                 drop(write);
                                 continue;
                             };
-                            let win = match write.insert_piece(v, column) {
-                                PlaceResult::Success => {
+                            let place_result = write.insert_piece(v, column);
+                            if matches!(place_result, PlaceResult::Failure) {
+                                continue;
+                            }
+                            encode!(
+                                client.write,
+                                ClientboundGamePacket,
+                                ClientboundGamePacket::PlacePieceAck { transaction_id }
+                            );
+                            piece_informants.push((other_id, column));
+                            spectator_moves.push((write.spectators.clone(), column, v == 1));
+                            let game_id = write.id;
+                            drop(write);
+
+                            match place_result {
+                                PlaceResult::Win => {
+                                    finished_games.push((
+                                        game_id,
+                                        if v == 1 {
+                                            GameResult::FirstPlayerWin
+                                        } else {
+                                            GameResult::SecondPlayerWin
+                                        },
+                                    ));
+                                    lost_clients.push(other_id);
                                     encode!(
                                         client.write,
                                         ClientboundGamePacket,
-                                        ClientboundGamePacket::PlacePieceAck { transaction_id }
+                                        ClientboundGamePacket::PlayerWin { me: true }
                                     );
-                                    piece_informants.push((other_id, column));
-                                    false
                                 }
-                                PlaceResult::Win => {
+                                PlaceResult::Draw => {
+                                    finished_games.push((game_id, GameResult::Draw));
+                                    drawn_clients.push(other_id);
                                     encode!(
                                         client.write,
                                         ClientboundGamePacket,
-                                        ClientboundGamePacket::PlacePieceAck { transaction_id }
+                                        ClientboundGamePacket::Draw
                                     );
-                                    piece_informants.push((other_id, column));
-                                    true
                                 }
-                                _ => false,
-                            };
-                            drop(write);
+                                _ => {}
+                            }
+                        }
+                    }
+                    ClientMessage::RequestHint => {
+                        let Some(game) = client.game.as_ref() else {
+                            continue;
+                        };
+                        if client.hint_pending {
+                            continue;
+                        }
+                        let read_game = game.read().await;
+                        let player = if id.eq(&read_game.client_a) {
+                            Player::First
+                        } else {
+                            Player::Second
+                        };
+                        let board = read_game.board;
+                        let allowed = read_game.rules.hints_allowed
+                            && matches!(client.state, ClientState::Game)
+                            && board.outcome().is_none()
+                            && board.next_player() == player;
+                        drop(read_game);
+                        if allowed {
+                            client.hint_pending = true;
+                            self.engine.request_hint(*id, board);
+                        } else {
+                            encode!(
+                                client.write,
+                                ClientboundGamePacket,
+                                ClientboundGamePacket::HintUnavailable
+                            );
+                        }
+                    }
+                }
+            }
+        }
 
-                            if win {
-                                lost_clients.push(other_id);
-                                encode!(
-                                    client.write,
-                                    ClientboundGamePacket,
-                                    ClientboundGamePacket::PlayerWin { me: true }
-                                );
+        for answer in std::mem::take(&mut self.answers) {
+            match answer {
+                Answer::Hint(hint) => {
+                    let Some(client) = self.clients.get_mut(&hint.client) else {
+                        continue;
+                    };
+                    client.hint_pending = false;
+                    let board = match client.game.as_ref() {
+                        Some(game) => Some(game.read().await.board),
+                        None => None,
+                    };
+                    // a hint for a position that's gone isn't worth sending
+                    if matches!(client.state, ClientState::Game) && board == Some(hint.board) {
+                        encode!(
+                            client.write,
+                            ClientboundGamePacket,
+                            ClientboundGamePacket::Hint {
+                                column: hint.column,
+                                score: hint.score,
+                                exact: hint.exact
                             }
+                        );
+                    }
+                }
+                Answer::Analysis(analysis) => {
+                    let Some(client) = self.clients.get_mut(&analysis.client) else {
+                        continue;
+                    };
+                    client.analysis_pending = false;
+                    // only the lobby reads lobby packets
+                    if !matches!(
+                        client.state,
+                        ClientState::Lobby | ClientState::LookingForGame | ClientState::HostingRoom
+                    ) {
+                        continue;
+                    }
+                    let Some(evaluations) = analysis.evaluations else {
+                        encode!(
+                            client.write,
+                            ClientboundLobbyPacket,
+                            ClientboundLobbyPacket::GameAnalysisNotFound {
+                                game_id: analysis.game_id
+                            }
+                        );
+                        continue;
+                    };
+                    let moves = analysis
+                        .moves
+                        .iter()
+                        .zip(&evaluations)
+                        .map(|(column, evaluation)| match evaluation {
+                            Some(evaluation) => MoveEvaluation {
+                                column: *column,
+                                solved: true,
+                                score: evaluation.score,
+                                best_move: evaluation.best_move,
+                                best_score: evaluation.best_score,
+                            },
+                            None => MoveEvaluation {
+                                column: *column,
+                                solved: false,
+                                score: 0,
+                                best_move: *column,
+                                best_score: 0,
+                            },
+                        })
+                        .collect();
+                    encode!(
+                        client.write,
+                        ClientboundLobbyPacket,
+                        ClientboundLobbyPacket::GameAnalysis {
+                            game_id: analysis.game_id,
+                            moves
+                        }
+                    );
+                }
+            }
+        }
+
+        for (spectators, column, first_player) in spectator_moves {
+            for spectator in spectators {
+                if let Some(client) = self.clients.get_mut(&spectator) {
+                    encode!(
+                        client.write,
+                        ClientboundSpectatorPacket,
+                        ClientboundSpectatorPacket::PiecePlaced {
+                            column,
+                            first_player
+                        }
+                    );
+                }
+            }
+        }
+
+        for (game_id, result) in finished_games {
+            self.end_game(game_id, result).await;
+        }
+
+        for game_id in spectator_counts_changed {
+            if let Some(game) = self.games.get(&game_id) {
+                let read_game = game.read().await;
+                let count = read_game.spectators.len() as i32;
+                for player in [read_game.client_a, read_game.client_b] {
+                    if let Some(client) = self.clients.get_mut(&player) {
+                        if matches!(client.state, ClientState::Game) {
+                            encode!(
+                                client.write,
+                                ClientboundGamePacket,
+                                ClientboundGamePacket::SpectatorCount { count }
+                            );
                         }
                     }
                 }
             }
         }
 
+        if !game_list_requests.is_empty() {
+            let mut games = vec![];
+            for game in self.games.values() {
+                let read_game = game.read().await;
+                let [first_player, second_player] = read_game.usernames.clone();
+                games.push((
+                    read_game.id,
+                    first_player,
+                    second_player,
+                    read_game.move_count() as i32,
+                    read_game.spectators.len() as i32,
+                ));
+            }
+            games.sort_by_key(|(game_id, ..)| *game_id);
+            for id in game_list_requests {
+                if let Some(client) = self.clients.get_mut(&id) {
+                    let games = games
+                        .iter()
+                        .map(
+                            |(game_id, first_player, second_player, moves, spectators)| {
+                                GameSummary {
+                                    game_id: *game_id,
+                                    first_player: first_player.clone(),
+                                    second_player: second_player.clone(),
+                                    moves: *moves,
+                                    spectators: *spectators,
+                                }
+                            },
+                        )
+                        .collect();
+                    encode!(
+                        client.write,
+                        ClientboundLobbyPacket,
+                        ClientboundLobbyPacket::GameList { games }
+                    );
+                }
+            }
+        }
+
+        for (username, message) in lobby_chat {
+            for client in self.clients.values_mut().filter(|client| {
+                matches!(
+                    client.state,
+                    ClientState::Lobby | ClientState::LookingForGame | ClientState::HostingRoom
+                )
+            }) {
+                encode!(
+                    client.write,
+                    ClientboundLobbyPacket,
+                    ClientboundLobbyPacket::Chat {
+                        username: username.clone(),
+                        message: message.clone()
+                    }
+                );
+            }
+        }
+
+        for (recipients, username, message) in game_chat {
+            for recipient in recipients {
+                if let Some(client) = self.clients.get_mut(&recipient) {
+                    if matches!(client.state, ClientState::Game) {
+                        encode!(
+                            client.write,
+                            ClientboundGamePacket,
+                            ClientboundGamePacket::Chat {
+                                username: username.clone(),
+                                message: message.clone()
+                            }
+                        );
+                    }
+                }
+            }
+        }
+
         for (id, column) in piece_informants {
             if let Some(client) = self.clients.get_mut(&id) {
                 encode!(
@@ -374,7 +1030,17 @@ impl Connect4Server {
             }
         }
 
-        for (client_a, client_b) in client_game_ready {
+        for drawn_client in drawn_clients {
+            if let Some(client) = self.clients.get_mut(&drawn_client) {
+                encode!(
+                    client.write,
+                    ClientboundGamePacket,
+                    ClientboundGamePacket::Draw
+                );
+            }
+        }
+
+        for (client_a, client_b, hints_allowed) in client_game_ready {
             let [client_a_mut, client_b_mut] =
                 match self.clients.get_many_mut([&client_a, &client_b]) {
                     None => {
@@ -391,7 +1057,9 @@ impl Connect4Server {
                 ClientboundGamePacket,
                 ClientboundGamePacket::OpponentJoin {
                     username: client_b_mut.username.as_ref().unwrap().clone(),
-                    i_go_first: true
+                    i_go_first: true,
+                    opponent_is_bot: client_b_mut.bot,
+                    hints_allowed
                 }
             );
 
@@ -400,55 +1068,212 @@ impl Connect4Server {
                 ClientboundGamePacket,
                 ClientboundGamePacket::OpponentJoin {
                     username: client_a_mut.username.as_ref().unwrap().clone(),
-                    i_go_first: false
+                    i_go_first: false,
+                    opponent_is_bot: client_a_mut.bot,
+                    hints_allowed
                 }
             );
         }
 
-        let mut clients_looking_for_games = self
+        for removable in clients_to_remove.iter() {
+            self.matchmaking.cancel(removable);
+            self.rooms.close_hosted_by(removable);
+            self.challenges.remove_involving(removable);
+        }
+
+        let online_usernames = self
             .clients
-            .values_mut()
-            .filter(|client| matches!(client.state, ClientState::LookingForGame));
-        while let Ok(chunk) = clients_looking_for_games.next_chunk::<2>() {
-            let new_game = Game {
-                client_a: chunk[0].uuid,
-                client_a_acquire: false,
-                client_b: chunk[1].uuid,
-                client_b_acquire: false,
-                connect_4_board: [[0u8; 6]; 7],
-                turn: 1u8,
+            .values()
+            .filter_map(|client| client.username.clone())
+            .collect::<Vec<_>>();
+        for id in player_list_requests {
+            if let Some(client) = self.clients.get_mut(&id) {
+                let usernames = online_usernames
+                    .iter()
+                    .filter(|username| !client.username.as_ref().eq(&Some(*username)))
+                    .cloned()
+                    .collect();
+                encode!(
+                    client.write,
+                    ClientboundLobbyPacket,
+                    ClientboundLobbyPacket::PlayerList { usernames }
+                );
+            }
+        }
+
+        for (challenger, target, rules) in challenge_notices {
+            let challenger_name = self.username_of(&challenger);
+            let target_name = self.username_of(&target);
+            if self.in_lobby(&target) {
+                let client = self.clients.get_mut(&target).unwrap();
+                encode!(
+                    client.write,
+                    ClientboundLobbyPacket,
+                    ClientboundLobbyPacket::ChallengeReceived {
+                        username: challenger_name,
+                        rules
+                    }
+                );
+            } else {
+                self.challenges.take(challenger, target);
+                if !self.in_lobby(&challenger) {
+                    continue;
+                }
+                if let Some(client) = self.clients.get_mut(&challenger) {
+                    encode!(
+                        client.write,
+                        ClientboundLobbyPacket,
+                        ClientboundLobbyPacket::ChallengeFailed {
+                            username: target_name
+                        }
+                    );
+                }
+            }
+        }
+
+        for (challenger, target) in challenge_declines {
+            // a challenger that went on to another game reads game packets
+            if !self.in_lobby(&challenger) {
+                continue;
+            }
+            let target_name = self.username_of(&target);
+            if let Some(client) = self.clients.get_mut(&challenger) {
+                encode!(
+                    client.write,
+                    ClientboundLobbyPacket,
+                    ClientboundLobbyPacket::ChallengeDeclined {
+                        username: target_name
+                    }
+                );
+            }
+        }
+
+        for (challenger, target, rules) in challenge_games {
+            // either player may have been put into another game earlier this tick
+            let both_in_lobby = self.in_lobby(&challenger) && self.in_lobby(&target);
+            let (client_a, client_b) = if rules.challenger_goes_first {
+                (challenger, target)
+            } else {
+                (target, challenger)
             };
-            let lock_game = Arc::new(RwLock::new(new_game));
-            chunk[0].game = Some(lock_game.clone());
-            chunk[1].game = Some(lock_game);
+            if both_in_lobby && self.start_game(client_a, client_b, rules).await {
+                continue;
+            }
+            if self.in_lobby(&target) {
+                let challenger_name = self.username_of(&challenger);
+                if let Some(client) = self.clients.get_mut(&target) {
+                    encode!(
+                        client.write,
+                        ClientboundLobbyPacket,
+                        ClientboundLobbyPacket::ChallengeFailed {
+                            username: challenger_name
+                        }
+                    );
+                }
+            }
+        }
 
-            chunk[0].in_game_since = Some(SystemTime::now());
-            chunk[1].in_game_since = Some(SystemTime::now());
+        for (host, joiner, code) in room_games {
+            let host_waiting = self
+                .clients
+                .get(&host)
+                .map(|client| matches!(client.state, ClientState::HostingRoom))
+                .unwrap_or(false);
+            // the joiner may have been put into a challenge game earlier this tick
+            let joiner_in_lobby = self.in_lobby(&joiner);
+            if host_waiting
+                && joiner_in_lobby
+                && self.start_game(host, joiner, default_rules()).await
+            {
+                continue;
+            }
+            // joining took the room, the host keeps waiting in it
+            if host_waiting {
+                self.rooms.reopen(&code, host);
+            }
+            // a joiner that's in a game already reads game packets
+            if joiner_in_lobby {
+                if let Some(client) = self.clients.get_mut(&joiner) {
+                    encode!(
+                        client.write,
+                        ClientboundLobbyPacket,
+                        ClientboundLobbyPacket::RoomJoinFailed { code }
+                    );
+                }
+            }
+        }
 
-            chunk[0].state = ClientState::WaitingForGame;
-            chunk[1].state = ClientState::WaitingForGame;
+        for host in self.rooms.take_expired() {
+            if let Some(client) = self.clients.get_mut(&host) {
+                client.state = ClientState::Lobby;
+                encode!(
+                    client.write,
+                    ClientboundLobbyPacket,
+                    ClientboundLobbyPacket::RoomClosed { expired: true }
+                );
+            }
+        }
 
-            encode!(
-                chunk[0].write,
-                ClientboundLobbyPacket,
-                ClientboundLobbyPacket::GameFound
-            );
-            encode!(
-                chunk[1].write,
-                ClientboundLobbyPacket,
-                ClientboundLobbyPacket::GameFound
-            );
+        while let Some((first, second)) = self.matchmaking.next_pair() {
+            if !self
+                .start_game(first.client, second.client, default_rules())
+                .await
+            {
+                for entry in [second, first] {
+                    if self.clients.contains_key(&entry.client) {
+                        self.matchmaking.requeue_front(entry);
+                    }
+                }
+                break;
+            }
+        }
+
+        for (id, position, estimated_wait) in self.matchmaking.take_position_updates() {
+            if let Some(client) = self.clients.get_mut(&id) {
+                encode!(
+                    client.write,
+                    ClientboundLobbyPacket,
+                    ClientboundLobbyPacket::QueueUpdate {
+                        position: position as i32,
+                        estimated_wait_seconds: estimated_wait.as_secs() as i32
+                    }
+                );
+            }
         }
 
         for removable in clients_to_remove.iter() {
             if let Some(ServerClient {
                 username: Some(name),
                 game,
+                spectating,
                 ..
             }) = self.clients.remove(removable)
             {
                 self.acquired_names.remove(&name.to_lowercase());
+                if let Some(game) = spectating.and_then(|game_id| self.games.get(&game_id)) {
+                    let mut write_game = game.write().await;
+                    write_game
+                        .spectators
+                        .retain(|spectator| !spectator.eq(removable));
+                    let count = write_game.spectators.len() as i32;
+                    let players = [write_game.client_a, write_game.client_b];
+                    drop(write_game);
+                    for player in players {
+                        if let Some(client) = self.clients.get_mut(&player) {
+                            if matches!(client.state, ClientState::Game) {
+                                encode!(
+                                    client.write,
+                                    ClientboundGamePacket,
+                                    ClientboundGamePacket::SpectatorCount { count }
+                                );
+                            }
+                        }
+                    }
+                }
                 if let Some(game) = game {
+                    let game_id = game.read().await.id;
+                    self.end_game(game_id, GameResult::Abandoned { by: name.clone() })
+                        .await;
                     let game_read = game.read().await;
                     if !clients_to_remove.contains(&game_read.client_a) {
                         let client = self.clients.get_mut(&game_read.client_a).unwrap();
@@ -476,13 +1301,122 @@ impl Connect4Server {
         }
         Ok(())
     }
+
+    fn in_lobby(&self, id: &Uuid) -> bool {
+        self.clients
+            .get(id)
+            .map(|client| matches!(client.state, ClientState::Lobby))
+            .unwrap_or(false)
+    }
+
+    fn username_of(&self, id: &Uuid) -> String {
+        self.clients
+            .get(id)
+            .and_then(|client| client.username.clone())
+            .unwrap_or_default()
+    }
+
+    async fn start_game(&mut self, client_a: Uuid, client_b: Uuid, rules: GameRules) -> bool {
+        let [client_a_mut, client_b_mut] = match self.clients.get_many_mut([&client_a, &client_b]) {
+            None => return false,
+            Some(x) => x,
+        };
+
+        let game_id = self.next_game_id;
+        self.next_game_id += 1;
+
+        let new_game = Game {
+            id: game_id,
+            usernames: [
+                client_a_mut.username.clone().unwrap_or_default(),
+                client_b_mut.username.clone().unwrap_or_default(),
+            ],
+            // games played with hints can't change a rating
+            rated: !rules.hints_allowed,
+            rules,
+            started_at: SystemTime::now(),
+            moves: vec![],
+            spectators: vec![],
+            client_a: client_a_mut.uuid,
+            client_a_acquire: false,
+            client_b: client_b_mut.uuid,
+            client_b_acquire: false,
+            board: Board::new(),
+        };
+        let lock_game = Arc::new(RwLock::new(new_game));
+        client_a_mut.game = Some(lock_game.clone());
+        client_b_mut.game = Some(lock_game.clone());
+
+        client_a_mut.in_game_since = Some(SystemTime::now());
+        client_b_mut.in_game_since = Some(SystemTime::now());
+
+        client_a_mut.state = ClientState::WaitingForGame;
+        client_b_mut.state = ClientState::WaitingForGame;
+        // challenges to or from a player in a game can't be answered anymore
+        self.challenges.remove_involving(&client_a);
+        self.challenges.remove_involving(&client_b);
+
+        encode!(
+            client_a_mut.write,
+            ClientboundLobbyPacket,
+            ClientboundLobbyPacket::GameFound
+        );
+        encode!(
+            client_b_mut.write,
+            ClientboundLobbyPacket,
+            ClientboundLobbyPacket::GameFound
+        );
+        self.games.insert(game_id, lock_game);
+        true
+    }
+
+    /// Removes a finished game from the live games, tells its spectators how it ended and stores
+    /// its record.
+    async fn end_game(&mut self, game_id: i32, result: GameResult) {
+        let Some(game) = self.games.remove(&game_id) else {
+            return;
+        };
+        let mut write_game = game.write().await;
+        let record = write_game.take_record(result);
+        let read_game = write_game.downgrade();
+
+        let winner = record.winner().unwrap_or_default().to_string();
+        let abandoned = matches!(record.result, GameResult::Abandoned { .. });
+        for spectator in read_game.spectators.iter() {
+            if let Some(client) = self.clients.get_mut(spectator) {
+                client.spectating = None;
+                encode!(
+                    client.write,
+                    ClientboundSpectatorPacket,
+                    ClientboundSpectatorPacket::GameOver {
+                        winner: winner.clone(),
+                        abandoned
+                    }
+                );
+            }
+        }
+        if let Err(err) = self.record_sink.store(&record) {
+            log::error!("Failed to store the record of game {}: {}", record.id, err);
+        }
+        self.ratings.record(&record);
+        self.archive.push(record);
+        if !abandoned {
+            for player in [read_game.client_a, read_game.client_b] {
+                if let Some(client) = self.clients.get_mut(&player) {
+                    client.game = None;
+                }
+            }
+        }
+    }
 }
 
 pin_project! {
     pub struct Connect4ServerRead<'a> {
         acquired_names: &'a mut HashMap<String, Uuid>,
         clients: &'a mut HashMap<Uuid, ServerClient>,
-        client_receiver: &'a mut UnboundedReceiver<ClientAdd>
+        client_receiver: &'a mut UnboundedReceiver<ClientAdd>,
+        engine: &'a mut Engine,
+        answers: &'a mut Vec<Answer>
     }
 }
 
@@ -517,10 +1451,15 @@ impl<'a> Future for Connect4ServerRead<'a> {
                         state: ClientState::Login,
                         write: client.write,
                         game: None,
+                        spectating: None,
                         username: None,
+                        bot: false,
                         client_receiver: client.client_receiver,
                         queued_message: None,
                         in_game_since: None,
+                        chat_limiter: Default::default(),
+                        hint_pending: false,
+                        analysis_pending: false,
                     },
                 );
                 has_data_to_process = true;
@@ -531,6 +1470,11 @@ impl<'a> Future for Connect4ServerRead<'a> {
             }
         }
 
+        while let Poll::Ready(Some(answer)) = me.engine.poll_answer(cx) {
+            me.answers.push(answer);
+            has_data_to_process = true;
+        }
+
         if has_data_to_process {
             Poll::Ready(Ok(()))
         } else {